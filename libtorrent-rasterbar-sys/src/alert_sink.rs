@@ -0,0 +1,51 @@
+use crate::ffi::{Alert, Log};
+
+/// Receives the typed alerts of a session.
+///
+/// The callback is called from the alert polling thread of the session, so
+/// it should return quickly and must not panic.
+pub struct AlertSink {
+    callback: Box<dyn Fn(Alert) + Send + Sync>,
+    log_callback: Option<Box<dyn Fn(Log) + Send + Sync>>,
+}
+
+impl AlertSink {
+    pub fn new<F>(callback: F) -> Box<AlertSink>
+    where
+        F: Fn(Alert) + Send + Sync + 'static,
+    {
+        Box::new(AlertSink {
+            callback: Box::new(callback),
+            log_callback: None,
+        })
+    }
+
+    /// also receives the log messages of the session, see ``Log``.
+    /// Unlike the alerts they may come from any thread.
+    pub fn with_logs<F>(mut self: Box<Self>, callback: F) -> Box<AlertSink>
+    where
+        F: Fn(Log) + Send + Sync + 'static,
+    {
+        self.log_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// a sink which drops every alert
+    pub fn noop() -> Box<AlertSink> {
+        AlertSink::new(|_| {})
+    }
+
+    pub(crate) fn on_alert(&self, alert: Alert) {
+        (self.callback)(alert)
+    }
+
+    pub(crate) fn logs_enabled(&self) -> bool {
+        self.log_callback.is_some()
+    }
+
+    pub(crate) fn on_log(&self, log: Log) {
+        if let Some(callback) = &self.log_callback {
+            callback(log)
+        }
+    }
+}
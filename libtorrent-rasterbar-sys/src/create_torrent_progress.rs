@@ -0,0 +1,47 @@
+use std::sync::{
+    Mutex,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Receives the hashing progress of ``create_torrent``.
+///
+/// The callback is called with the number of hashed pieces and the total
+/// number of pieces. Returning false cancels the creation.
+pub struct CreateTorrentProgress {
+    callback: Mutex<Box<dyn FnMut(u32, u32) -> bool + Send>>,
+    cancelled: AtomicBool,
+}
+
+impl CreateTorrentProgress {
+    pub fn new<F>(callback: F) -> CreateTorrentProgress
+    where
+        F: FnMut(u32, u32) -> bool + Send + 'static,
+    {
+        CreateTorrentProgress {
+            callback: Mutex::new(Box::new(callback)),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// a progress which never cancels
+    pub fn noop() -> CreateTorrentProgress {
+        CreateTorrentProgress::new(|_, _| true)
+    }
+
+    /// true if the callback cancelled the creation
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn on_progress(&self, hashed: u32, num_pieces: u32) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        let mut callback = self.callback.lock().unwrap_or_else(|e| e.into_inner());
+        let go_on = callback(hashed, num_pieces);
+        if !go_on {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+        go_on
+    }
+}
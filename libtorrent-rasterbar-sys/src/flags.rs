@@ -1,10 +1,12 @@
 #![allow(non_camel_case_types)]
 
 use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
 
 bitflags! {
     /// libtorrent/session_handler.hpp
     /// These flags are defined in struct session_handle
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
     pub struct SaveStateFlags: u32 {
         /// saves settings (i.e. the settings_pack)
         /// static constexpr save_state_flags_t save_settings = 0_bit;
@@ -25,9 +27,63 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// libtorrent/alert.hpp
+    /// These flags are defined in namespace alert_category, every alert
+    /// belongs to one or more categories
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct AlertCategory: u32 {
+        /// alerts posted when operations fail
+        const error = 1 << 0;
+        /// alerts about events on peers
+        const peer = 1 << 1;
+        /// alerts about UPnP and NAT-PMP port mappings
+        const port_mapping = 1 << 2;
+        /// alerts about the storage, e.g. files renamed or moved
+        const storage = 1 << 3;
+        /// alerts about tracker requests and replies
+        const tracker = 1 << 4;
+        /// alerts about peers connecting and disconnecting
+        const connect = 1 << 5;
+        /// alerts about state changes of torrents
+        const status = 1 << 6;
+        /// alerts about peers blocked by the ip filter
+        const ip_block = 1 << 8;
+        /// alerts about limits hurting performance
+        const performance_warning = 1 << 9;
+        /// alerts about the dht
+        const dht = 1 << 10;
+        /// debug logging of the session
+        const session_log = 1 << 13;
+        /// debug logging of the torrents
+        const torrent_log = 1 << 14;
+        /// debug logging of the peers
+        const peer_log = 1 << 15;
+        /// alerts about incoming requests from peers
+        const incoming_request = 1 << 16;
+        /// debug logging of the dht
+        const dht_log = 1 << 17;
+        /// alerts about dht operations
+        const dht_operation = 1 << 18;
+        /// debug logging of port mappings
+        const port_mapping_log = 1 << 19;
+        /// debug logging of the piece picker
+        const picker_log = 1 << 20;
+        /// alerts about finished files
+        const file_progress = 1 << 21;
+        /// alerts about finished pieces
+        const piece_progress = 1 << 22;
+        /// alerts about uploaded blocks
+        const upload = 1 << 23;
+        /// alerts about downloaded blocks
+        const block_progress = 1 << 24;
+    }
+}
+
 bitflags! {
     /// libtorrent/torrent_flags.hpp
     /// These flags are defined in namespace torrent_flags
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct TorrentFlags: u64 {
         /// If ``seed_mode`` is set, libtorrent will assume that all files
         /// are present for this torrent and that they all match the hashes in
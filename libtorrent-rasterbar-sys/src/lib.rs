@@ -1,12 +1,135 @@
 pub mod flags;
 
+mod alert_sink;
+mod create_torrent_progress;
+mod resume_store;
 mod test_session;
 
+pub use alert_sink::AlertSink;
+pub use create_torrent_progress::CreateTorrentProgress;
+pub use resume_store::{ResumeStore, ResumeStoreBox};
+
 #[cxx::bridge(namespace = "libtorrent_wrapper")]
 pub mod ffi {
-    pub struct ParamPair<'a> {
-        pub key: &'a str,
-        pub value: &'a str,
+    /// libtorrent/settings_pack.hpp
+    ///
+    /// the type of a setting, settings_pack::string_type_base,
+    /// int_type_base or bool_type_base
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SettingType {
+        Str,
+        Int,
+        Bool,
+    }
+
+    /// a single entry of a settings_pack. Only the value matching ``kind`` is
+    /// meaningful.
+    #[derive(Debug, Clone)]
+    pub struct Setting {
+        pub name: String,
+        pub kind: SettingType,
+        pub str_value: String,
+        pub int_value: i32,
+        pub bool_value: bool,
+    }
+
+    /// a setting libtorrent rejected, ``name`` is empty if there is none
+    #[derive(Debug, Clone)]
+    pub struct SettingError {
+        pub name: String,
+        pub message: String,
+    }
+
+    /// a host (or ip address) and port, e.g. of a dht node or a peer
+    #[derive(Debug, Clone)]
+    pub struct HostPort {
+        pub host: String,
+        pub port: u16,
+    }
+
+    /// libtorrent/info_hash.hpp
+    ///
+    /// the v1 (SHA-1) and v2 (SHA-256) info-hashes of a torrent as hex, empty
+    /// if the torrent doesn't have one
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct InfoHashes {
+        pub v1: String,
+        pub v2: String,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct RenamedFile {
+        pub file_index: i32,
+        pub path: String,
+    }
+
+    /// libtorrent/add_torrent_params.hpp
+    ///
+    /// The add_torrent_params contains all the information in a .torrent file
+    /// along with all information necessary to add that torrent to a session.
+    ///
+    /// Only the fields that are set override the values loaded from the
+    /// torrent file, magnet link or resume data. Strings and lists are set if
+    /// they are not empty, the other fields if their ``has_*`` flag is true.
+    #[derive(Debug, Clone, Default)]
+    pub struct AddTorrentParams {
+        /// the path where the torrent is or will be stored.
+        pub save_path: String,
+
+        /// the name of the torrent, used until the metadata is received.
+        pub name: String,
+
+        /// the tracker urls, added in addition to the ones in the torrent file
+        /// or magnet link. ``tracker_tiers`` holds the tier of each tracker.
+        pub trackers: Vec<String>,
+        pub tracker_tiers: Vec<i32>,
+
+        /// dht nodes to bootstrap the DHT from, in addition to the ones in the
+        /// torrent file.
+        pub dht_nodes: Vec<HostPort>,
+
+        /// flags controlling aspects of this torrent and how it's added. See
+        /// TorrentFlags.
+        pub has_flags: bool,
+        pub flags: u64,
+
+        /// storage_mode_allocate = 0, storage_mode_sparse = 1
+        pub has_storage_mode: bool,
+        pub storage_mode: u8,
+
+        /// ``max_uploads``, ``max_connections``, ``upload_limit``,
+        /// ``download_limit`` correspond to the ``set_max_uploads()``,
+        /// ``set_max_connections()``, ``set_upload_limit()`` and
+        /// ``set_download_limit()`` functions on torrent_handle. -1 means
+        /// unlimited.
+        pub has_max_uploads: bool,
+        pub max_uploads: i32,
+        pub has_max_connections: bool,
+        pub max_connections: i32,
+        pub has_upload_limit: bool,
+        pub upload_limit: i32,
+        pub has_download_limit: bool,
+        pub download_limit: i32,
+
+        /// can be set to control the initial file priorities when adding a
+        /// torrent.
+        pub file_priorities: Vec<u8>,
+
+        /// this is a map of file indices in the torrent and new filenames to be
+        /// applied before the torrent is added.
+        pub renamed_files: Vec<RenamedFile>,
+
+        /// url seeds to be added to the torrent (BEP 17).
+        pub url_seeds: Vec<String>,
+
+        /// peers to add to the torrent, to be tried to be connected to as
+        /// bittorrent peers. ``host`` must be an ip address.
+        pub peers: Vec<HostPort>,
+
+        /// bencoded resume data, as written by ``save_resume_data``. If empty,
+        /// the resume file in the session's resume directory is used, if any.
+        pub resume_data: Vec<u8>,
     }
 
     #[derive(Debug)]
@@ -62,10 +185,11 @@ pub mod ffi {
         /// may be fewer in the last piece)
         pub blocks_per_piece: u32,
 
-        /// the info-hash of the torrent. For BitTorrent v2 support, use
-        /// ``info_hashes()`` to get an object that may hold both a v1 and v2
-        /// info-hash
+        /// the info-hash of the torrent, the v1 info-hash or the v2 info-hash
+        /// of a v2-only torrent, like ``Log::info_hash`` and the keys of the
+        /// ``ResumeStore``. Use ``info_hashes`` for both.
         pub info_hash: String,
+        pub info_hashes: InfoHashes,
 
         /// the number of files in the torrent
         pub num_files: u32,
@@ -95,6 +219,10 @@ pub mod ffi {
         /// Flags indicating torrent properties
         pub is_private: bool, // True if this is a private torrent
         pub is_i2p: bool, // True if this is an i2p torrent
+
+        /// the bencoded .torrent file, used to add the torrent to a session.
+        /// Only set by ``parse_torrent_info``.
+        pub torrent_file: Vec<u8>,
     }
 
     /// libtorrent/torrent_status.hpp
@@ -405,8 +533,9 @@ pub mod ffi {
         pub announcing_to_lsd: bool,
         pub announcing_to_dht: bool,
 
-        /// the info-hash for this torrent
+        /// the info-hash for this torrent, see ``TorrentInfo::info_hash``
         pub info_hash: String,
+        pub info_hashes: InfoHashes,
 
         /// the timestamps of the last time this torrent uploaded or downloaded
         /// payload to any peer.
@@ -817,10 +946,193 @@ pub mod ffi {
         pub verified: bool, // default 1
     }
 
+    /// a torrent as stored by a ``ResumeStore``
+    #[derive(Debug, Clone, Default)]
+    pub struct ResumeEntry {
+        /// the hex of the best info-hash of the torrent, the file name of the
+        /// entry in the default directory layout
+        pub info_hash: String,
+
+        /// the bencoded resume data
+        pub resume_data: Vec<u8>,
+
+        /// the previous generation of the resume data, empty if there is
+        /// none. Used if ``resume_data`` can't be read.
+        pub backup: Vec<u8>,
+
+        /// the .torrent file, empty if it isn't stored
+        pub torrent: Vec<u8>,
+    }
+
+    /// how a torrent whose resume data couldn't be read was added back
     #[derive(Debug)]
+    #[repr(u8)]
+    pub enum ResumeRecovery {
+        /// it wasn't, the torrent is missing from the session
+        NotRecovered,
+        /// from the previous generation of the resume data
+        Backup,
+        /// from the stored .torrent file into the recovery save path, its
+        /// files are checked
+        TorrentFile,
+    }
+
+    /// a torrent of the ``ResumeStore`` whose save path didn't exist when
+    /// the session was created, e.g. on a drive which wasn't mounted yet. It
+    /// stays in the store but isn't added to the session.
+    #[derive(Debug)]
+    pub struct OrphanedTorrent {
+        /// the hex of the best info-hash, as stored
+        pub info_hash: String,
+        pub info_hashes: InfoHashes,
+        /// empty for a magnet link without metadata
+        pub name: String,
+        pub save_path: String,
+    }
+
+    /// a torrent of the ``ResumeStore`` whose resume data couldn't be read
+    /// when the session was created
+    #[derive(Debug)]
+    pub struct ResumeLoadFailure {
+        /// the hex of the best info-hash, as stored
+        pub info_hash: String,
+        pub error: String,
+        pub recovery: ResumeRecovery,
+    }
+
+    /// a torrent whose resume data couldn't be saved
+    #[derive(Debug)]
+    pub struct SaveResumeFailure {
+        /// the hex of the best info-hash
+        pub info_hash: String,
+        pub error: String,
+    }
+
+    /// the outcome of ``Session::save_all_resume_data``, by the hex of the
+    /// best info-hash of the torrents
+    #[derive(Debug, Default)]
+    pub struct SaveResumeSummary {
+        pub saved: Vec<String>,
+        pub failed: Vec<SaveResumeFailure>,
+        /// the torrents whose resume data didn't arrive before the timeout
+        pub timed_out: Vec<String>,
+    }
+
+    /// the outcome of ``Session::shutdown``
+    #[derive(Debug, Default)]
+    pub struct ShutdownReport {
+        pub resume_data: SaveResumeSummary,
+        /// why the session state couldn't be saved, empty if it was
+        pub state_error: String,
+    }
+
+    /// what happens to a log message when the log is full
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum LogOverflowPolicy {
+        /// the oldest message is dropped
+        DropOldest,
+        /// the new message is dropped
+        DropNewest,
+        /// the alert thread waits until ``get_logs`` makes room, other
+        /// threads drop the new message
+        Block,
+    }
+
+    /// how ``create_session`` sets up the session, besides its settings
+    #[derive(Debug)]
+    pub struct SessionOptions {
+        pub save_state_flags: u32,
+        pub session_state_path: String,
+        pub recovery_save_path: String,
+        pub log_size: u32,
+        pub log_overflow_policy: LogOverflowPolicy,
+        pub alert_poll_interval_ms: u32,
+        pub session_stats_interval_ms: u32,
+        pub torrent_updates_interval_ms: u32,
+        pub dht_stats_interval_ms: u32,
+        pub autosave_interval_ms: u32,
+    }
+
+    /// the stats the alert thread posts periodically
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum StatsPost {
+        /// ``post_session_stats()``, a session_stats_alert
+        SessionStats,
+        /// ``post_torrent_updates()``, a state_update_alert
+        TorrentUpdates,
+        /// ``post_dht_stats()``, a dht_stats_alert
+        DhtStats,
+    }
+
+    /// the severity of a ``Log``. Alerts are mapped by their
+    /// category: error alerts are errors, performance warnings warnings,
+    /// peer and picker logs traces, the other logs debug messages and the
+    /// rest info.
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum LogLevel {
+        Error,
+        Warn,
+        Info,
+        Debug,
+        Trace,
+    }
+
+    /// where a ``Log`` comes from
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum LogTarget {
+        /// a diagnostic of the wrapper itself, e.g. a failed resume data save
+        Wrapper,
+        /// a session alert, e.g. a session_log_alert
+        Session,
+        /// a torrent alert, e.g. a torrent_log_alert
+        Torrent,
+        /// a peer alert, e.g. a peer_log_alert
+        Peer,
+        /// a dht alert, e.g. a dht_log_alert
+        Dht,
+        Tracker,
+        Storage,
+        PortMapping,
+    }
+
+    /// a log message, of an alert which isn't handled by the session or a
+    /// diagnostic of the wrapper
+    #[derive(Debug, Clone)]
     pub struct Log {
         pub message: String,
+        /// milliseconds
         pub timestamp: i64,
+        /// the type of the alert, e.g. ``torrent_log``, empty for a
+        /// diagnostic of the wrapper
+        pub alert_type: String,
+        /// the ``alert_category`` bits of the alert, see ``AlertCategory``
+        pub category: u32,
+        pub level: LogLevel,
+        pub target: LogTarget,
+        /// the hex of the v1 info-hash of the torrent, or of the v2 one if
+        /// it has none, empty if there is no torrent
+        pub info_hash: String,
+        /// empty if there is no torrent or its name isn't known yet
+        pub torrent_name: String,
+        /// the ip:port of the peer, empty if there is none
+        pub peer: String,
+    }
+
+    /// selects the log messages ``get_logs_filtered`` drains
+    #[derive(Debug, Clone)]
+    pub struct LogFilter {
+        /// the hex of the v1 or v2 info-hash of a torrent, empty for all
+        /// messages
+        pub info_hash: String,
+        /// the messages with any of these ``alert_category`` bits, 0 for all
+        /// messages. Diagnostics of the wrapper have no category.
+        pub categories: u32,
+        /// the least severe level
+        pub max_level: LogLevel,
     }
 
     #[derive(Debug)]
@@ -831,6 +1143,244 @@ pub mod ffi {
         pub prev_timestamp: i64,
     }
 
+    /// libtorrent/error_code.hpp
+    ///
+    /// a flattened ``lt::error_code``. ``value`` is 0 if there is no error.
+    #[derive(Debug, Clone)]
+    pub struct ErrorCode {
+        /// the name of the error category, e.g. "libtorrent", "system", "bdecode"
+        pub category: String,
+        pub value: i32,
+        pub message: String,
+    }
+
+    /// the kind of an ``OperationError``
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum OperationErrorKind {
+        /// the exception wasn't thrown by a failed operation
+        None,
+        /// the torrent was removed
+        InvalidHandle,
+        InvalidInfoHash,
+        InvalidFileIndex,
+        /// the metadata of a magnet link wasn't received yet
+        NoMetadata,
+        /// libtorrent reported ``error``
+        ErrorCode,
+    }
+
+    /// why an operation of the session or of a torrent handle threw, see
+    /// ``take_operation_error``
+    #[derive(Debug, Clone)]
+    pub struct OperationError {
+        pub kind: OperationErrorKind,
+        /// the rejected info-hash for ``InvalidInfoHash``
+        pub info_hash: String,
+        /// the rejected index for ``InvalidFileIndex``
+        pub file_index: i32,
+        /// the error for ``ErrorCode``
+        pub error: ErrorCode,
+    }
+
+    /// the kind of alerts that are forwarded to the ``AlertSink`` as an ``Alert``
+    #[derive(Debug)]
+    pub enum AlertKind {
+        /// add_torrent_alert
+        TorrentAdded,
+        /// torrent_removed_alert
+        TorrentRemoved,
+        /// torrent_finished_alert
+        TorrentFinished,
+        /// torrent_paused_alert
+        TorrentPaused,
+        /// torrent_resumed_alert
+        TorrentResumed,
+        /// torrent_error_alert
+        TorrentError,
+        /// state_changed_alert
+        StateChanged,
+        /// metadata_received_alert
+        MetadataReceived,
+        /// tracker_reply_alert
+        TrackerReply,
+        /// tracker_error_alert
+        TrackerError,
+        /// file_completed_alert
+        FileCompleted,
+        /// file_error_alert
+        FileError,
+        /// hash_failed_alert
+        HashFailed,
+        /// storage_moved_alert
+        StorageMoved,
+        /// storage_moved_failed_alert
+        StorageMovedFailed,
+        /// peer_info_alert, the reply of ``post_peer_info``
+        PeerInfo,
+        /// file_progress_alert, the reply of ``post_file_progress``
+        FileProgress,
+        /// piece_info_alert, the reply of ``post_download_queue``
+        PieceInfo,
+        /// piece_availability_alert, the reply of ``post_piece_availability``
+        PieceAvailability,
+        /// tracker_list_alert, the reply of ``post_trackers``
+        TrackerList,
+        /// listen_succeeded_alert
+        ListenSucceeded,
+        /// listen_failed_alert
+        ListenFailed,
+    }
+
+    /// libtorrent/alert_types.hpp
+    ///
+    /// a flattened libtorrent alert. Which fields are meaningful depends on
+    /// ``kind``, the others are left empty (or -1 for indices).
+    #[derive(Debug, Clone)]
+    pub struct Alert {
+        pub kind: AlertKind,
+
+        /// milliseconds
+        pub timestamp: i64,
+
+        /// the info-hashes of the torrent the alert belongs to, empty for
+        /// session-level alerts
+        pub info_hashes: InfoHashes,
+
+        /// the name of the torrent the alert belongs to
+        pub torrent_name: String,
+
+        /// the index of the file, for file_completed_alert
+        pub file_index: i32, // default -1
+
+        /// the index of the piece, for hash_failed_alert
+        pub piece_index: i32, // default -1
+
+        /// the file name of torrent_error_alert and file_error_alert, the new
+        /// storage path of storage_moved_alert and the failed path of
+        /// storage_moved_failed_alert
+        pub path: String,
+
+        /// the tracker url of tracker_reply_alert and tracker_error_alert
+        pub tracker_url: String,
+
+        /// the number of peers returned by the tracker in tracker_reply_alert,
+        /// or the number of times in a row a tracker has failed in
+        /// tracker_error_alert
+        pub count: i32,
+
+        /// the current and previous torrent state of state_changed_alert,
+        /// see ``State``
+        pub state: u8,
+        pub prev_state: u8,
+
+        /// the listen address as ip:port for listen_succeeded_alert and
+        /// listen_failed_alert
+        pub endpoint: String,
+
+        /// the error of the alert. ``value`` is 0 if the alert carries no error
+        pub error: ErrorCode,
+
+        /// the human readable message of the alert, as returned by
+        /// ``alert::message()``
+        pub message: String,
+    }
+
+    /// why adding a torrent failed
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum AddTorrentError {
+        None,
+        /// the torrent is already in the session
+        Duplicate,
+        /// the torrent file, its info dictionary or the resume data is invalid
+        InvalidMetadata,
+        /// the save path is empty or can't be created
+        InvalidSavePath,
+        /// there is no orphaned torrent with the info-hash, see
+        /// ``Session::relocate_orphaned_torrent``
+        NotOrphaned,
+        Other,
+    }
+
+    /// the BitTorrent versions of a created torrent
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum TorrentVersion {
+        /// both v1 and v2 metadata, usable by all clients
+        Hybrid,
+        V1Only,
+        V2Only,
+    }
+
+    /// libtorrent/create_torrent.hpp
+    ///
+    /// the parameters of ``create_torrent``
+    #[derive(Debug, Clone)]
+    pub struct CreateTorrentParams {
+        /// the file or directory to create the torrent from
+        pub path: String,
+
+        /// the size of each piece in bytes. It must be a power of 2 and a
+        /// minimum of 16 kiB. 0 picks a piece size based on the total size.
+        pub piece_size: i32,
+
+        pub version: TorrentVersion,
+
+        /// the tracker urls and the tier of each tracker
+        pub trackers: Vec<String>,
+        pub tracker_tiers: Vec<i32>,
+
+        /// url seeds (BEP 19)
+        pub web_seeds: Vec<String>,
+
+        pub comment: String,
+        pub creator: String,
+
+        /// sets the private flag of the torrent
+        pub is_private: bool,
+    }
+
+    /// why parsing a .torrent file failed
+    #[derive(Debug)]
+    #[repr(u8)]
+    pub enum TorrentInfoError {
+        None,
+        /// the data is not valid bencoding
+        InvalidBencode,
+        /// the torrent has no info dictionary
+        MissingInfoDict,
+        /// the piece length is missing or invalid
+        InvalidPieceLength,
+        Other,
+    }
+
+    /// the result of ``parse_torrent_info``
+    pub struct ParseTorrentInfoResult {
+        pub info: TorrentInfo,
+        pub error_kind: TorrentInfoError,
+        pub error: ErrorCode,
+    }
+
+    /// the result of ``Session::add_torrent`` and ``Session::add_magnet``
+    pub struct AddTorrentResult {
+        /// null if the torrent was added asynchronously or if it failed
+        pub handle: UniquePtr<TorrentHandle>,
+
+        /// the info-hash used to look up the torrent and in alerts, see
+        /// ``TorrentInfo::info_hash``
+        pub info_hash: String,
+
+        /// the v1 (SHA-1) and v2 (SHA-256) info-hashes
+        pub info_hashes: InfoHashes,
+
+        pub error_kind: AddTorrentError,
+        pub error: ErrorCode,
+
+        /// the save path the torrent was added with
+        pub save_path: String,
+    }
+
     unsafe extern "C++" {
         include!("libtorrent-rasterbar-sys/wrap/wrapper.hpp");
 
@@ -838,76 +1388,182 @@ pub mod ffi {
         type TorrentHandle;
 
         /// Create a new session
-        /// The default values of the session settings are set for a regular
-        /// bittorrent client running on a desktop system. There are functions that
-        /// can set the session settings to pre set settings for other environments.
-        /// These can be used for the basis, and should be tweaked to fit your needs
-        /// better.
-        ///
-        /// ``min_memory_usage`` returns settings that will use the minimal amount of
-        /// RAM, at the potential expense of upload and download performance. It
-        /// adjusts the socket buffer sizes, disables the disk cache, lowers the send
-        /// buffer watermarks so that each connection only has at most one block in
-        /// use at any one time. It lowers the outstanding blocks send to the disk
-        /// I/O thread so that connections only have one block waiting to be flushed
-        /// to disk at any given time. It lowers the max number of peers in the peer
-        /// list for torrents. It performs multiple smaller reads when it hashes
-        /// pieces, instead of reading it all into memory before hashing.
         ///
-        /// This configuration is intended to be the starting point for embedded
-        /// devices. It will significantly reduce memory usage.
-        ///
-        /// ``high_performance_seed`` returns settings optimized for a seed box,
-        /// serving many peers and that doesn't do any downloading. It has a 128 MB
-        /// disk cache and has a limit of 400 files in its file pool. It support fast
-        /// upload rates by allowing large send buffers.
-        ///
-        /// ``session_param_list`` is a list of key-value pairs that will be used to
-        /// override the default values.
+        /// ``settings`` are applied over the session state loaded from
+        /// ``session_state_path``. The presets (``lt::min_memory_usage()``,
+        /// ``lt::high_performance_seed()``) are resolved by the caller, see
+        /// ``min_memory_usage_settings``.
         /// The definations and default values of the session settings are in
         /// libtorrent/include/settings_pack.hpp
         /// libtorrent/src/settings_pack.cpp
+        ///
+        /// The session state is loaded from and saved to
+        /// ``options.session_state_path``, its parent directory is created if it
+        /// doesn't exist. If it's empty the session state is kept in
+        /// ``resume_store``, if there is none it's neither loaded nor saved.
+        /// The torrents of ``resume_store`` are added back to the session and
+        /// their resume data is saved there, ``ResumeStoreBox::none()``
+        /// disables it. A torrent whose resume data and its backup can't be
+        /// read is added from its stored .torrent file into
+        /// ``options.recovery_save_path`` and checked, an empty path doesn't
+        /// add it.
+        ///
+        /// Alerts are handled as soon as they're posted, the alert thread
+        /// wakes up at least every ``options.alert_poll_interval_ms``. The
+        /// session stats, torrent updates and dht stats are posted every
+        /// ``session_stats_interval_ms``, ``torrent_updates_interval_ms``
+        /// and ``dht_stats_interval_ms``, 0 disables a post. Up to
+        /// ``log_size`` log messages are kept until they're drained, when
+        /// there are more ``log_overflow_policy`` applies. Every
+        /// ``autosave_interval_ms`` the resume data of the torrents which
+        /// need it is saved, and the session state, 0 disables it.
+        ///
+        /// ``alert_sink`` receives the typed alerts of the session.
         fn create_session(
-            min_memory_usage: bool,
-            high_performance_seed: bool,
-            session_param_list: &[ParamPair],
-            save_state_flags: u32,
-            session_state_path: &str,
-            resume_dir: &str,
-            torrent_dir: &str,
-            log_size: u32,
+            settings: &[Setting],
+            options: &SessionOptions,
+            resume_store: Box<ResumeStoreBox>,
+            alert_sink: Box<AlertSink>,
         ) -> Result<UniquePtr<Session>>;
 
+        /// parses a setting from its string form, as in a config file. Besides
+        /// plain values this accepts the names of enum values (e.g.
+        /// ``pe_forced``), on/off for bools and a comma separated list of
+        /// categories for ``alert_mask``.
+        fn parse_setting(name: &str, value: &str) -> Result<Setting>;
+
         // Session impl
         // {{{
-        fn add_torrent(self: &Session, torrent_path: &str, torrent_param_list: &[ParamPair]) -> Result<()>;
+        /// every setting known to libtorrent with its default value, as
+        /// returned by ``lt::default_settings()``
+        fn default_settings() -> Vec<Setting>;
+
+        /// the settings changed by ``lt::min_memory_usage()``
+        fn min_memory_usage_settings() -> Vec<Setting>;
+
+        /// the settings changed by ``lt::high_performance_seed()``
+        fn high_performance_seed_settings() -> Vec<Setting>;
+
+        /// parses the bencoded contents of a .torrent file. This doesn't need
+        /// a session.
+        fn parse_torrent_info(data: &[u8]) -> ParseTorrentInfoResult;
+
+        /// creates a torrent from a file or directory and returns the
+        /// bencoded .torrent file. Hashing the pieces reads all files, the
+        /// progress is reported to ``progress``.
+        fn create_torrent(params: &CreateTorrentParams, progress: &CreateTorrentProgress) -> Result<Vec<u8>>;
+
+        /// classifies the error of an add_torrent_alert
+        fn classify_add_torrent_error(error: &ErrorCode) -> AddTorrentError;
+
+        /// the error of the exception last passed to rust on this thread. Its
+        /// kind is ``None`` once taken, or if the exception wasn't thrown by a
+        /// failed operation, e.g. by a ``ResumeStore``.
+        fn take_operation_error() -> OperationError;
+
+        /// adds a torrent and blocks until it's added (or failed to be added).
+        fn add_torrent(self: &Session, torrent_path: &str, params: &AddTorrentParams) -> Result<AddTorrentResult>;
+
+        fn add_magnet(self: &Session, magnet_uri: &str, params: &AddTorrentParams) -> Result<AddTorrentResult>;
+
+        /// like ``add_torrent`` and ``add_magnet``, but returns as soon as the
+        /// torrent is queued. The outcome is reported by an add_torrent_alert
+        /// with the returned ``info_hash``. Only errors found before queuing
+        /// (e.g. the save path) are set in the result.
+        fn async_add_torrent(self: &Session, torrent_path: &str, params: &AddTorrentParams)
+        -> Result<AddTorrentResult>;
+
+        fn async_add_magnet(self: &Session, magnet_uri: &str, params: &AddTorrentParams) -> Result<AddTorrentResult>;
+
+        /// adds a torrent from the bencoded contents of a .torrent file.
+        fn add_torrent_bytes(self: &Session, data: &[u8], params: &AddTorrentParams) -> Result<AddTorrentResult>;
 
-        fn add_magnet(self: &Session, magnet_uri: &str, torrent_param_list: &[ParamPair]) -> Result<()>;
+        fn async_add_torrent_bytes(self: &Session, data: &[u8], params: &AddTorrentParams) -> Result<AddTorrentResult>;
 
-        fn remove_torrent(self: &Session, info_hash_str: &str, delete_files: bool);
+        /// ``info_hash_str`` is the hex encoded v1 or v2 info-hash, or the
+        /// truncated v2 info-hash. Fails for unknown info-hashes.
+        fn remove_torrent(self: &Session, info_hash_str: &str, delete_files: bool) -> Result<()>;
 
         fn get_stats(self: &Session) -> TwoSessionStats;
 
-        fn get_torrent_handle(self: &Session, info_hash_str: &str) -> UniquePtr<TorrentHandle>;
+        /// looks up a torrent by info-hash like ``remove_torrent``, fails if
+        /// there is no such torrent
+        fn get_torrent_handle(self: &Session, info_hash_str: &str) -> Result<UniquePtr<TorrentHandle>>;
 
         fn pause(self: &Session);
         fn resume(self: &Session);
         fn is_paused(self: &Session) -> bool;
 
+        /// applies the settings to the running session, settings that are not
+        /// in ``settings`` keep their value. Nothing is applied if a setting is
+        /// unknown or its value has the wrong type, that setting is returned.
+        fn apply_settings(self: &Session, settings: &[Setting]) -> SettingError;
+
+        /// the current value of every setting of the session
+        fn get_settings(self: &Session) -> Vec<Setting>;
+
         /// Get the list of torrents in the session
         fn get_torrents(self: &Session) -> Vec<TorrentInfo>;
 
         /// Get the list of all torrent status in the session
         fn get_all_torrent_status(self: &Session) -> Vec<TorrentStatus>;
 
-        fn get_logs(self: Pin<&mut Session>) -> Vec<Log>;
+        /// drains the log messages, may be called from any thread
+        fn get_logs(self: &Session) -> Vec<Log>;
+
+        /// drains the log messages matching ``filter``, the others are kept
+        fn get_logs_filtered(self: &Session, filter: &LogFilter) -> Vec<Log>;
+
+        /// the number of log messages dropped because the log was full
+        fn dropped_logs(self: &Session) -> u64;
+
+        /// how often ``post`` is posted by the alert thread, 0 disables it
+        fn set_post_interval(self: &Session, post: StatsPost, interval_ms: u32);
+
+        /// the torrents of the ``ResumeStore`` whose resume data couldn't be
+        /// read when the session was created
+        fn resume_load_failures(self: &Session) -> Vec<ResumeLoadFailure>;
+
+        /// the torrents of the ``ResumeStore`` which weren't added because
+        /// their save path didn't exist
+        fn orphaned_torrents(self: &Session) -> Vec<OrphanedTorrent>;
+
+        /// adds the orphaned torrents whose save path exists now, with a
+        /// result for each of them. The save paths aren't created. A torrent
+        /// which fails to be added stays orphaned.
+        fn retry_orphaned_torrents(self: &Session) -> Vec<AddTorrentResult>;
+
+        /// adds an orphaned torrent with a new save path, which is created
+        /// if it doesn't exist. Accepts the v1 or v2 info-hash, the error is
+        /// ``AddTorrentError::NotOrphaned`` if the torrent isn't orphaned.
+        fn relocate_orphaned_torrent(self: &Session, info_hash_str: &str, save_path: &str) -> Result<AddTorrentResult>;
+
+        /// removes an orphaned torrent from the ``ResumeStore``, returns
+        /// false if it isn't orphaned
+        fn forget_orphaned_torrent(self: &Session, info_hash_str: &str) -> Result<bool>;
+
+        /// requests the resume data of every torrent and blocks until it's
+        /// saved to the ``ResumeStore`` or ``timeout_ms`` passed. Must not be
+        /// called from the ``AlertSink``.
+        fn save_all_resume_data(self: &Session, timeout_ms: u32) -> SaveResumeSummary;
+
+        /// saves the session state to the session state path or the
+        /// ``ResumeStore``
+        fn save_state(self: &Session) -> Result<()>;
+
+        /// pauses the session, waits up to ``timeout_ms`` for the resume
+        /// data of every torrent, stops the alert thread and saves the
+        /// session state. Nothing but dropping the session may follow,
+        /// which then doesn't save anything. A session which is dropped
+        /// without it is shut down with a timeout of 30s.
+        fn shutdown(self: &Session, timeout_ms: u32) -> ShutdownReport;
         // }}}
 
         // TorrentHandle impl
         // {{{
         fn is_valid(self: &TorrentHandle) -> bool;
 
-        fn add_tracker(self: &TorrentHandle, tracker_url: &str, tier: u8);
+        fn add_tracker(self: &TorrentHandle, tracker_url: &str, tier: u8) -> Result<()>;
 
         /// ``scrape_tracker()`` will send a scrape request to a tracker. By
         /// default (``idx`` = -1) it will scrape the last working tracker. If
@@ -920,7 +1576,7 @@ pub mod ffi {
         /// ``num_incomplete`` fields in the torrent_status struct once it
         /// completes. When it completes, it will generate a scrape_reply_alert.
         /// If it fails, it will generate a scrape_failed_alert.
-        fn scrape_tracker(self: &TorrentHandle);
+        fn scrape_tracker(self: &TorrentHandle) -> Result<()>;
 
         /// ``force_recheck`` puts the torrent back in a state where it assumes to
         /// have no resume data. All peers will be disconnected and the torrent
@@ -930,7 +1586,7 @@ pub mod ffi {
         /// will start connecting to peers again, as normal.
         /// The torrent will be placed last in queue, i.e. its queue position
         /// will be the highest of all torrents in the session.
-        fn force_recheck(self: &TorrentHandle);
+        fn force_recheck(self: &TorrentHandle) -> Result<()>;
 
         /// ``force_reannounce()`` will force this torrent to do another tracker
         /// request, to receive new peers. The ``seconds`` argument specifies how
@@ -952,11 +1608,11 @@ pub mod ffi {
         ///
         /// ``force_lsd_announce`` will announce the torrent on LSD
         /// immediately.
-        fn force_reannounce(self: &TorrentHandle);
-        fn force_dht_announce(self: &TorrentHandle);
-        fn force_lsd_announce(self: &TorrentHandle);
+        fn force_reannounce(self: &TorrentHandle) -> Result<()>;
+        fn force_dht_announce(self: &TorrentHandle) -> Result<()>;
+        fn force_lsd_announce(self: &TorrentHandle) -> Result<()>;
 
-        fn clear_error(self: &TorrentHandle);
+        fn clear_error(self: &TorrentHandle) -> Result<()>;
 
         /// ``set_upload_limit`` will limit the upload bandwidth used by this
         /// particular torrent to the limit you set. It is given as the number of
@@ -972,15 +1628,15 @@ pub mod ffi {
         /// setting, for upload and download, respectively.
         ///
         /// Local peers are not rate limited by default. see peer-classes_.
-        fn set_upload_limit(self: &TorrentHandle, limit: i32);
-        fn upload_limit(self: &TorrentHandle) -> i32;
-        fn set_download_limit(self: &TorrentHandle, limit: i32);
-        fn download_limit(self: &TorrentHandle) -> i32;
+        fn set_upload_limit(self: &TorrentHandle, limit: i32) -> Result<()>;
+        fn upload_limit(self: &TorrentHandle) -> Result<i32>;
+        fn set_download_limit(self: &TorrentHandle, limit: i32) -> Result<()>;
+        fn download_limit(self: &TorrentHandle) -> Result<i32>;
 
         /// This will disconnect all peers and clear the peer list for this
         /// torrent. New peers will have to be acquired before resuming, from
         /// trackers, DHT or local service discovery, for example.
-        fn clear_peers(self: &TorrentHandle);
+        fn clear_peers(self: &TorrentHandle) -> Result<()>;
 
         /// ``set_max_uploads()`` sets the maximum number of peers that's unchoked
         /// at the same time on this torrent. If you set this to -1, there will be
@@ -989,8 +1645,8 @@ pub mod ffi {
         /// settings_pack.
         ///
         /// ``max_uploads()`` returns the current settings.
-        fn set_max_uploads(self: &TorrentHandle, max_uploads: i32);
-        fn max_uploads(self: &TorrentHandle) -> i32;
+        fn set_max_uploads(self: &TorrentHandle, max_uploads: i32) -> Result<()>;
+        fn max_uploads(self: &TorrentHandle) -> Result<i32>;
 
         /// ``set_max_connections()`` sets the maximum number of connection this
         /// torrent will open. If all connections are used up, incoming
@@ -1001,8 +1657,8 @@ pub mod ffi {
         /// ``connections_limit`` in settings_pack.
         ///
         /// ``max_connections()`` returns the current settings.
-        fn set_max_connections(self: &TorrentHandle, max_connections: i32);
-        fn max_connections(self: &TorrentHandle) -> i32;
+        fn set_max_connections(self: &TorrentHandle, max_connections: i32) -> Result<()>;
+        fn max_connections(self: &TorrentHandle) -> Result<i32>;
 
         /// ``pause()``, and ``resume()`` will disconnect all peers and reconnect
         /// all peers respectively. When a torrent is paused, it will however
@@ -1023,8 +1679,8 @@ pub mod ffi {
         /// 	not auto-managed first. Torrents are auto-managed by default when added
         ///
         /// 	to the session. For more information, see queuing_.
-        fn pause(self: &TorrentHandle, flags: u8);
-        fn resume(self: &TorrentHandle);
+        fn pause(self: &TorrentHandle, flags: u8) -> Result<()>;
+        fn resume(self: &TorrentHandle) -> Result<()>;
 
         /// sets and gets the torrent state flags. See torrent_flags_t.
         /// The ``set_flags`` overload that take a mask will affect all
@@ -1039,10 +1695,10 @@ pub mod ffi {
         /// The `seed_mode` flag is special, it can only be cleared once the
         /// torrent has been added, and it can only be set as part of the
         /// add_torrent_params flags, when adding the torrent.
-        fn flags(self: &TorrentHandle) -> u64;
-        fn set_flags(self: &TorrentHandle, flags: u64);
-        fn set_flags_with_mask(self: &TorrentHandle, flags: u64, mask: u64);
-        fn unset_flags(self: &TorrentHandle, flags: u64);
+        fn flags(self: &TorrentHandle) -> Result<u64>;
+        fn set_flags(self: &TorrentHandle, flags: u64) -> Result<()>;
+        fn set_flags_with_mask(self: &TorrentHandle, flags: u64, mask: u64) -> Result<()>;
+        fn unset_flags(self: &TorrentHandle, flags: u64) -> Result<()>;
 
         /// ``index`` must be in the range [0, number_of_files).
         ///
@@ -1082,26 +1738,94 @@ pub mod ffi {
         /// Moving data from a file into the part file is currently not
         /// supported. If a file has its priority set to 0 *after* it has already
         /// been created, it will not be moved into the partfile.
-        fn set_file_priority(self: &TorrentHandle, index: i32, priority: u8);
-        fn get_file_priority(self: &TorrentHandle, index: i32) -> u8;
-        fn set_prioritize_files(self: &TorrentHandle, files: &[u8]);
-        fn get_file_priorities(self: &TorrentHandle) -> Vec<u8>;
+        fn set_file_priority(self: &TorrentHandle, index: i32, priority: u8) -> Result<()>;
+        fn get_file_priority(self: &TorrentHandle, index: i32) -> Result<u8>;
+        fn set_prioritize_files(self: &TorrentHandle, files: &[u8]) -> Result<()>;
+        fn get_file_priorities(self: &TorrentHandle) -> Result<Vec<u8>>;
+
+        /// the metadata of the torrent, ``torrent_file`` is left empty
+        fn get_torrent_info(self: &TorrentHandle) -> Result<TorrentInfo>;
+
+        /// the bencoded .torrent file of the torrent, written on every call
+        fn get_torrent_file(self: &TorrentHandle) -> Result<Vec<u8>>;
+
+        /// the ``post_*()`` functions only request the information from
+        /// libtorrent. The reply alert is cached by the session and forwarded
+        /// to the ``AlertSink``, after which it can be read with ``peers()``,
+        /// ``file_progress()``, ``piece_info()``, ``piece_availability()`` and
+        /// ``trackers()``.
+        fn post_peer_info(self: &TorrentHandle) -> Result<()>;
+        fn post_file_progress(self: &TorrentHandle, piece_granularity: bool) -> Result<()>;
+        fn post_download_queue(self: &TorrentHandle) -> Result<()>;
+        fn post_piece_availability(self: &TorrentHandle) -> Result<()>;
+        fn post_trackers(self: &TorrentHandle) -> Result<()>;
+
+        fn peers(self: &TorrentHandle) -> Vec<PeerInfo>;
+        fn file_progress(self: &TorrentHandle) -> Vec<i64>;
+        fn piece_info(self: &TorrentHandle) -> PieceInfo;
+        fn piece_availability(self: &TorrentHandle) -> Vec<i32>;
+        fn trackers(self: &TorrentHandle) -> Vec<AnnounceEntry>;
+
+        /// the info-hash of the torrent as hex, see ``TorrentInfo::info_hash``
+        fn info_hash(self: &TorrentHandle) -> Result<String>;
+
+        /// the v1 and v2 info-hashes of the torrent. A torrent added by a v1
+        /// magnet link gets its v2 info-hash once the metadata is received.
+        fn info_hashes(self: &TorrentHandle) -> Result<InfoHashes>;
+
+        fn get_torrent_status(self: &TorrentHandle) -> Result<TorrentStatus>;
+
+        fn make_magnet_uri(self: &TorrentHandle) -> Result<String>;
+        // }}}
+    }
 
-        fn get_torrent_info(self: &TorrentHandle) -> TorrentInfo;
+    extern "Rust" {
+        type AlertSink;
 
-        fn get_peers(self: &TorrentHandle) -> Vec<PeerInfo>;
+        /// called from the alert polling thread of the session for every
+        /// alert of a kind listed in ``AlertKind``, after the state caches of
+        /// the session are updated and unlocked
+        fn on_alert(self: &AlertSink, alert: Alert);
 
-        fn get_file_progress(self: &TorrentHandle, piece_granularity: bool) -> Vec<i64>;
+        /// whether ``on_log`` should be called, building the records isn't
+        /// free
+        fn logs_enabled(self: &AlertSink) -> bool;
 
-        fn get_piece_info(self: &TorrentHandle) -> PieceInfo;
+        /// called for every alert which goes to the log and every diagnostic
+        /// of the wrapper, which prints nothing itself. May be called from
+        /// any thread.
+        fn on_log(self: &AlertSink, log: Log);
 
-        fn get_piece_availability(self: &TorrentHandle) -> Vec<i32>;
+        type CreateTorrentProgress;
 
-        fn get_trackers(self: &TorrentHandle) -> Vec<AnnounceEntry>;
+        /// called while hashing the pieces in ``create_torrent``, returns
+        /// false to cancel
+        fn on_progress(self: &CreateTorrentProgress, hashed: u32, num_pieces: u32) -> bool;
 
-        fn get_torrent_status(self: &TorrentHandle) -> TorrentStatus;
+        type ResumeStoreBox;
 
-        fn make_magnet_uri(self: &TorrentHandle) -> String;
-        // }}}
+        /// false for ``ResumeStoreBox::none()``, nothing is loaded or saved
+        fn enabled(self: &ResumeStoreBox) -> bool;
+
+        /// every stored torrent, called when the session is created
+        fn load_all(self: &ResumeStoreBox) -> Result<Vec<ResumeEntry>>;
+
+        /// the resume data of a torrent, empty if there is none
+        fn load(self: &ResumeStoreBox, info_hash: &str) -> Result<Vec<u8>>;
+
+        /// stores the resume data of a torrent, ``torrent`` is the .torrent
+        /// file or empty if the metadata isn't known
+        fn save(self: &ResumeStoreBox, info_hash: &str, resume_data: &[u8], torrent: &[u8]) -> Result<()>;
+
+        /// removes a torrent from the store
+        fn remove(self: &ResumeStoreBox, info_hash: &str) -> Result<()>;
+
+        /// the session state, empty if there is none. Used if the session
+        /// has no ``session_state_path``.
+        fn load_session_state(self: &ResumeStoreBox) -> Result<Vec<u8>>;
+
+        /// stores the session state if the session has no
+        /// ``session_state_path``
+        fn save_session_state(self: &ResumeStoreBox, state: &[u8]) -> Result<()>;
     }
 }
@@ -0,0 +1,143 @@
+use std::{io, sync::Arc};
+
+use crate::ffi::ResumeEntry;
+
+/// Where a session keeps the resume data of its torrents.
+///
+/// The session calls ``load_all`` when it's created and adds every entry
+/// back, ``save`` whenever libtorrent posts new resume data for a torrent
+/// and ``remove`` when a torrent is removed. Torrents are identified by the
+/// hex of their best info-hash, the v1 info-hash if there is one, otherwise
+/// the v2 info-hash. A torrent added from a v2 magnet link is saved under its
+/// v2 info-hash until its metadata shows it's hybrid, then it's saved under
+/// its v1 info-hash and the v2 entry is removed.
+///
+/// The methods are called from the alert polling thread of the session and
+/// from the threads calling into the session, so a store must be
+/// thread-safe and should not block for long.
+pub trait ResumeStore: Send + Sync {
+    /// every stored torrent
+    fn load_all(&self) -> io::Result<Vec<ResumeEntry>>;
+
+    /// the bencoded resume data of ``info_hash``, used when a torrent is
+    /// added which is already in the store
+    fn load(&self, info_hash: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// stores the bencoded resume data of ``info_hash``. ``torrent`` is the
+    /// .torrent file once the metadata of the torrent is known, it doesn't
+    /// change, so a store may keep only the first one.
+    fn save(&self, info_hash: &str, resume_data: &[u8], torrent: Option<&[u8]>) -> io::Result<()>;
+
+    /// removes everything stored for ``info_hash``, removing a torrent which
+    /// isn't stored is not an error
+    fn remove(&self, info_hash: &str) -> io::Result<()>;
+
+    /// the bencoded session state, loaded when the session is created unless
+    /// it has a ``session_state_path``. By default the store keeps no
+    /// session state.
+    fn load_session_state(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// stores the bencoded session state when the session is dropped, unless
+    /// it has a ``session_state_path``
+    fn save_session_state(&self, _state: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: ResumeStore + ?Sized> ResumeStore for Arc<S> {
+    fn load_all(&self) -> io::Result<Vec<ResumeEntry>> {
+        (**self).load_all()
+    }
+
+    fn load(&self, info_hash: &str) -> io::Result<Option<Vec<u8>>> {
+        (**self).load(info_hash)
+    }
+
+    fn save(&self, info_hash: &str, resume_data: &[u8], torrent: Option<&[u8]>) -> io::Result<()> {
+        (**self).save(info_hash, resume_data, torrent)
+    }
+
+    fn remove(&self, info_hash: &str) -> io::Result<()> {
+        (**self).remove(info_hash)
+    }
+
+    fn load_session_state(&self) -> io::Result<Option<Vec<u8>>> {
+        (**self).load_session_state()
+    }
+
+    fn save_session_state(&self, state: &[u8]) -> io::Result<()> {
+        (**self).save_session_state(state)
+    }
+}
+
+/// The ``ResumeStore`` of a session as passed to ``create_session``.
+pub struct ResumeStoreBox {
+    store: Option<Box<dyn ResumeStore>>,
+}
+
+impl ResumeStoreBox {
+    pub fn new<S>(store: S) -> Box<ResumeStoreBox>
+    where
+        S: ResumeStore + 'static,
+    {
+        Box::new(ResumeStoreBox {
+            store: Some(Box::new(store)),
+        })
+    }
+
+    /// no store, no resume data is loaded or saved
+    pub fn none() -> Box<ResumeStoreBox> {
+        Box::new(ResumeStoreBox { store: None })
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    pub(crate) fn load_all(&self) -> io::Result<Vec<ResumeEntry>> {
+        match &self.store {
+            Some(store) => store.load_all(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// empty if there is no resume data
+    pub(crate) fn load(&self, info_hash: &str) -> io::Result<Vec<u8>> {
+        match &self.store {
+            Some(store) => Ok(store.load(info_hash)?.unwrap_or_default()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// ``torrent`` is empty if the metadata isn't known
+    pub(crate) fn save(&self, info_hash: &str, resume_data: &[u8], torrent: &[u8]) -> io::Result<()> {
+        match &self.store {
+            Some(store) => store.save(info_hash, resume_data, (!torrent.is_empty()).then_some(torrent)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn remove(&self, info_hash: &str) -> io::Result<()> {
+        match &self.store {
+            Some(store) => store.remove(info_hash),
+            None => Ok(()),
+        }
+    }
+
+    /// empty if there is no session state
+    pub(crate) fn load_session_state(&self) -> io::Result<Vec<u8>> {
+        match &self.store {
+            Some(store) => Ok(store.load_session_state()?.unwrap_or_default()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub(crate) fn save_session_state(&self, state: &[u8]) -> io::Result<()> {
+        match &self.store {
+            Some(store) => store.save_session_state(state),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,34 +1,60 @@
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
     use cxx::UniquePtr;
 
     use crate::{
-        ffi::{create_session, ParamPair, Session},
+        AlertSink, ResumeStoreBox,
+        ffi::{
+            AddTorrentParams, Log, LogOverflowPolicy, OperationErrorKind, Session, SessionOptions, SettingType,
+            create_session, parse_setting, take_operation_error,
+        },
         flags::SaveStateFlags,
     };
 
     fn create_default_session() -> anyhow::Result<UniquePtr<Session>> {
-        let ses = create_session(
-            &[
-                ParamPair {
-                    key: "user_agent",
-                    value: "libtorrent-rs/2.0.11",
-                },
-                ParamPair {
-                    key: "alert_mask",
-                    value: "error,peer,port_mapping,storage,tracker,connect,status,ip_block,performance_warning,dht,incoming_request,dht_operation,port_mapping_log,file_progress",
-                },
-            ],
-            SaveStateFlags::SaveDhtState.bits(),
-            "/tmp/t/libtest/ses.state",
-            "/tmp/t/libtest/resume",
-            "/tmp/t/libtest/torrents",
-            20,
-        )?;
+        create_session_with_sink(AlertSink::noop())
+    }
+
+    fn create_session_with_sink(alert_sink: Box<AlertSink>) -> anyhow::Result<UniquePtr<Session>> {
+        let settings = [
+            parse_setting("user_agent", "libtorrent-rs/2.0.11")?,
+            parse_setting(
+                "alert_mask",
+                "error,peer,port_mapping,storage,tracker,connect,status,ip_block,performance_warning,dht,incoming_request,dht_operation,port_mapping_log,file_progress",
+            )?,
+        ];
+
+        let options = SessionOptions {
+            save_state_flags: SaveStateFlags::save_dht_state.bits(),
+            // ephemeral, nothing is loaded from or saved to disk
+            session_state_path: String::new(),
+            recovery_save_path: String::new(),
+            log_size: 20,
+            log_overflow_policy: LogOverflowPolicy::DropOldest,
+            alert_poll_interval_ms: 500,
+            session_stats_interval_ms: 500,
+            torrent_updates_interval_ms: 500,
+            dht_stats_interval_ms: 500,
+            autosave_interval_ms: 0,
+        };
+        let ses = create_session(&settings, &options, ResumeStoreBox::none(), alert_sink)?;
 
         Ok(ses)
     }
 
+    fn torrent_params(save_path: &str) -> AddTorrentParams {
+        AddTorrentParams {
+            save_path: save_path.to_string(),
+            has_max_connections: true,
+            max_connections: 100,
+            has_max_uploads: true,
+            max_uploads: -1,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_create_session() {
         let ses = create_default_session().unwrap();
@@ -44,26 +70,37 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
 
         std::thread::sleep(std::time::Duration::from_secs(5000));
     }
 
+    #[test]
+    fn test_log_sink() {
+        let logs = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let logs = logs.clone();
+            AlertSink::noop().with_logs(move |log: Log| logs.lock().unwrap().push(log))
+        };
+        let ses = create_session_with_sink(sink).unwrap();
+
+        ses.add_magnet(
+            "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
+            &torrent_params("/tmp/t/libtest/items/3574438"),
+        )
+        .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(5));
+
+        let logs = logs.lock().unwrap();
+        assert!(!logs.is_empty());
+        assert!(
+            logs.iter()
+                .any(|log| log.info_hash == "771cc76f2c725648b20a74d27ffeba2458a88343" && !log.alert_type.is_empty())
+        );
+    }
+
     #[test]
     fn test_get_torrents() {
         let ses = create_default_session().unwrap();
@@ -71,20 +108,7 @@ mod tests {
 
         ses.add_torrent(
             "/tmp/t/START-243.torrent",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
 
@@ -94,10 +118,10 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let t = h.get_torrent_info();
+        let t = h.get_torrent_info().unwrap();
 
         assert!(!t.files.is_empty());
 
@@ -113,20 +137,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -136,15 +147,19 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let peers = h.get_peers();
+        h.post_peer_info().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let peers = h.peers();
         println!("----- 5");
         println!("peers: {:#?}", peers);
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        let peers = h.get_peers();
+        h.post_peer_info().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let peers = h.peers();
         println!("----- 5");
         println!("peers: {:#?}", peers);
         std::thread::sleep(std::time::Duration::from_secs(1));
@@ -159,20 +174,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -182,15 +184,19 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let progress = h.get_file_progress(true);
+        h.post_file_progress(true).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let progress = h.file_progress();
         println!("----- 5");
         println!("progress: {:#?}", progress);
         std::thread::sleep(std::time::Duration::from_secs(1));
 
-        let progress = h.get_file_progress(true);
+        h.post_file_progress(true).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let progress = h.file_progress();
         println!("----- 5");
         println!("progress: {:#?}", progress);
         std::thread::sleep(std::time::Duration::from_secs(1));
@@ -205,20 +211,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -228,14 +221,18 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let availability = h.get_piece_availability();
+        h.post_piece_availability().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let availability = h.piece_availability();
         println!("----- 5");
         println!("availability: {:#?}", availability);
         std::thread::sleep(std::time::Duration::from_secs(1));
-        let availability = h.get_piece_availability();
+        h.post_piece_availability().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let availability = h.piece_availability();
         println!("----- 5");
         println!("availability: {:#?}", availability);
         std::thread::sleep(std::time::Duration::from_secs(1));
@@ -250,20 +247,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -273,14 +257,18 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let trackers = h.get_trackers();
+        h.post_trackers().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let trackers = h.trackers();
         println!("----- 5");
         println!("trackers: {:#?}", trackers);
         std::thread::sleep(std::time::Duration::from_secs(10));
-        let trackers = h.get_trackers();
+        h.post_trackers().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let trackers = h.trackers();
         println!("----- 5");
         println!("trackers: {:#?}", trackers);
     }
@@ -294,20 +282,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/FRD-015",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/FRD-015"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -317,11 +292,13 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
         loop {
-            let piece_info = h.get_piece_info();
+            h.post_download_queue().unwrap();
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            let piece_info = h.piece_info();
             println!("----- 5");
             println!("piece_info: {:#?}", piece_info);
             std::thread::sleep(std::time::Duration::from_secs(10));
@@ -337,20 +314,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -360,14 +324,14 @@ mod tests {
         assert!(!tts.is_empty());
         println!("{:#?}", tts);
 
-        let h = ses.get_torrent_handle(&tts[0].info_hash);
+        let h = ses.get_torrent_handle(&tts[0].info_hash).unwrap();
         assert!(h.is_valid());
 
-        let ts = h.get_torrent_status();
+        let ts = h.get_torrent_status().unwrap();
         println!("----- 5");
         println!("trackers: {:#?}", ts);
         std::thread::sleep(std::time::Duration::from_secs(10));
-        let ts = h.get_torrent_status();
+        let ts = h.get_torrent_status().unwrap();
         println!("----- 5");
         println!("trackers: {:#?}", ts);
     }
@@ -381,20 +345,7 @@ mod tests {
 
         ses.add_magnet(
             "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343",
-            &[
-                ParamPair {
-                    key: "max_connections",
-                    value: "100",
-                },
-                ParamPair {
-                    key: "max_uploads",
-                    value: "-1",
-                },
-                ParamPair {
-                    key: "save_path",
-                    value: "/tmp/t/libtest/items/3574438",
-                },
-            ],
+            &torrent_params("/tmp/t/libtest/items/3574438"),
         )
         .unwrap();
         std::thread::sleep(std::time::Duration::from_secs(20));
@@ -403,4 +354,63 @@ mod tests {
         let tss = ses.get_stats();
         println!("{:#?}", tss);
     }
+
+    #[test]
+    fn test_apply_settings_error() {
+        let ses = create_default_session().unwrap();
+
+        let mut wrong_type = parse_setting("connections_limit", "50").unwrap();
+        wrong_type.kind = SettingType::Str;
+        wrong_type.str_value = "50".to_string();
+        let settings = [parse_setting("active_downloads", "7").unwrap(), wrong_type];
+
+        let err = ses.apply_settings(&settings);
+        assert_eq!(err.name, "connections_limit");
+        assert_eq!(err.message, "expected integer");
+
+        // nothing was applied
+        let active_downloads = ses
+            .get_settings()
+            .into_iter()
+            .find(|s| s.name == "active_downloads")
+            .unwrap();
+        assert_ne!(active_downloads.int_value, 7);
+
+        assert!(ses.apply_settings(&settings[..1]).name.is_empty());
+    }
+
+    #[test]
+    fn test_operation_error() {
+        let ses = create_default_session().unwrap();
+
+        assert!(
+            ses.get_torrent_handle("dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c")
+                .is_err()
+        );
+        assert_eq!(take_operation_error().kind, OperationErrorKind::InvalidHandle);
+        // it's taken
+        assert_eq!(take_operation_error().kind, OperationErrorKind::None);
+
+        assert!(ses.get_torrent_handle("not hex").is_err());
+        let err = take_operation_error();
+        assert_eq!(err.kind, OperationErrorKind::InvalidInfoHash);
+        assert_eq!(err.info_hash, "not hex");
+
+        // any other exception
+        assert!(parse_setting("connections_limit", "many").is_err());
+        assert_eq!(take_operation_error().kind, OperationErrorKind::None);
+        // the handle of a removed torrent
+        let hash = "771cc76f2c725648b20a74d27ffeba2458a88343";
+        ses.add_magnet(
+            &format!("magnet:?xt=urn:btih:{hash}"),
+            &torrent_params("/tmp/t/libtest/operation_error"),
+        )
+        .unwrap();
+        let h = ses.get_torrent_handle(hash).unwrap();
+        assert_eq!(h.info_hash().unwrap(), hash);
+        ses.remove_torrent(hash, false).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(h.info_hash().is_err());
+        assert_eq!(take_operation_error().kind, OperationErrorKind::InvalidHandle);
+    }
 }
@@ -4,7 +4,10 @@
 #include "../libtorrent/include/libtorrent/time.hpp"
 #include "../libtorrent/include/libtorrent/torrent_status.hpp"
 
+#include <algorithm>
 #include <cstdint>
+#include <mutex>
+#include <utility>
 #include <vector>
 
 namespace libtorrent_wrapper {
@@ -102,7 +105,6 @@ PeerState::PeerState() {}
 PeerState::~PeerState() {}
 
 void PeerState::update_peers(lt::peer_info_alert* a) {
-  printf("!!!!update_peers\n");
   auto h = a->handle;
   auto peers = a->peer_info;
   auto j = m_all_peers.find(h);
@@ -117,7 +119,6 @@ std::vector<lt::peer_info> PeerState::get_peers(lt::torrent_handle h) {
   auto i = m_all_peers.find(h);
   if (i == m_all_peers.end())
     return std::vector<lt::peer_info>();
-  printf("!!!!get_peers\n");
   return i->second;
 }
 
@@ -251,4 +252,64 @@ void TrackerState::remove(lt::torrent_handle h) {
   m_all_trackers.erase(i);
 }
 
+LogQueue::LogQueue(std::size_t capacity, overflow policy)
+    : m_capacity(std::max<std::size_t>(capacity, 1)), m_policy(policy) {}
+LogQueue::~LogQueue() {}
+
+void LogQueue::push(LogEvent e, bool may_block) {
+  std::unique_lock<std::mutex> lock(m_mutex);
+  if (m_policy == overflow::block && may_block) {
+    m_not_full.wait(lock, [this] { return m_closed || m_events.size() < m_capacity; });
+  }
+
+  if (m_closed) {
+    ++m_dropped;
+    return;
+  }
+
+  if (m_events.size() >= m_capacity) {
+    ++m_dropped;
+    if (m_policy != overflow::drop_oldest) {
+      return;
+    }
+    m_events.pop_front();
+  }
+  m_events.push_back(std::move(e));
+}
+
+std::vector<LogEvent> LogQueue::drain() {
+  return drain([](LogEvent const&) { return true; });
+}
+
+std::vector<LogEvent> LogQueue::drain(std::function<bool(LogEvent const&)> const& match) {
+  std::vector<LogEvent> ret;
+  {
+    std::lock_guard<std::mutex> lock(m_mutex);
+    std::deque<LogEvent> rest;
+    for (auto& e : m_events) {
+      if (match(e)) {
+        ret.push_back(std::move(e));
+      } else {
+        rest.push_back(std::move(e));
+      }
+    }
+    m_events.swap(rest);
+  }
+  m_not_full.notify_all();
+  return ret;
+}
+
+std::uint64_t LogQueue::dropped() const {
+  std::lock_guard<std::mutex> lock(m_mutex);
+  return m_dropped;
+}
+
+void LogQueue::close() {
+  {
+    std::lock_guard<std::mutex> lock(m_mutex);
+    m_closed = true;
+  }
+  m_not_full.notify_all();
+}
+
 } // namespace libtorrent_wrapper
@@ -9,7 +9,12 @@
 #include "../libtorrent/include/libtorrent/torrent_handle.hpp"
 #include "../libtorrent/include/libtorrent/torrent_status.hpp"
 
+#include <condition_variable>
 #include <cstdint>
+#include <deque>
+#include <functional>
+#include <mutex>
+#include <string>
 #include <unordered_map>
 #include <vector>
 
@@ -164,6 +169,64 @@ private:
   std::unordered_map<lt::torrent_handle, std::vector<lt::announce_entry>> m_all_trackers;
 };
 
+// the log message of an alert or a diagnostic of the wrapper
+struct LogEvent {
+  lt::time_point timestamp;
+  std::string message;
+  std::string alert_type;     // empty for a diagnostic
+  std::uint32_t category = 0; // the alert_category bits
+  std::uint8_t level = 0;     // a LogLevel
+  std::uint8_t target = 0;    // a LogTarget
+  lt::info_hash_t info_hashes;
+  std::string torrent_name;
+  std::string peer;
+};
+
+// a bounded queue of log events, filled by the alert thread and the
+// diagnostics of the wrapper, drained from any thread
+class LogQueue {
+public:
+  // what push() does when the queue is full
+  enum class overflow : std::uint8_t {
+    drop_oldest,
+    drop_newest,
+    block,
+  };
+
+  LogQueue(std::size_t capacity, overflow policy);
+  ~LogQueue();
+
+  // adds an event. When the queue is full the oldest event or this one is
+  // dropped. With the block policy push() waits until there is room or the
+  // queue is closed if `may_block` is set, otherwise this event is dropped.
+  // Only a caller which holds no lock a drain()ing thread may wait for can
+  // block.
+  void push(LogEvent e, bool may_block);
+
+  // removes and returns all events
+  std::vector<LogEvent> drain();
+
+  // removes and returns the events `match` returns true for, the others
+  // stay in the queue
+  std::vector<LogEvent> drain(std::function<bool(LogEvent const&)> const& match);
+
+  // the number of events dropped because the queue was full or closed
+  std::uint64_t dropped() const;
+
+  // drops the events of blocked and later push() calls, so they return
+  void close();
+
+private:
+  std::size_t const m_capacity;
+  overflow const m_policy;
+
+  mutable std::mutex m_mutex; // protects the members below
+  std::condition_variable m_not_full;
+  std::deque<LogEvent> m_events;
+  std::uint64_t m_dropped = 0;
+  bool m_closed = false;
+};
+
 } // namespace libtorrent_wrapper
 
 #endif
@@ -1,16 +1,26 @@
 #include "utils.hpp"
+#include "libtorrent-rasterbar-sys/src/lib.rs.h"
 #include "rust/cxx.h"
 
 #include "../libtorrent/include/libtorrent/address.hpp"
+#include "../libtorrent/include/libtorrent/error.hpp"
 #include "../libtorrent/include/libtorrent/socket.hpp"
 
 #include <boost/filesystem.hpp>
+#include <cstdio>
 #include <fstream>
 #include <ios>
 #include <sstream>
+#include <stdexcept>
 #include <string>
 #include <vector>
 
+#ifdef _WIN32
+#include <io.h>
+#else
+#include <unistd.h>
+#endif
+
 namespace libtorrent_wrapper {
 
 bool load_file(std::string const& filename, std::vector<char>& v, int limit) {
@@ -28,11 +38,18 @@ bool load_file(std::string const& filename, std::vector<char>& v, int limit) {
 }
 
 bool save_file(std::string const& filename, std::vector<char> const& v) {
-  std::fstream f(filename,
-                 std::ios_base::trunc | std::ios_base::out | std::ios_base::binary);
-  f.write(v.data(), int(v.size()));
-  f.flush();
-  return !f.fail();
+  std::FILE* f = std::fopen(filename.c_str(), "wb");
+  if (f == nullptr) {
+    return false;
+  }
+
+  bool ok = std::fwrite(v.data(), 1, v.size(), f) == v.size() && std::fflush(f) == 0;
+#ifdef _WIN32
+  ok = ok && _commit(_fileno(f)) == 0;
+#else
+  ok = ok && fsync(fileno(f)) == 0;
+#endif
+  return std::fclose(f) == 0 && ok;
 }
 
 std::string rust_str_to_string(rust::Str s) { return std::string(s.data(), s.length()); }
@@ -49,18 +66,50 @@ std::string to_hex(lt::sha256_hash const& s) {
   return ret.str();
 }
 
-lt::sha1_hash from_hex(std::string const& hex) {
-  if (hex.length() != 40) {
-    throw std::invalid_argument("Invalid SHA1 hash length");
+std::string best_hex(lt::info_hash_t const& ih) {
+  if (ih.has_v1()) {
+    return to_hex(ih.v1);
+  }
+  if (ih.has_v2()) {
+    return to_hex(ih.v2);
+  }
+  return std::string();
+}
+
+std::vector<std::string> all_hex(lt::info_hash_t const& ih) {
+  std::vector<std::string> ret;
+  if (ih.has_v1()) {
+    ret.push_back(to_hex(ih.v1));
+  }
+  if (ih.has_v2()) {
+    ret.push_back(to_hex(ih.v2));
+  }
+  return ret;
+}
+
+template <std::size_t N>
+std::array<char, N> hex_to_bytes(std::string const& hex, char const* what) {
+  if (hex.length() != N * 2) {
+    throw std::invalid_argument(std::string("Invalid ") + what + " hash length");
+  }
+  if (hex.find_first_not_of("0123456789abcdefABCDEF") != std::string::npos) {
+    throw std::invalid_argument(std::string("Invalid ") + what + " hash");
   }
 
-  std::array<char, 20> bytes;
-  for (size_t i = 0; i < 20; i++) {
+  std::array<char, N> bytes;
+  for (size_t i = 0; i < N; i++) {
     std::string byte = hex.substr(i * 2, 2);
     bytes[i] = static_cast<char>(std::stoi(byte, nullptr, 16));
   }
+  return bytes;
+}
 
-  return lt::sha1_hash(bytes);
+lt::sha1_hash from_hex(std::string const& hex) {
+  return lt::sha1_hash(hex_to_bytes<20>(hex, "SHA1"));
+}
+
+lt::sha256_hash sha256_from_hex(std::string const& hex) {
+  return lt::sha256_hash(hex_to_bytes<32>(hex, "SHA256"));
 }
 
 std::string endpoint_to_string(lt::tcp::endpoint const& ep) {
@@ -111,4 +160,37 @@ std::vector<std::string> list_dir(const std::string& dir, bool recursive) {
   return files;
 }
 
+operation_error::operation_error(OperationErrorKind kind, std::string const& what)
+    : std::runtime_error(what), kind(kind) {}
+
+void throw_invalid_handle() {
+  throw operation_error(OperationErrorKind::InvalidHandle, "invalid torrent handle");
+}
+
+void throw_invalid_info_hash(std::string const& hex) {
+  operation_error e(OperationErrorKind::InvalidInfoHash, "invalid info-hash: " + hex);
+  e.info_hash = hex;
+  throw e;
+}
+
+void throw_invalid_file_index(int index) {
+  operation_error e(OperationErrorKind::InvalidFileIndex,
+                    "invalid file index: " + std::to_string(index));
+  e.file_index = index;
+  throw e;
+}
+
+void throw_no_metadata() {
+  throw operation_error(OperationErrorKind::NoMetadata, "torrent has no metadata yet");
+}
+
+void throw_error_code(lt::error_code const& ec) {
+  if (ec == lt::errors::invalid_torrent_handle) {
+    throw_invalid_handle();
+  }
+  operation_error e(OperationErrorKind::ErrorCode, ec.message());
+  e.ec = ec;
+  throw e;
+}
+
 } // namespace libtorrent_wrapper
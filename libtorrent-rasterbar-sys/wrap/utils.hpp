@@ -1,19 +1,25 @@
 #ifndef LIBTORRENT_WRAPPER_UTILS_HPP_
 #define LIBTORRENT_WRAPPER_UTILS_HPP_
 
+#include "../libtorrent/include/libtorrent/error_code.hpp"
+#include "../libtorrent/include/libtorrent/info_hash.hpp"
 #include "../libtorrent/include/libtorrent/sha1_hash.hpp"
 #include "../libtorrent/include/libtorrent/socket.hpp"
 
 #include "rust/cxx.h"
+#include <cstdint>
+#include <stdexcept>
 #include <string>
 #include <vector>
 
 namespace libtorrent_wrapper {
 
+enum class OperationErrorKind : std::uint8_t;
+
 // load a file into a vector
 bool load_file(std::string const& filename, std::vector<char>& v, int limit = 8000000);
 
-// save buf to a file
+// save buf to a file and sync it to disk
 bool save_file(std::string const& filename, std::vector<char> const& v);
 
 // convert rust::Str to std::string
@@ -25,9 +31,22 @@ std::string to_hex(lt::sha1_hash const& s);
 // convert lt::sha256_hash to hex
 std::string to_hex(lt::sha256_hash const& s);
 
+// the hex of the v1 info-hash if there is one, otherwise of the full v2
+// info-hash (not the truncated one of get_best()). Torrents are identified by
+// it in the ResumeStore and in logs, like ``InfoHashes::best`` on the rust side
+std::string best_hex(lt::info_hash_t const& ih);
+
+// the hex of the v1 and of the full v2 info-hash, of those the torrent has.
+// A torrent added from a v2 magnet link is keyed by its v2 info-hash until
+// its metadata shows it has a v1 one, too
+std::vector<std::string> all_hex(lt::info_hash_t const& ih);
+
 // convert hex to lt::sha1_hash
 lt::sha1_hash from_hex(std::string const& hex);
 
+// convert hex to lt::sha256_hash
+lt::sha256_hash sha256_from_hex(std::string const& hex);
+
 // convert lt::tcp::endpoint to string as
 // ipv4:port or [ipv6]:port
 std::string endpoint_to_string(lt::tcp::endpoint const& ep);
@@ -35,5 +54,28 @@ std::string endpoint_to_string(lt::tcp::endpoint const& ep);
 // list files in a directory
 std::vector<std::string> list_dir(const std::string& dir, bool recursive = false);
 
+// the exception of a failed operation of the session or of a torrent handle.
+// rust::behavior::trycatch (wrapper.hpp) keeps it when it's passed to rust,
+// where take_operation_error() turns it into an ``LTError`` (errors.rs)
+struct operation_error : std::runtime_error {
+  operation_error(OperationErrorKind kind, std::string const& what);
+
+  OperationErrorKind kind;
+  // the rejected info-hash of invalid_info_hash
+  std::string info_hash;
+  // the rejected index of invalid_file_index
+  int file_index = -1;
+  // the error of error_code
+  lt::error_code ec;
+};
+
+[[noreturn]] void throw_invalid_handle();
+[[noreturn]] void throw_invalid_info_hash(std::string const& hex);
+[[noreturn]] void throw_invalid_file_index(int index);
+[[noreturn]] void throw_no_metadata();
+
+// throws ``invalid_handle`` for errors::invalid_torrent_handle
+[[noreturn]] void throw_error_code(lt::error_code const& ec);
+
 } // namespace libtorrent_wrapper
 #endif
@@ -1,9 +1,13 @@
 #include "wrapper.hpp"
 
 #include "../libtorrent/include/libtorrent/add_torrent_params.hpp"
+#include "../libtorrent/include/libtorrent/address.hpp"
 #include "../libtorrent/include/libtorrent/alert.hpp"
 #include "../libtorrent/include/libtorrent/alert_types.hpp"
 #include "../libtorrent/include/libtorrent/announce_entry.hpp"
+#include "../libtorrent/include/libtorrent/bdecode.hpp"
+#include "../libtorrent/include/libtorrent/bencode.hpp"
+#include "../libtorrent/include/libtorrent/create_torrent.hpp"
 #include "../libtorrent/include/libtorrent/aux_/path.hpp"
 #include "../libtorrent/include/libtorrent/download_priority.hpp"
 #include "../libtorrent/include/libtorrent/error_code.hpp"
@@ -11,24 +15,26 @@
 #include "../libtorrent/include/libtorrent/magnet_uri.hpp"
 #include "../libtorrent/include/libtorrent/read_resume_data.hpp"
 #include "../libtorrent/include/libtorrent/session_types.hpp"
+#include "../libtorrent/include/libtorrent/settings_pack.hpp"
 #include "../libtorrent/include/libtorrent/string_view.hpp"
 #include "../libtorrent/include/libtorrent/time.hpp"
 #include "../libtorrent/include/libtorrent/torrent_flags.hpp"
 #include "../libtorrent/include/libtorrent/torrent_handle.hpp"
 #include "../libtorrent/include/libtorrent/units.hpp"
 #include "../libtorrent/include/libtorrent/write_resume_data.hpp"
+#include "../libtorrent/include/libtorrent/write_torrent_file.hpp"
 
 #include "libtorrent-rasterbar-sys/src/lib.rs.h"
 #include "states.hpp"
 #include "utils.hpp"
 
 #include <algorithm>
-#include <boost/algorithm/string.hpp>
-#include <boost/algorithm/string/split.hpp>
 #include <cstdint>
 #include <cstdio>
+#include <iterator>
 #include <memory>
 #include <string>
+#include <stdexcept>
 #include <system_error>
 #include <thread>
 #include <utility>
@@ -36,6 +42,17 @@
 
 namespace libtorrent_wrapper {
 
+InfoHashes cast_info_hashes(lt::info_hash_t const& ih) {
+  InfoHashes ret;
+  if (ih.has_v1()) {
+    ret.v1 = to_hex(ih.v1);
+  }
+  if (ih.has_v2()) {
+    ret.v2 = to_hex(ih.v2);
+  }
+  return ret;
+}
+
 TorrentStatus cast_torrent_status(lt::torrent_status const& ts) {
   TorrentStatus ret;
 
@@ -105,7 +122,8 @@ TorrentStatus cast_torrent_status(lt::torrent_status const& ts) {
   ret.announcing_to_trackers = ts.announcing_to_trackers;
   ret.announcing_to_lsd = ts.announcing_to_lsd;
   ret.announcing_to_dht = ts.announcing_to_dht;
-  ret.info_hash = to_hex(ts.info_hashes.get_best());
+  ret.info_hash = best_hex(ts.info_hashes);
+  ret.info_hashes = cast_info_hashes(ts.info_hashes);
   ret.last_upload = ts.last_upload.time_since_epoch().count() / 1000000; // milliseconds
   ret.last_download =
       ts.last_download.time_since_epoch().count() / 1000000; // milliseconds
@@ -165,7 +183,8 @@ TorrentInfo cast_torrent_info(const lt::torrent_info& lt_ti) {
   // fill blocks per piece
   ti.blocks_per_piece = lt_ti.blocks_per_piece();
   // fill info-hash
-  ti.info_hash = rust::String::lossy(to_hex(lt_ti.info_hashes().get_best()));
+  ti.info_hash = rust::String::lossy(best_hex(lt_ti.info_hashes()));
+  ti.info_hashes = cast_info_hashes(lt_ti.info_hashes());
   // file num_files
   ti.num_files = static_cast<std::uint32_t>(lt_ti.num_files());
   // fill name
@@ -184,121 +203,339 @@ TorrentInfo cast_torrent_info(const lt::torrent_info& lt_ti) {
   return ti;
 }
 
-Session::Session(lt::session_params params, std::uint32_t save_state_flags,
-                 std::string session_state_path, std::string resume_dir,
-                 std::string torrent_dir, std::uint32_t log_size)
-    : m_save_state_flags(save_state_flags), m_session_state_path(session_state_path),
-      m_resume_dir(resume_dir), m_torrent_dir(torrent_dir), m_log_size(log_size) {
+ErrorCode cast_error_code(lt::error_code const& ec) {
+  ErrorCode ret;
+  ret.value = ec.value();
+  if (ec) {
+    ret.category = ec.category().name();
+    ret.message = rust::String::lossy(ec.message());
+  }
+  return ret;
+}
+
+// fill the torrent related fields of the alert
+void fill_torrent_alert(Alert& ret, lt::torrent_alert const* a,
+                        lt::info_hash_t const& info_hashes) {
+  ret.info_hashes = cast_info_hashes(info_hashes);
+  ret.torrent_name = rust::String::lossy(a->torrent_name());
+}
+
+// Convert an alert to a typed Alert
+// Returns false if the alert is not one of AlertKind
+bool cast_alert(lt::alert const* a, Alert& ret) {
+  using namespace lt;
+
+  ret.timestamp = a->timestamp().time_since_epoch().count() / 1000000; // milliseconds
+  ret.file_index = -1;
+  ret.piece_index = -1;
+  ret.count = 0;
+  ret.state = 0;
+  ret.prev_state = 0;
+  ret.error = cast_error_code(lt::error_code());
+
+  if (auto const* p = alert_cast<add_torrent_alert>(a)) {
+    ret.kind = AlertKind::TorrentAdded;
+    // the handle is invalid if the torrent failed to be added
+    fill_torrent_alert(ret, p,
+                       p->params.ti ? p->params.ti->info_hashes() : p->params.info_hashes);
+    ret.error = cast_error_code(p->error);
+  } else if (auto const* p = alert_cast<torrent_removed_alert>(a)) {
+    ret.kind = AlertKind::TorrentRemoved;
+    fill_torrent_alert(ret, p, p->info_hashes);
+  } else if (auto const* p = alert_cast<torrent_finished_alert>(a)) {
+    ret.kind = AlertKind::TorrentFinished;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<torrent_paused_alert>(a)) {
+    ret.kind = AlertKind::TorrentPaused;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<torrent_resumed_alert>(a)) {
+    ret.kind = AlertKind::TorrentResumed;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<torrent_error_alert>(a)) {
+    ret.kind = AlertKind::TorrentError;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.path = rust::String::lossy(p->filename());
+    ret.error = cast_error_code(p->error);
+  } else if (auto const* p = alert_cast<state_changed_alert>(a)) {
+    ret.kind = AlertKind::StateChanged;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.state = static_cast<std::uint8_t>(p->state);
+    ret.prev_state = static_cast<std::uint8_t>(p->prev_state);
+  } else if (auto const* p = alert_cast<metadata_received_alert>(a)) {
+    ret.kind = AlertKind::MetadataReceived;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<tracker_reply_alert>(a)) {
+    ret.kind = AlertKind::TrackerReply;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.tracker_url = rust::String::lossy(p->tracker_url());
+    ret.count = p->num_peers;
+  } else if (auto const* p = alert_cast<tracker_error_alert>(a)) {
+    ret.kind = AlertKind::TrackerError;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.tracker_url = rust::String::lossy(p->tracker_url());
+    ret.count = p->times_in_row;
+    ret.error = cast_error_code(p->error);
+  } else if (auto const* p = alert_cast<file_completed_alert>(a)) {
+    ret.kind = AlertKind::FileCompleted;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.file_index = static_cast<std::int32_t>(p->index);
+  } else if (auto const* p = alert_cast<file_error_alert>(a)) {
+    ret.kind = AlertKind::FileError;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.path = rust::String::lossy(p->filename());
+    ret.error = cast_error_code(p->error);
+  } else if (auto const* p = alert_cast<hash_failed_alert>(a)) {
+    ret.kind = AlertKind::HashFailed;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.piece_index = static_cast<std::int32_t>(p->piece_index);
+  } else if (auto const* p = alert_cast<storage_moved_alert>(a)) {
+    ret.kind = AlertKind::StorageMoved;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.path = rust::String::lossy(p->storage_path());
+  } else if (auto const* p = alert_cast<storage_moved_failed_alert>(a)) {
+    ret.kind = AlertKind::StorageMovedFailed;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+    ret.path = rust::String::lossy(p->file_path());
+    ret.error = cast_error_code(p->error);
+  } else if (auto const* p = alert_cast<peer_info_alert>(a)) {
+    ret.kind = AlertKind::PeerInfo;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<file_progress_alert>(a)) {
+    ret.kind = AlertKind::FileProgress;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<piece_info_alert>(a)) {
+    ret.kind = AlertKind::PieceInfo;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<piece_availability_alert>(a)) {
+    ret.kind = AlertKind::PieceAvailability;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<tracker_list_alert>(a)) {
+    ret.kind = AlertKind::TrackerList;
+    fill_torrent_alert(ret, p, p->handle.info_hashes());
+  } else if (auto const* p = alert_cast<listen_succeeded_alert>(a)) {
+    ret.kind = AlertKind::ListenSucceeded;
+    ret.endpoint = endpoint_to_string(lt::tcp::endpoint(p->address, p->port));
+  } else if (auto const* p = alert_cast<listen_failed_alert>(a)) {
+    ret.kind = AlertKind::ListenFailed;
+    ret.endpoint = endpoint_to_string(lt::tcp::endpoint(p->address, p->port));
+    ret.error = cast_error_code(p->error);
+  } else {
+    return false;
+  }
+
+  ret.message = rust::String::lossy(a->message());
+  return true;
+}
+
+rust::Slice<const std::uint8_t> to_slice(std::vector<char> const& v) {
+  return rust::Slice<const std::uint8_t>(reinterpret_cast<std::uint8_t const*>(v.data()),
+                                         v.size());
+}
+
+lt::span<char const> to_span(rust::Slice<const std::uint8_t> data) {
+  return lt::span<char const>(reinterpret_cast<char const*>(data.data()), data.size());
+}
+
+lt::span<char const> to_span(rust::Vec<std::uint8_t> const& data) {
+  return lt::span<char const>(reinterpret_cast<char const*>(data.data()), data.size());
+}
+
+LogQueue::overflow cast_log_overflow_policy(LogOverflowPolicy policy) {
+  switch (policy) {
+  case LogOverflowPolicy::DropNewest:
+    return LogQueue::overflow::drop_newest;
+  case LogOverflowPolicy::Block:
+    return LogQueue::overflow::block;
+  default:
+    return LogQueue::overflow::drop_oldest;
+  }
+}
+
+// how long destroying a session which wasn't shut down waits for the resume
+// data of its torrents
+static constexpr std::uint32_t default_shutdown_timeout_ms = 30000;
+
+Session::Session(lt::session_params params, SessionOptions const& options,
+                 rust::Box<ResumeStoreBox> resume_store, rust::Box<AlertSink> alert_sink)
+    : m_save_state_flags(options.save_state_flags),
+      m_session_state_path(std::string(options.session_state_path)),
+      m_resume_store(std::move(resume_store)),
+      m_recovery_save_path(std::string(options.recovery_save_path)),
+      m_autosave_interval_ms(options.autosave_interval_ms),
+      m_last_autosave(lt::clock_type::now()),
+      m_alert_poll_interval_ms(options.alert_poll_interval_ms),
+      m_logs(options.log_size, cast_log_overflow_policy(options.log_overflow_policy)),
+      m_alert_sink(std::move(alert_sink)) {
   lt_session = std::make_shared<lt::session>(lt::session(std::move(params)));
   m_running = true;
 
-  m_thread = std::make_shared<std::thread>([=] { poll_alerts(); });
+  // the default time_point of m_last_post makes the first posts immediate
+  m_post_interval_ms[static_cast<std::size_t>(StatsPost::SessionStats)] =
+      options.session_stats_interval_ms;
+  m_post_interval_ms[static_cast<std::size_t>(StatsPost::TorrentUpdates)] =
+      options.torrent_updates_interval_ms;
+  m_post_interval_ms[static_cast<std::size_t>(StatsPost::DhtStats)] =
+      options.dht_stats_interval_ms;
 
-  // load all resume data
+  // load all resume data before the alert thread is started, so nothing
+  // refers to the session if it throws
   load_all_resume_data();
+
+  m_thread = std::make_shared<std::thread>([=] { poll_alerts(); });
 }
 
 Session::~Session() {
-  m_running = false;
+  if (!m_shut_down) {
+    ShutdownReport report = shutdown(default_shutdown_timeout_ms);
+    if (!report.state_error.empty()) {
+      log(LogLevel::Error,
+          "failed to save session state: " + std::string(report.state_error));
+    }
+  }
+
+  lt_session->abort(); // asynchronous deconstruction
+  lt_session.reset();
+}
+
+ShutdownReport Session::shutdown(std::uint32_t timeout_ms) const {
+  ShutdownReport ret;
+  if (m_shut_down.exchange(true)) {
+    return ret;
+  }
+
+  // no torrent changes after its resume data was saved
+  lt_session->pause();
+
+  // nobody may drain the log anymore, an alert thread blocked on a full log
+  // couldn't handle the save_resume_data_alerts or exit
+  m_logs.close();
 
-  printf("Session destructor 1\n");
+  // the alert thread handles the save_resume_data_alerts, it's stopped
+  // once they arrived or the deadline passed
+  ret.resume_data = save_all_resume_data(timeout_ms);
 
+  m_running = false;
   if (m_thread) {
     m_thread->join();
   }
 
-  lt_session->pause();
-  printf("Session destructor 2\n");
+  try {
+    save_state();
+  } catch (std::exception const& e) {
+    ret.state_error = rust::String::lossy(e.what());
+  }
+  return ret;
+}
 
-  save_all_resume();
+void Session::save_state() const {
+  if (m_session_state_path.empty() && !m_resume_store->enabled()) {
+    return;
+  }
 
-  printf("Session destructor 3\n");
+  // the alert thread autosaves while the user may save or shut down, the
+  // saves share the temporary file
+  std::lock_guard<std::mutex> lock(m_save_state_mutex);
 
-  // save session state
   lt::save_state_flags_t flags(m_save_state_flags);
   std::vector<char> out = lt::write_session_params_buf(lt_session->session_state(flags));
-  if (!save_file(m_session_state_path, out)) {
-    std::fprintf(stderr, "Failed to save session state\n");
+  if (m_session_state_path.empty()) {
+    // throws rust::Error
+    m_resume_store->save_session_state(to_slice(out));
+    return;
   }
 
-  lt_session->abort(); // asynchronous deconstruction
-  lt_session.reset();
-
-  printf("Session destructor 4\n");
+  // write a temporary file, sync it and rename it, so a crash while saving
+  // doesn't leave a truncated session state behind
+  std::string const tmp = m_session_state_path + ".tmp";
+  if (!save_file(tmp, out)) {
+    throw std::runtime_error("failed to write " + tmp);
+  }
+  lt::error_code ec;
+  lt::rename(tmp, m_session_state_path, ec);
+  if (ec) {
+    throw std::runtime_error("failed to rename " + tmp + ": " + ec.message());
+  }
 }
 
-void Session::save_all_resume() const {
-  int outstanding_resume_data = 0; // counter of outstanding resume data
-  printf("outstanding_resume_data 1: %d\n", outstanding_resume_data);
-  std::vector<lt::torrent_handle> handles = lt_session->get_torrents();
-  for (lt::torrent_handle const& h : handles)
+SaveResumeSummary Session::save_all_resume_data(std::uint32_t timeout_ms) const {
+  SaveResumeSummary ret;
+  if (!m_resume_store->enabled()) {
+    return ret;
+  }
+
+  auto const deadline = std::chrono::steady_clock::now() + std::chrono::milliseconds(timeout_ms);
+
+  ResumeBatch batch;
+  std::unique_lock<std::mutex> lock(m_resume_mutex);
+  // the requests are registered before the alerts can be handled, the alert
+  // thread blocks on m_resume_mutex until we wait
+  for (lt::torrent_handle const& h : lt_session->get_torrents()) {
     try {
+      std::string info_hash = best_hex(h.info_hashes());
       h.save_resume_data(lt::torrent_handle::save_info_dict);
-      ++outstanding_resume_data;
-    } catch (lt::system_error const& e) {
-      // the handle was invalid, ignore this one and move to the next
+      batch.pending.insert(info_hash);
+    } catch (lt::system_error const&) {
+      // the handle was invalid, the torrent was removed in the meantime
     }
-  printf("outstanding_resume_data 2: %d\n", outstanding_resume_data);
-
-  while (outstanding_resume_data > 0) {
-    printf("outstanding_resume_data 3: %d\n", outstanding_resume_data);
-    lt::alert const* a = lt_session->wait_for_alert(lt::seconds(30));
-
-    // if we don't get an alert within 30 seconds, abort
-    if (a == nullptr)
-      break;
-
-    std::vector<lt::alert*> alerts;
-    lt_session->pop_alerts(&alerts);
-
-    for (lt::alert* i : alerts) {
-      if (lt::alert_cast<lt::save_resume_data_failed_alert>(i)) {
-        std::fprintf(stderr, "fails to save resume data");
-        --outstanding_resume_data;
-        continue;
-      }
-
-      lt::save_resume_data_alert const* p = lt::alert_cast<lt::save_resume_data_alert>(i);
-      if (p == nullptr) {
-        continue;
-      }
+  }
 
-      // if the save path doesn't exist, don't save the resume data
-      lt::error_code ec;
-      if (!lt::exists(p->params.save_path, ec)) {
-        --outstanding_resume_data;
-        std::fprintf(stderr, "resume data path doesn't exist: %s\n",
-                     p->params.save_path.c_str());
-        continue;
-      }
-      if (ec) {
-        --outstanding_resume_data;
-        std::fprintf(stderr, "lt::exists failed: %s | %s\n", ec.message().c_str(),
-                     p->params.save_path.c_str());
-        continue;
-      }
+  m_resume_batches.push_back(&batch);
+  m_resume_cv.wait_until(lock, deadline, [&batch] { return batch.pending.empty(); });
+  m_resume_batches.erase(std::find(m_resume_batches.begin(), m_resume_batches.end(), &batch));
+  lock.unlock();
 
-      auto const buf = lt::write_resume_data_buf(p->params);
-      auto resume_file = get_resume_file_path(p->params.info_hashes.get_best());
+  for (auto const& info_hash : batch.saved) {
+    ret.saved.push_back(info_hash);
+  }
+  for (auto const& f : batch.failed) {
+    SaveResumeFailure failure;
+    failure.info_hash = f.first;
+    failure.error = rust::String::lossy(f.second);
+    ret.failed.push_back(std::move(failure));
+  }
+  for (auto const& info_hash : batch.pending) {
+    ret.timed_out.push_back(info_hash);
+  }
+  return ret;
+}
 
-      bool ok = save_file(resume_file, buf);
+void Session::resume_data_done(std::string const& info_hash, std::string const& error) const {
+  std::lock_guard<std::mutex> lock(m_resume_mutex);
+  for (ResumeBatch* batch : m_resume_batches) {
+    if (batch->pending.erase(info_hash) == 0) {
+      continue;
+    }
+    if (error.empty()) {
+      batch->saved.push_back(info_hash);
+    } else {
+      batch->failed.emplace_back(info_hash, error);
+    }
+  }
+  m_resume_cv.notify_all();
+}
 
-      if (!ok) {
-        std::fprintf(stderr, "failed to save resume data: %s\n", resume_file.c_str());
-      } else {
-        std::fprintf(stderr, "saved resume data: %s\n", resume_file.c_str());
+void Session::autosave() const {
+  if (m_resume_store->enabled()) {
+    for (lt::torrent_handle const& h : lt_session->get_torrents()) {
+      try {
+        if (h.need_save_resume_data()) {
+          h.save_resume_data(lt::torrent_handle::save_info_dict);
+        }
+      } catch (lt::system_error const&) {
+        // the handle was invalid, the torrent was removed in the meantime
       }
-
-      --outstanding_resume_data;
     }
   }
+
+  try {
+    save_state();
+  } catch (std::exception const& e) {
+    log(LogLevel::Error, std::string("failed to save session state: ") + e.what());
+  }
 }
 
 void assign_session_setting(lt::settings_pack& settings, std::string const& key,
                             std::string const& value) {
-
-  printf("set %s to %s\n", key.data(), value.data());
-
   int const sett_name = lt::setting_by_name(key);
   if (sett_name < 0) {
     throw std::runtime_error("unknown setting: \"" + key + "\"");
@@ -390,7 +627,6 @@ void assign_session_setting(lt::settings_pack& settings, std::string const& key,
       lt::alert_category_t val;
       while (std::getline(flags, f, ',')) {
         auto const it = alert_categories.find(f);
-        printf("alert mask: %s\n  ==> %s\n", f.c_str(), it->first.data());
         if (it == alert_categories.end())
           val |= lt::alert_category_t{unsigned(std::stoi(f))};
         else
@@ -410,106 +646,227 @@ void assign_session_setting(lt::settings_pack& settings, std::string const& key,
   }
 }
 
-std::unique_ptr<Session> create_session(bool min_memory_usage, bool high_performance_seed,
-                                        rust::Slice<const ParamPair> session_param_list,
-                                        std::uint32_t save_state_flags,
-                                        rust::Str session_state_path,
-                                        rust::Str resume_dir, rust::Str torrent_dir,
-                                        std::uint32_t log_size) {
-  std::string ssp = rust_str_to_string(session_state_path);
-  std::string rd = rust_str_to_string(resume_dir);
-  std::string td = rust_str_to_string(torrent_dir);
+// convert the settings of a settings_pack, all of them or only the ones set in
+// the pack. Settings without a name (removed or internal ones) are skipped
+rust::Vec<Setting> cast_settings(lt::settings_pack const& pack, bool only_set = false) {
+  using lt::settings_pack;
 
-  lt::session_params params;
+  rust::Vec<Setting> ret;
+  auto add = [&](int base, int count, SettingType kind) {
+    for (int i = 0; i < count; ++i) {
+      int const sett_name = base + i;
+      char const* name = lt::name_for_setting(sett_name);
+      if (name == nullptr || *name == '\0')
+        continue;
+      if (only_set && !pack.has_val(sett_name))
+        continue;
 
-  lt::save_state_flags_t flags(save_state_flags);
-  std::vector<char> in;
-  if (load_file(ssp, in)) {
-    params = read_session_params(in, flags);
+      Setting s;
+      s.name = name;
+      s.kind = kind;
+      s.int_value = 0;
+      s.bool_value = false;
+      switch (kind) {
+      case SettingType::Str:
+        s.str_value = rust::String::lossy(pack.get_str(sett_name));
+        break;
+      case SettingType::Int:
+        s.int_value = pack.get_int(sett_name);
+        break;
+      case SettingType::Bool:
+        s.bool_value = pack.get_bool(sett_name);
+        break;
+      }
+      ret.push_back(std::move(s));
+    }
+  };
+
+  add(settings_pack::string_type_base, settings_pack::num_string_settings, SettingType::Str);
+  add(settings_pack::int_type_base, settings_pack::num_int_settings, SettingType::Int);
+  add(settings_pack::bool_type_base, settings_pack::num_bool_settings, SettingType::Bool);
+  return ret;
+}
+
+// set the settings in pack, stops at the first unknown setting or value of the
+// wrong type and returns it. The name of the returned error is empty if every
+// setting was set
+SettingError assign_settings(lt::settings_pack& pack, rust::Slice<const Setting> settings) {
+  using lt::settings_pack;
+
+  for (Setting const& s : settings) {
+    std::string name(s.name);
+    int const sett_name = lt::setting_by_name(name);
+    if (sett_name < 0) {
+      return SettingError{s.name, "unknown setting"};
+    }
+
+    switch (sett_name & settings_pack::type_mask) {
+    case settings_pack::string_type_base:
+      if (s.kind != SettingType::Str)
+        return SettingError{s.name, "expected string"};
+      pack.set_str(sett_name, std::string(s.str_value));
+      break;
+    case settings_pack::int_type_base:
+      if (s.kind != SettingType::Int)
+        return SettingError{s.name, "expected integer"};
+      pack.set_int(sett_name, s.int_value);
+      break;
+    case settings_pack::bool_type_base:
+      if (s.kind != SettingType::Bool)
+        return SettingError{s.name, "expected bool"};
+      pack.set_bool(sett_name, s.bool_value);
+      break;
+    }
   }
+  return SettingError{};
+}
 
-  if (min_memory_usage)
-    params.settings = lt::min_memory_usage();
-  if (high_performance_seed)
-    params.settings = lt::high_performance_seed();
+rust::Vec<Setting> default_settings() { return cast_settings(lt::default_settings()); }
 
-  // make parent directories
-  lt::error_code ec;
-  if (lt::has_parent_path(ssp)) {
-    lt::create_directories(lt::parent_path(ssp), ec);
-    if (ec)
-      throw std::runtime_error(ec.message());
+rust::Vec<Setting> min_memory_usage_settings() {
+  return cast_settings(lt::min_memory_usage(), true);
+}
+
+rust::Vec<Setting> high_performance_seed_settings() {
+  return cast_settings(lt::high_performance_seed(), true);
+}
+
+Setting parse_setting(rust::Str name, rust::Str value) {
+  std::string key = rust_str_to_string(name);
+
+  lt::settings_pack pack;
+  try {
+    assign_session_setting(pack, key, rust_str_to_string(value));
+  } catch (std::invalid_argument const&) {
+    throw std::runtime_error("invalid value for \"" + key + "\"");
+  } catch (std::out_of_range const&) {
+    throw std::runtime_error("invalid value for \"" + key + "\"");
   }
 
-  ec.clear();
-  if (!lt::exists(rd, ec)) {
-    ec.clear();
-    lt::create_directories(rd, ec);
-    if (ec)
-      throw std::runtime_error(ec.message());
+  rust::Vec<Setting> settings = cast_settings(pack, true);
+  if (settings.empty()) {
+    throw std::runtime_error("unknown setting: \"" + key + "\"");
   }
+  return settings[0];
+}
+
+// create dir and its parents if it doesn't exist, an empty dir is skipped
+void create_dir(std::string const& dir) {
+  lt::error_code ec;
+  if (dir.empty() || lt::exists(dir, ec))
+    return;
 
   ec.clear();
-  if (!lt::exists(td, ec)) {
-    ec.clear();
-    lt::create_directories(td, ec);
-    if (ec)
-      throw std::runtime_error(ec.message());
+  lt::create_directories(dir, ec);
+  if (ec)
+    throw std::runtime_error(ec.message());
+}
+
+std::unique_ptr<Session> create_session(rust::Slice<const Setting> settings,
+                                        SessionOptions const& options,
+                                        rust::Box<ResumeStoreBox> resume_store,
+                                        rust::Box<AlertSink> alert_sink) {
+  std::string ssp(options.session_state_path);
+
+  lt::session_params params;
+
+  lt::save_state_flags_t flags(options.save_state_flags);
+  std::vector<char> in;
+  if (!ssp.empty()) {
+    if (load_file(ssp, in)) {
+      params = read_session_params(in, flags);
+    }
+  } else {
+    rust::Vec<std::uint8_t> state = resume_store->load_session_state();
+    if (!state.empty()) {
+      params = read_session_params(to_span(state), flags);
+    }
+  }
+
+  // make parent directories
+  if (lt::has_parent_path(ssp)) {
+    create_dir(lt::parent_path(ssp));
   }
 
-  auto& settings = params.settings;
-  for (ParamPair const& sp : session_param_list) {
-    assign_session_setting(settings, rust_str_to_string(sp.key),
-                           rust_str_to_string(sp.value));
+  SettingError err = assign_settings(params.settings, settings);
+  if (!err.name.empty()) {
+    throw std::runtime_error("invalid setting \"" + std::string(err.name) +
+                             "\": " + std::string(err.message));
   }
 
-  return std::make_unique<Session>(std::move(params), save_state_flags, ssp, rd, td,
-                                   log_size);
+  return std::make_unique<Session>(std::move(params), options, std::move(resume_store),
+                                   std::move(alert_sink));
 }
 
-std::string Session::get_resume_file_path(lt::sha1_hash info_hash) const {
-  std::string info_hash_str = to_hex(info_hash);
-  std::string resume_file(m_resume_dir);
-  lt::append_path(resume_file, info_hash_str + ".resume");
-  return resume_file;
+// load_torrent_file() and load_torrent_buffer() throw on invalid torrents
+template <typename Load>
+lt::error_code try_load_torrent(Load load, lt::add_torrent_params& atp) {
+  try {
+    atp = load();
+  } catch (lt::system_error const& e) {
+    return e.code();
+  }
+  return lt::error_code();
 }
 
-std::string Session::get_torrent_file_path(lt::sha1_hash info_hash) const {
-  std::string info_hash_str = to_hex(info_hash);
-  std::string torrent_file(m_torrent_dir);
-  lt::append_path(torrent_file, info_hash_str + ".torrent");
-  return torrent_file;
+// reads resume data, missing (empty) data is an error too
+lt::error_code read_resume(rust::Vec<std::uint8_t> const& data, lt::add_torrent_params& atp) {
+  if (data.empty()) {
+    return lt::error_code(boost::system::errc::no_such_file_or_directory, lt::generic_category());
+  }
+
+  lt::error_code ec;
+  lt::add_torrent_params p = lt::read_resume_data(to_span(data), ec);
+  if (!ec) {
+    atp = std::move(p);
+  }
+  return ec;
 }
 
-void Session::load_all_resume_data() const {
-  std::vector<lt::add_torrent_params> resume_data_list;
-  std::vector<std::string> files = list_dir(m_resume_dir);
-  for (auto& f : files) {
-    if (!(f.size() > 7 && f.substr(f.size() - 7) == ".resume"))
-      continue;
+void Session::load_all_resume_data() {
+  if (!m_resume_store->enabled()) {
+    return;
+  }
 
-    std::vector<char> resume_data;
-    if (!load_file(f, resume_data)) {
-      continue;
-    }
+  rust::Vec<ResumeEntry> entries = m_resume_store->load_all();
 
-    lt::error_code ec;
-    lt::add_torrent_params p = lt::read_resume_data(resume_data, ec);
+  std::vector<lt::add_torrent_params> resume_data_list;
+  for (auto const& entry : entries) {
+    lt::add_torrent_params p;
+    lt::error_code ec = read_resume(entry.resume_data, p);
     if (ec) {
-      continue;
+      ResumeLoadFailure failure;
+      failure.info_hash = entry.info_hash;
+      failure.error = rust::String::lossy(ec.message());
+      failure.recovery = ResumeRecovery::NotRecovered;
+
+      if (!read_resume(entry.backup, p)) {
+        failure.recovery = ResumeRecovery::Backup;
+      } else if (!entry.torrent.empty() && !m_recovery_save_path.empty() &&
+                 !try_load_torrent([&] { return lt::load_torrent_buffer(to_span(entry.torrent)); },
+                                   p)) {
+        // without resume data the files which exist are checked when the
+        // torrent is added
+        p.save_path = m_recovery_save_path;
+        failure.recovery = ResumeRecovery::TorrentFile;
+      }
+
+      bool recovered = failure.recovery != ResumeRecovery::NotRecovered;
+      m_resume_load_failures.push_back(std::move(failure));
+      if (!recovered) {
+        continue;
+      }
     }
 
-    // if save_path doesn't exist, skip
+    // if save_path doesn't exist (e.g. a drive which isn't mounted yet),
+    // keep the torrent as orphaned instead of adding it
     ec.clear();
-    if (!lt::exists(p.save_path, ec)) {
-      continue;
-    }
-    if (ec) {
+    if (!lt::exists(p.save_path, ec) || ec) {
+      std::lock_guard<std::mutex> lock(m_orphans_mutex);
+      m_orphans[std::string(entry.info_hash)] = std::move(p);
       continue;
     }
 
-    printf("load resume data: %s\n", f.c_str());
-
     resume_data_list.emplace_back(std::move(p));
   }
 
@@ -524,183 +881,540 @@ void Session::load_all_resume_data() const {
   }
 }
 
-void assign_torrent_setting(lt::add_torrent_params& atp, std::string const& key,
-                            std::string const& value) {
-  using namespace lt::literals;
+// apply the fields of `params` which are set on top of `atp`, see
+// AddTorrentParams in lib.rs. The values are validated on the Rust side.
+void apply_add_torrent_params(lt::add_torrent_params& atp, AddTorrentParams const& params) {
+  if (!params.save_path.empty()) {
+    atp.save_path = lt::canonicalize_path(std::string(params.save_path));
+  }
 
-  printf("torrent set %s = %s\n", key.data(), value.data());
+  if (!params.name.empty()) {
+    atp.name = std::string(params.name);
+  }
 
-  if (key == "trackers"_sv) {
-    std::vector<std::string> trackers;
-    boost::split(trackers, value, boost::is_any_of(","));
-    for (auto& t : trackers) {
-      t = boost::trim_copy(t);
-      if (t.empty())
-        continue;
-      auto it = std::find(atp.trackers.begin(), atp.trackers.end(), t);
-      if (it != atp.trackers.end())
-        continue;
-      printf("add trackers: %s\n", t.c_str());
-      atp.trackers.emplace_back(t);
-    }
+  // tracker_tiers may be shorter than trackers, missing tiers are 0
+  atp.tracker_tiers.resize(atp.trackers.size(), 0);
+  for (std::size_t i = 0; i < params.trackers.size(); i++) {
+    std::string t(params.trackers[i]);
+    auto it = std::find(atp.trackers.begin(), atp.trackers.end(), t);
+    if (it != atp.trackers.end())
+      continue;
+    atp.trackers.emplace_back(std::move(t));
+    atp.tracker_tiers.push_back(i < params.tracker_tiers.size() ? params.tracker_tiers[i] : 0);
   }
 
-  if (key == "dht_nodes"_sv) {
-    std::vector<std::pair<std::string, int>> node_pairs;
-    std::vector<std::string> nodes;
-    boost::split(nodes, value, boost::is_any_of(","));
-    for (auto& n : nodes) {
-      n = boost::trim_copy(n);
-      if (n.empty())
-        continue;
+  for (auto& n : params.dht_nodes) {
+    std::pair<std::string, int> node = std::make_pair(std::string(n.host), int(n.port));
+    auto it = std::find(atp.dht_nodes.begin(), atp.dht_nodes.end(), node);
+    if (it != atp.dht_nodes.end())
+      continue;
+    atp.dht_nodes.emplace_back(std::move(node));
+  }
 
-      std::vector<std::string> items;
-      boost::split(items, n, boost::is_any_of(":"));
-      if (items.size() != 2)
-        continue;
-      std::pair<std::string, int> node = std::make_pair(items[0], atoi(items[1].c_str()));
+  if (params.has_flags) {
+    atp.flags = lt::torrent_flags_t(params.flags);
+  }
 
-      auto it = std::find(atp.dht_nodes.begin(), atp.dht_nodes.end(), node);
-      if (it != atp.dht_nodes.end())
-        continue;
-      atp.dht_nodes.emplace_back(std::move(node));
-    }
+  if (params.has_storage_mode) {
+    atp.storage_mode = static_cast<lt::storage_mode_t>(params.storage_mode);
   }
 
-  if (key == "name"_sv) {
-    atp.name = value;
+  if (params.has_max_uploads) {
+    atp.max_uploads = params.max_uploads;
   }
 
-  if (key == "save_path"_sv) {
-    atp.save_path = lt::canonicalize_path(value);
+  if (params.has_max_connections) {
+    atp.max_connections = params.max_connections;
   }
 
-  if (key == "storage_mode"_sv) {
-    if (value == "storage_mode_sparse"_sv) {
-      atp.storage_mode = lt::storage_mode_t::storage_mode_sparse;
-    } else if (value == "storage_mode_allocate"_sv) {
-      atp.storage_mode = lt::storage_mode_t::storage_mode_allocate;
-    } else {
-      printf("unknown storage mode: %s\n", value.data());
-      atp.storage_mode = lt::storage_mode_t::storage_mode_sparse;
-    }
+  if (params.has_upload_limit) {
+    atp.upload_limit = params.upload_limit;
   }
 
-  if (key == "flags"_sv) {
-    atp.flags = static_cast<lt::torrent_flags_t>(atoi(value.data()));
+  if (params.has_download_limit) {
+    atp.download_limit = params.download_limit;
   }
 
-  if (key == "max_uploads") {
-    atp.max_uploads = atoi(value.data());
+  if (!params.file_priorities.empty()) {
+    atp.file_priorities.clear();
+    for (auto p : params.file_priorities) {
+      atp.file_priorities.push_back(lt::download_priority_t(p));
+    }
   }
 
-  if (key == "max_connections") {
-    atp.max_connections = atoi(value.data());
+  for (auto& f : params.renamed_files) {
+    atp.renamed_files[lt::file_index_t(f.file_index)] = std::string(f.path);
   }
 
-  if (key == "upload_limit") {
-    atp.upload_limit = atoi(value.data());
+  for (auto& u : params.url_seeds) {
+    std::string url(u);
+    auto it = std::find(atp.url_seeds.begin(), atp.url_seeds.end(), url);
+    if (it != atp.url_seeds.end())
+      continue;
+    atp.url_seeds.emplace_back(std::move(url));
   }
 
-  if (key == "download_limit") {
-    atp.download_limit = atoi(value.data());
+  for (auto& p : params.peers) {
+    lt::error_code ec;
+    lt::address addr = lt::make_address(std::string(p.host), ec);
+    if (ec) {
+      throw std::system_error(ec);
+    }
+    atp.peers.emplace_back(addr, p.port);
   }
 }
 
-void Session::add_torrent_from_parmas(
-    lt::add_torrent_params atp, rust::Slice<const ParamPair> torrent_param_list) const {
-  using lt::storage_mode_t;
+AddTorrentError classify_add_torrent_error(lt::error_code const& ec) {
+  if (!ec) {
+    return AddTorrentError::None;
+  }
+
+  if (ec == lt::errors::duplicate_torrent) {
+    return AddTorrentError::Duplicate;
+  }
+
+  if (ec.category() == lt::bdecode_category()) {
+    return AddTorrentError::InvalidMetadata;
+  }
+
+  if (ec.category() == lt::libtorrent_category()) {
+    switch (ec.value()) {
+    case lt::errors::torrent_is_no_dict:
+    case lt::errors::torrent_missing_info:
+    case lt::errors::torrent_info_no_dict:
+    case lt::errors::torrent_missing_piece_length:
+    case lt::errors::torrent_missing_name:
+    case lt::errors::torrent_invalid_name:
+    case lt::errors::torrent_invalid_length:
+    case lt::errors::torrent_file_parse_failed:
+    case lt::errors::torrent_missing_pieces:
+    case lt::errors::torrent_invalid_hashes:
+    case lt::errors::too_many_pieces_in_torrent:
+    case lt::errors::invalid_swarm_metadata:
+    case lt::errors::invalid_bencoding:
+    case lt::errors::no_files_in_torrent:
+    case lt::errors::no_metadata:
+      return AddTorrentError::InvalidMetadata;
+    default:
+      break;
+    }
+  }
+
+  return AddTorrentError::Other;
+}
 
-  for (auto& p : torrent_param_list) {
-    assign_torrent_setting(atp, rust_str_to_string(p.key), rust_str_to_string(p.value));
+AddTorrentError classify_add_torrent_error(ErrorCode const& error) {
+  std::string category(error.category);
+  if (category == lt::libtorrent_category().name()) {
+    return classify_add_torrent_error(lt::error_code(error.value, lt::libtorrent_category()));
   }
+  if (category == lt::bdecode_category().name()) {
+    return classify_add_torrent_error(lt::error_code(error.value, lt::bdecode_category()));
+  }
+  return error.value == 0 ? AddTorrentError::None : AddTorrentError::Other;
+}
 
-  lt_session->async_add_torrent(std::move(atp));
+namespace {
+
+// the operation_error last passed to rust on this thread
+thread_local std::unique_ptr<operation_error> t_operation_error;
+
+} // namespace
+
+void keep_operation_error(operation_error const* e) {
+  t_operation_error.reset(e ? new operation_error(*e) : nullptr);
 }
 
-// add torrent to session
-// - torrent_path: path to torrent file
-// - torrent_param_list: list of key-value pairs see: libtorrent/add_torrent_params.hpp
-void Session::add_torrent(rust::Str torrent_path,
-                          rust::Slice<const ParamPair> torrent_param_list) const {
-  std::string tp = rust_str_to_string(torrent_path);
-  std::printf("Add %s\n", tp.data());
+OperationError take_operation_error() {
+  OperationError ret;
+  ret.kind = OperationErrorKind::None;
+  ret.file_index = -1;
 
-  lt::add_torrent_params atp = lt::load_torrent_file(tp);
-  std::vector<char> resume_data;
-  if (load_file(get_resume_file_path(atp.info_hashes.get_best()), resume_data)) {
-    lt::error_code ec;
-    lt::add_torrent_params rd = lt::read_resume_data(resume_data, ec);
-    if (ec)
-      // TODO: add to log
-      std::printf("  failed to load resume data: %s\n", ec.message().c_str());
-    else
-      atp = rd;
+  std::unique_ptr<operation_error> e = std::move(t_operation_error);
+  if (e) {
+    ret.kind = e->kind;
+    ret.info_hash = e->info_hash;
+    ret.file_index = e->file_index;
+    ret.error = cast_error_code(e->ec);
   }
+  return ret;
+}
+
+AddTorrentResult make_add_torrent_result(lt::add_torrent_params const& atp) {
+  lt::info_hash_t info_hashes = atp.ti ? atp.ti->info_hashes() : atp.info_hashes;
 
-  add_torrent_from_parmas(atp, torrent_param_list);
+  AddTorrentResult ret;
+  ret.info_hash = best_hex(info_hashes);
+  ret.info_hashes = cast_info_hashes(info_hashes);
+  ret.save_path = atp.save_path;
+  ret.error_kind = AddTorrentError::None;
+  ret.error = cast_error_code(lt::error_code());
+  return ret;
 }
 
-void Session::add_magnet(rust::Str magnet_uri,
-                         rust::Slice<const ParamPair> torrent_param_list) const {
-  std::string mu = rust_str_to_string(magnet_uri);
-  std::printf("Add %s\n", mu.data());
+AddTorrentResult make_add_torrent_error(AddTorrentError kind, lt::error_code const& ec) {
+  AddTorrentResult ret;
+  ret.error_kind = kind;
+  ret.error = cast_error_code(ec);
+  return ret;
+}
 
+// the save path must be set and be (or become) a directory
+lt::error_code check_save_path(std::string const& save_path) {
   lt::error_code ec;
-  lt::add_torrent_params atp = lt::parse_magnet_uri(mu, ec);
-  if (ec) {
-    throw std::system_error(ec);
+  if (save_path.empty()) {
+    ec = lt::error_code(boost::system::errc::invalid_argument, lt::generic_category());
+    return ec;
   }
 
-  ec.clear();
-  std::vector<char> resume_data;
-  if (load_file(get_resume_file_path(atp.info_hashes.get_best()), resume_data)) {
-    lt::add_torrent_params rd = lt::read_resume_data(resume_data, ec);
+  lt::create_directories(save_path, ec);
+  return ec;
+}
+
+// loads the resume data of `params`, or else of the resume file of the
+// torrent, into `atp`
+lt::error_code Session::load_resume_data(lt::add_torrent_params& atp,
+                                         AddTorrentParams const& params) const {
+  lt::error_code ec;
+
+  if (!params.resume_data.empty()) {
+    lt::span<char const> buf(reinterpret_cast<char const*>(params.resume_data.data()),
+                             params.resume_data.size());
+    lt::add_torrent_params rd = lt::read_resume_data(buf, ec);
+    if (!ec) {
+      atp = std::move(rd);
+    }
+    return ec;
+  }
+
+  std::string const info_hash = best_hex(atp.info_hashes);
+  rust::Vec<std::uint8_t> resume_data;
+  try {
+    resume_data = m_resume_store->load(info_hash);
+  } catch (rust::Error const& e) {
+    log(LogLevel::Error, "failed to load resume data of " + info_hash + ": " + e.what(),
+        atp.info_hashes);
+  }
+
+  if (!resume_data.empty()) {
+    lt::add_torrent_params rd = lt::read_resume_data(to_span(resume_data), ec);
     if (ec)
-      // TODO: add to log
-      std::printf("  failed to load resume data: %s\n", ec.message().c_str());
+      log(LogLevel::Warn, "failed to read resume data of " + info_hash + ": " + ec.message(),
+          atp.info_hashes);
     else
-      atp = rd;
+      atp = std::move(rd);
   }
 
-  add_torrent_from_parmas(atp, torrent_param_list);
+  return lt::error_code();
 }
 
-std::unique_ptr<TorrentHandle>
-Session::get_torrent_handle(rust::Str info_hash_str) const {
-  lt::torrent_handle h = find_torrent_handle(info_hash_str);
-  return std::make_unique<TorrentHandle>(h, const_cast<Session*>(this));
-}
+AddTorrentResult Session::add_torrent_from_params(lt::add_torrent_params atp,
+                                                  AddTorrentParams const& params,
+                                                  bool async) const {
+  lt::error_code ec = load_resume_data(atp, params);
+  if (ec) {
+    return make_add_torrent_error(AddTorrentError::InvalidMetadata, ec);
+  }
 
-void Session::remove_torrent(rust::Str info_hash_str, bool delete_files) const {
-  lt::torrent_handle h = find_torrent_handle(info_hash_str);
+  apply_add_torrent_params(atp, params);
 
-  if (!h.is_valid()) {
-    return;
+  AddTorrentResult ret = make_add_torrent_result(atp);
+
+  ec = check_save_path(atp.save_path);
+  if (ec) {
+    ret.error_kind = AddTorrentError::InvalidSavePath;
+    ret.error = cast_error_code(ec);
+    return ret;
   }
 
-  // remove resume data
-  lt::error_code ec;
-  auto resume_file = get_resume_file_path(h.info_hashes().get_best());
-  if (lt::exists(resume_file, ec)) {
-    ec.clear();
-    lt::remove(resume_file, ec);
+  if (async) {
+    lt_session->async_add_torrent(std::move(atp));
+    return ret;
   }
 
-  // remove torrent file
-  ec.clear();
-  auto torrent_file = get_torrent_file_path(h.info_hashes().get_best());
-  if (lt::exists(torrent_file, ec)) {
-    ec.clear();
-    lt::remove(torrent_file, ec);
+  lt::torrent_handle h = lt_session->add_torrent(std::move(atp), ec);
+  if (ec) {
+    ret.error_kind = classify_add_torrent_error(ec);
+    ret.error = cast_error_code(ec);
+    return ret;
   }
 
-  lt_session->remove_torrent(h, delete_files ? lt::session::delete_files
-                                             : lt::session::delete_partfile);
+  ret.handle = std::make_unique<TorrentHandle>(h, const_cast<Session*>(this));
+  return ret;
 }
 
-TwoSessionStats Session::get_stats() const {
-  TwoSessionStats tss;
+// add torrent to session
+// - torrent_path: path to torrent file
+// - params: see AddTorrentParams and libtorrent/add_torrent_params.hpp
+AddTorrentResult Session::add_torrent(rust::Str torrent_path,
+                                      AddTorrentParams const& params) const {
+  std::string tp = rust_str_to_string(torrent_path);
+
+  lt::add_torrent_params atp;
+  lt::error_code ec = try_load_torrent([&] { return lt::load_torrent_file(tp); }, atp);
+  if (ec) {
+    return make_add_torrent_error(classify_add_torrent_error(ec), ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, false);
+}
+
+AddTorrentResult Session::add_magnet(rust::Str magnet_uri, AddTorrentParams const& params) const {
+  std::string mu = rust_str_to_string(magnet_uri);
+
+  lt::error_code ec;
+  lt::add_torrent_params atp = lt::parse_magnet_uri(mu, ec);
+  if (ec) {
+    return make_add_torrent_error(AddTorrentError::Other, ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, false);
+}
+
+AddTorrentResult Session::add_torrent_bytes(rust::Slice<const std::uint8_t> data,
+                                            AddTorrentParams const& params) const {
+  lt::add_torrent_params atp;
+  lt::error_code ec = try_load_torrent([&] { return lt::load_torrent_buffer(to_span(data)); }, atp);
+  if (ec) {
+    return make_add_torrent_error(classify_add_torrent_error(ec), ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, false);
+}
+
+AddTorrentResult Session::async_add_torrent(rust::Str torrent_path,
+                                            AddTorrentParams const& params) const {
+  std::string tp = rust_str_to_string(torrent_path);
+
+  lt::add_torrent_params atp;
+  lt::error_code ec = try_load_torrent([&] { return lt::load_torrent_file(tp); }, atp);
+  if (ec) {
+    return make_add_torrent_error(classify_add_torrent_error(ec), ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, true);
+}
+
+AddTorrentResult Session::async_add_magnet(rust::Str magnet_uri,
+                                           AddTorrentParams const& params) const {
+  std::string mu = rust_str_to_string(magnet_uri);
+
+  lt::error_code ec;
+  lt::add_torrent_params atp = lt::parse_magnet_uri(mu, ec);
+  if (ec) {
+    return make_add_torrent_error(AddTorrentError::Other, ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, true);
+}
+
+AddTorrentResult Session::async_add_torrent_bytes(rust::Slice<const std::uint8_t> data,
+                                                  AddTorrentParams const& params) const {
+  lt::add_torrent_params atp;
+  lt::error_code ec = try_load_torrent([&] { return lt::load_torrent_buffer(to_span(data)); }, atp);
+  if (ec) {
+    return make_add_torrent_error(classify_add_torrent_error(ec), ec);
+  }
+
+  return add_torrent_from_params(std::move(atp), params, true);
+}
+
+rust::Vec<std::uint8_t> create_torrent(CreateTorrentParams const& params,
+                                       CreateTorrentProgress const& progress) {
+  std::string path = lt::complete(std::string(params.path));
+
+  lt::create_flags_t flags = {};
+  if (params.version == TorrentVersion::V1Only) {
+    flags |= lt::create_torrent::v1_only;
+  } else if (params.version == TorrentVersion::V2Only) {
+    flags |= lt::create_torrent::v2_only;
+  }
+
+  lt::file_storage fs;
+  lt::add_files(fs, path, flags);
+  if (fs.num_files() == 0) {
+    throw std::runtime_error("no files in " + path);
+  }
+
+  lt::create_torrent ct(fs, params.piece_size, flags);
+
+  for (std::size_t i = 0; i < params.trackers.size(); i++) {
+    int tier = i < params.tracker_tiers.size() ? params.tracker_tiers[i] : 0;
+    ct.add_tracker(std::string(params.trackers[i]), tier);
+  }
+  for (auto& ws : params.web_seeds) {
+    ct.add_url_seed(std::string(ws));
+  }
+  if (!params.comment.empty()) {
+    ct.set_comment(std::string(params.comment).c_str());
+  }
+  if (!params.creator.empty()) {
+    ct.set_creator(std::string(params.creator).c_str());
+  }
+  ct.set_priv(params.is_private);
+
+  auto const num_pieces = static_cast<std::uint32_t>(ct.num_pieces());
+  std::uint32_t hashed = 0;
+
+  // the callback must not throw through libtorrent's disk job loop. setting
+  // the error code passed to set_piece_hashes() is how libtorrent lets the
+  // callback abort, no more hash jobs are issued once it is set
+  lt::error_code ec;
+  lt::set_piece_hashes(
+      ct, lt::parent_path(path),
+      [&](lt::piece_index_t) {
+        if (ec) {
+          return;
+        }
+        hashed++;
+        if (!progress.on_progress(hashed, num_pieces)) {
+          ec = lt::errors::make_error_code(lt::errors::torrent_aborted);
+        }
+      },
+      ec);
+  if (progress.is_cancelled()) {
+    throw std::runtime_error("cancelled");
+  }
+  if (ec) {
+    throw std::system_error(ec);
+  }
+
+  std::vector<char> buf;
+  lt::bencode(std::back_inserter(buf), ct.generate());
+
+  rust::Vec<std::uint8_t> ret;
+  ret.reserve(buf.size());
+  for (auto c : buf) {
+    ret.push_back(static_cast<std::uint8_t>(c));
+  }
+  return ret;
+}
+
+TorrentInfoError classify_torrent_info_error(lt::error_code const& ec) {
+  if (!ec) {
+    return TorrentInfoError::None;
+  }
+
+  if (ec.category() == lt::bdecode_category() || ec == lt::errors::torrent_is_no_dict ||
+      ec == lt::errors::invalid_bencoding) {
+    return TorrentInfoError::InvalidBencode;
+  }
+
+  if (ec == lt::errors::torrent_missing_info || ec == lt::errors::torrent_info_no_dict) {
+    return TorrentInfoError::MissingInfoDict;
+  }
+
+  if (ec == lt::errors::torrent_missing_piece_length) {
+    return TorrentInfoError::InvalidPieceLength;
+  }
+
+  return TorrentInfoError::Other;
+}
+
+ParseTorrentInfoResult parse_torrent_info(rust::Slice<const std::uint8_t> data) {
+  ParseTorrentInfoResult ret;
+
+  lt::error_code ec;
+  lt::torrent_info ti(to_span(data), ec, lt::from_span);
+  ret.error_kind = classify_torrent_info_error(ec);
+  ret.error = cast_error_code(ec);
+  if (ec) {
+    return ret;
+  }
+
+  ret.info = cast_torrent_info(ti);
+  ret.info.torrent_file.reserve(data.size());
+  for (auto b : data) {
+    ret.info.torrent_file.push_back(b);
+  }
+  return ret;
+}
+
+std::string Session::save_resume_data(lt::add_torrent_params const& params) const {
+  if (!m_resume_store->enabled()) {
+    return "no resume store";
+  }
+
+  // if the save path doesn't exist, don't save the resume data
+  lt::error_code ec;
+  if (!lt::exists(params.save_path, ec) || ec) {
+    return "save path doesn't exist: " + params.save_path;
+  }
+
+  std::vector<char> const buf = lt::write_resume_data_buf(params);
+
+  // the .torrent file, once the metadata is known
+  std::vector<char> torrent;
+  if (params.ti) {
+    try {
+      lt::entry e = lt::write_torrent_file(params, lt::write_flags::allow_missing_piece_layer);
+      lt::bencode(std::back_inserter(torrent), e);
+    } catch (lt::system_error const& e) {
+      torrent.clear();
+    }
+  }
+
+  std::string info_hash = best_hex(params.info_hashes);
+  try {
+    m_resume_store->save(info_hash, to_slice(buf), to_slice(torrent));
+  } catch (rust::Error const& e) {
+    log(LogLevel::Error, "failed to save resume data of " + info_hash + ": " + e.what(),
+        params.info_hashes);
+    return e.what();
+  }
+
+  // a torrent added from a v2 magnet link was saved under its v2 info-hash
+  // until its metadata showed it's hybrid, drop that entry or it would be
+  // added again next to this one
+  for (auto const& key : all_hex(params.info_hashes)) {
+    if (key == info_hash) {
+      continue;
+    }
+    try {
+      m_resume_store->remove(key);
+    } catch (rust::Error const& e) {
+      log(LogLevel::Error, "failed to remove resume data of " + key + ": " + e.what(),
+          params.info_hashes);
+    }
+  }
+  return std::string();
+}
+
+std::unique_ptr<TorrentHandle>
+Session::get_torrent_handle(rust::Str info_hash_str) const {
+  lt::torrent_handle h = find_torrent_handle(info_hash_str);
+
+  if (!h.is_valid()) {
+    throw_invalid_handle();
+  }
+
+  return std::make_unique<TorrentHandle>(h, const_cast<Session*>(this));
+}
+
+void Session::remove_torrent(rust::Str info_hash_str, bool delete_files) const {
+  lt::torrent_handle h = find_torrent_handle(info_hash_str);
+
+  if (!h.is_valid()) {
+    throw_invalid_handle();
+  }
+
+  // remove resume data and torrent file, under every info-hash the torrent
+  // may have been saved with
+  for (auto const& info_hash : all_hex(h.info_hashes())) {
+    try {
+      m_resume_store->remove(info_hash);
+    } catch (rust::Error const& e) {
+      log(LogLevel::Error, "failed to remove resume data of " + info_hash + ": " + e.what(),
+          h.info_hashes());
+    }
+  }
+
+  lt_session->remove_torrent(h, delete_files ? lt::session::delete_files
+                                             : lt::session::delete_partfile);
+}
+
+TwoSessionStats Session::get_stats() const {
+  TwoSessionStats tss;
 
   for (auto& s : m_session_stats.stats()) {
     tss.stats.push_back(s);
@@ -747,6 +1461,121 @@ void Session::pause() const { lt_session->pause(); }
 void Session::resume() const { lt_session->resume(); }
 bool Session::is_paused() const { return lt_session->is_paused(); }
 
+SettingError Session::apply_settings(rust::Slice<const Setting> settings) const {
+  lt::settings_pack pack;
+  SettingError err = assign_settings(pack, settings);
+  if (err.name.empty()) {
+    lt_session->apply_settings(std::move(pack));
+  }
+  return err;
+}
+
+rust::Vec<Setting> Session::get_settings() const {
+  return cast_settings(lt_session->get_settings());
+}
+
+// the level of the log record of an alert, by its category
+LogLevel alert_log_level(lt::alert const* a) {
+  lt::alert_category_t const c = a->category();
+  if (c & lt::alert_category::error) {
+    return LogLevel::Error;
+  }
+  if (c & lt::alert_category::performance_warning) {
+    return LogLevel::Warn;
+  }
+  if (c & (lt::alert_category::peer_log | lt::alert_category::picker_log)) {
+    return LogLevel::Trace;
+  }
+  if (c & (lt::alert_category::session_log | lt::alert_category::torrent_log |
+           lt::alert_category::dht_log | lt::alert_category::port_mapping_log |
+           lt::alert_category::dht_operation | lt::alert_category::connect |
+           lt::alert_category::incoming_request | lt::alert_category::block_progress)) {
+    return LogLevel::Debug;
+  }
+  return LogLevel::Info;
+}
+
+// the target of the log record of an alert, peer, tracker and storage alerts
+// are torrent alerts too
+LogTarget alert_log_target(lt::alert const* a) {
+  lt::alert_category_t const c = a->category();
+  if (c & (lt::alert_category::dht | lt::alert_category::dht_log |
+           lt::alert_category::dht_operation)) {
+    return LogTarget::Dht;
+  }
+  if (c & (lt::alert_category::port_mapping | lt::alert_category::port_mapping_log)) {
+    return LogTarget::PortMapping;
+  }
+  if (dynamic_cast<lt::peer_alert const*>(a) != nullptr) {
+    return LogTarget::Peer;
+  }
+  if (dynamic_cast<lt::tracker_alert const*>(a) != nullptr) {
+    return LogTarget::Tracker;
+  }
+  if (c & lt::alert_category::storage) {
+    return LogTarget::Storage;
+  }
+  if (dynamic_cast<lt::torrent_alert const*>(a) != nullptr) {
+    return LogTarget::Torrent;
+  }
+  return LogTarget::Session;
+}
+
+LogEvent make_log_event(lt::alert const* a) {
+  LogEvent e;
+  e.timestamp = a->timestamp();
+  e.message = a->message();
+  e.alert_type = a->what();
+  e.category = static_cast<std::uint32_t>(a->category());
+  e.level = static_cast<std::uint8_t>(alert_log_level(a));
+  e.target = static_cast<std::uint8_t>(alert_log_target(a));
+
+  if (auto const* t = dynamic_cast<lt::torrent_alert const*>(a)) {
+    e.torrent_name = t->torrent_name();
+    try {
+      if (t->handle.is_valid()) {
+        e.info_hashes = t->handle.info_hashes();
+      }
+    } catch (lt::system_error const&) {
+      // the torrent was removed in the meantime
+    }
+  }
+  if (auto const* p = dynamic_cast<lt::peer_alert const*>(a)) {
+    e.peer = endpoint_to_string(p->endpoint);
+  }
+  return e;
+}
+
+Log cast_log(LogEvent const& e) {
+  Log l;
+  l.message = rust::String::lossy(e.message);
+  l.timestamp = e.timestamp.time_since_epoch().count() / 1000000; // milliseconds
+  l.alert_type = e.alert_type;
+  l.category = e.category;
+  l.level = static_cast<LogLevel>(e.level);
+  l.target = static_cast<LogTarget>(e.target);
+  l.info_hash = best_hex(e.info_hashes);
+  l.torrent_name = rust::String::lossy(e.torrent_name);
+  l.peer = e.peer;
+  return l;
+}
+
+// whether `hex` is the v1, v2 or best info-hash of `ih`
+bool match_info_hash(lt::info_hash_t const& ih, std::string const& hex) {
+  return (ih.has_v1() && to_hex(ih.v1) == hex) || (ih.has_v2() && to_hex(ih.v2) == hex) ||
+         ((ih.has_v1() || ih.has_v2()) && to_hex(ih.get_best()) == hex);
+}
+
+bool match_log_filter(LogFilter const& filter, std::string const& info_hash, LogEvent const& e) {
+  if (filter.categories != 0 && (e.category & filter.categories) == 0) {
+    return false;
+  }
+  if (e.level > static_cast<std::uint8_t>(filter.max_level)) {
+    return false;
+  }
+  return info_hash.empty() || match_info_hash(e.info_hashes, info_hash);
+}
+
 // Handle an alert
 // Note: only called from Session::pop_alerts
 bool Session::handle_alert(lt::alert* a) {
@@ -803,15 +1632,18 @@ bool Session::handle_alert(lt::alert* a) {
   }
 
   if (add_torrent_alert* p = alert_cast<add_torrent_alert>(a)) {
-    if (p->error) {
-      // TODO: handle the error
-      std::fprintf(stderr, "failed to add torrent: %s %s\n",
-                   p->params.ti ? p->params.ti->name().c_str() : p->params.name.c_str(),
-                   p->error.message().c_str());
-    } else {
+    // a failed add_torrent_alert is logged like any other error alert
+    if (!p->error) {
       torrent_handle h = p->handle;
       h.save_resume_data(torrent_handle::save_info_dict |
                          torrent_handle::if_metadata_changed);
+
+      // an orphaned torrent which is added again isn't orphaned anymore
+      std::lock_guard<std::mutex> lock(m_orphans_mutex);
+      auto it = find_orphan(best_hex(h.info_hashes()));
+      if (it != m_orphans.end()) {
+        m_orphans.erase(it);
+      }
     }
     return false;
   }
@@ -827,65 +1659,21 @@ bool Session::handle_alert(lt::alert* a) {
   }
 
   if (save_resume_data_alert* p = alert_cast<save_resume_data_alert>(a)) {
-    auto const buf = lt::write_resume_data_buf(p->params);
-    auto resume_file = get_resume_file_path(p->params.info_hashes.get_best());
-
-    // if the save path doesn't exist, don't save the resume data
-    lt::error_code ec;
-    if (!lt::exists(p->params.save_path, ec)) {
-      return false;
-    }
-    if (ec) {
-      return false;
-    }
-
-    bool ok = save_file(resume_file, buf);
-
-    if (!ok) {
-      std::fprintf(stderr, "failed to save resume data: %s\n", resume_file.c_str());
-      return false;
-    }
-
-    // save torrent file
-    ec.clear();
-    auto torrent_file = get_torrent_file_path(p->params.info_hashes.get_best());
-    if (!lt::exists(torrent_file, ec)) {
-      try {
-        lt::entry e =
-            lt::write_torrent_file(p->params, lt::write_flags::allow_missing_piece_layer);
-
-        printf("Saving torrent file: %s\n", torrent_file.c_str());
-
-        std::vector<char> torrent;
-        lt::bencode(std::back_inserter(torrent), e);
-
-        ok = save_file(torrent_file, torrent);
+    std::string error = save_resume_data(p->params);
+    resume_data_done(best_hex(p->params.info_hashes), error);
+    return false;
+  }
 
-        if (!ok) {
-          std::fprintf(stderr, "failed to save torrent file 1: %s\n",
-                       torrent_file.c_str());
-          return false;
-        }
-      } catch (lt::system_error const& e) {
-        std::fprintf(stderr, "failed to save torrent file 2: %s\n", torrent_file.c_str());
-        return false;
-      }
+  if (save_resume_data_failed_alert* p = alert_cast<save_resume_data_failed_alert>(a)) {
+    // the answer to a conditional request, e.g. if_download_progress, which
+    // isn't what save_all_resume_data() waits for
+    if (p->error == lt::errors::resume_data_not_modified) {
+      return true;
     }
-
+    resume_data_done(best_hex(p->handle.info_hashes()), p->error.message());
     return false;
   }
 
-  // TODO: handle the error
-  // if (save_resume_data_failed_alert* p =
-  // alert_cast<save_resume_data_failed_alert>(a))
-  // {
-  // 	--num_outstanding_resume_data;
-  // 	// don't print the error if it was just that we didn't need to save
-  // resume
-  // 	// data. Returning true means "handled" and not printed to the log
-  // 	return p->error == lt::errors::resume_data_not_modified;
-  // }
-
   if (torrent_paused_alert* p = alert_cast<torrent_paused_alert>(a)) {
     // write resume data for the finished torrent
     // the alert handler for save_resume_data_alert
@@ -909,349 +1697,495 @@ bool Session::handle_alert(lt::alert* a) {
 }
 
 void Session::pop_alerts() {
-  // add lock
-  std::lock_guard<std::mutex> lock(m_pop_alerts_mutex);
+  // the alerts for the AlertSink and the log events, which are passed on
+  // after the lock is released so neither a slow subscriber nor a full log
+  // with the block policy stalls the torrent handle queries
+  std::vector<Alert> forward;
+  std::vector<LogEvent> logs;
+  {
+    std::lock_guard<std::mutex> lock(m_pop_alerts_mutex);
 
-  std::vector<lt::alert*> alerts;
-  lt_session->pop_alerts(&alerts);
-  for (auto a : alerts) {
-    if (handle_alert(a)) {
-      continue;
+    std::vector<lt::alert*> alerts;
+    lt_session->pop_alerts(&alerts);
+    for (auto a : alerts) {
+      // handle the alert first, so the state caches are up to date when the
+      // sink is notified
+      bool handled = handle_alert(a);
+
+      Alert alert;
+      if (cast_alert(a, alert)) {
+        forward.push_back(std::move(alert));
+      }
+
+      if (handled) {
+        continue;
+      }
+
+      // if we didn't handle the alert, print it to the log
+      logs.push_back(make_log_event(a));
     }
+  }
 
-    // if we didn't handle the alert, print it to the log
-    std::pair<lt::time_point, std::string> event =
-        std::make_pair(a->timestamp(), a->message());
-    m_events.push_back(event);
-    if (m_events.size() >= m_log_size)
-      m_events.pop_front();
+  for (auto& alert : forward) {
+    m_alert_sink->on_alert(std::move(alert));
   }
+  for (auto& e : logs) {
+    push_log(std::move(e), true);
+  }
+}
+
+void Session::log(LogLevel level, std::string message,
+                  lt::info_hash_t const& info_hashes) const {
+  LogEvent e;
+  e.timestamp = lt::clock_type::now();
+  e.message = std::move(message);
+  e.level = static_cast<std::uint8_t>(level);
+  e.target = static_cast<std::uint8_t>(LogTarget::Wrapper);
+  e.info_hashes = info_hashes;
+  push_log(std::move(e), false);
+}
+
+void Session::push_log(LogEvent e, bool may_block) const {
+  if (m_alert_sink->logs_enabled()) {
+    m_alert_sink->on_log(cast_log(e));
+  }
+  m_logs.push(std::move(e), may_block);
 }
 
 void Session::poll_alerts() {
   auto ses = lt_session;
-  while (true) {
-    if (!ses->is_valid() || !m_running) {
-      printf("Session::poll_alerts exit\n");
-      break;
+  while (ses->is_valid() && m_running) {
+    lt::time_point const now = lt::clock_type::now();
+    // m_running is checked at least every alert poll interval
+    lt::time_point next = now + lt::milliseconds(m_alert_poll_interval_ms);
+
+    for (std::size_t i = 0; i < num_stats_posts; ++i) {
+      std::uint32_t const interval_ms = m_post_interval_ms[i];
+      if (interval_ms == 0) {
+        continue;
+      }
+
+      if (now - m_last_post[i] >= lt::milliseconds(interval_ms)) {
+        switch (static_cast<StatsPost>(i)) {
+        case StatsPost::SessionStats:
+          ses->post_session_stats();
+          break;
+        case StatsPost::TorrentUpdates:
+          ses->post_torrent_updates();
+          break;
+        default:
+          ses->post_dht_stats();
+          break;
+        }
+        m_last_post[i] = now;
+      }
+      next = std::min(next, m_last_post[i] + lt::milliseconds(interval_ms));
     }
 
-    ses->post_session_stats();
-    ses->post_torrent_updates();
-    ses->post_dht_stats();
+    if (m_autosave_interval_ms > 0) {
+      if (now - m_last_autosave >= lt::milliseconds(m_autosave_interval_ms)) {
+        autosave();
+        m_last_autosave = lt::clock_type::now();
+      }
+      next = std::min(next, m_last_autosave + lt::milliseconds(m_autosave_interval_ms));
+    }
 
-    pop_alerts();
+    // wakes up as soon as there is an alert, e.g. the answer to a post
+    if (ses->wait_for_alert(std::max(next - lt::clock_type::now(), lt::time_duration(0))) !=
+        nullptr) {
+      pop_alerts();
+    }
+  }
+}
 
-    std::this_thread::sleep_for(std::chrono::milliseconds(500));
+void Session::set_post_interval(StatsPost post, std::uint32_t interval_ms) const {
+  std::size_t const i = static_cast<std::size_t>(post);
+  if (i < num_stats_posts) {
+    m_post_interval_ms[i] = interval_ms;
   }
 }
 
 lt::torrent_handle Session::find_torrent_handle(rust::Str info_hash_str) const {
-  lt::sha1_hash info_hash = from_hex(rust_str_to_string(info_hash_str));
+  std::string hex = rust_str_to_string(info_hash_str);
   auto ses = lt_session;
-  return ses->find_torrent(info_hash);
+
+  try {
+    if (hex.length() == 64) {
+      // torrents are indexed by their v1 info-hash and by their truncated v2
+      // info-hash
+      lt::sha256_hash v2 = sha256_from_hex(hex);
+      return ses->find_torrent(lt::sha1_hash(v2.data()));
+    }
+    return ses->find_torrent(from_hex(hex));
+  } catch (std::invalid_argument const&) {
+    throw_invalid_info_hash(hex);
+  }
 }
 
-rust::Vec<Log> Session::get_logs() {
-  rust::Vec<Log> ret;
-  ret.reserve(m_events.size());
-  while (!m_events.empty()) {
-    auto e = m_events.front();
-    m_events.pop_front();
-    Log l;
-    l.timestamp = e.first.time_since_epoch().count() / 1000000; // milliseconds
-    l.message = e.second;
-    ret.push_back(l);
+rust::Vec<ResumeLoadFailure> Session::resume_load_failures() const {
+  rust::Vec<ResumeLoadFailure> ret;
+  for (auto const& f : m_resume_load_failures) {
+    ret.push_back(f);
   }
   return ret;
 }
 
-TorrentHandle::TorrentHandle(lt::torrent_handle h, Session* ses)
-    : m_torrent_handle(h), m_session(ses) {}
-TorrentHandle::~TorrentHandle() {}
+std::map<std::string, lt::add_torrent_params>::iterator
+Session::find_orphan(std::string const& hex) const {
+  for (auto it = m_orphans.begin(); it != m_orphans.end(); ++it) {
+    if (it->first == hex || match_info_hash(it->second.info_hashes, hex)) {
+      return it;
+    }
+  }
+  return m_orphans.end();
+}
 
-void TorrentHandle::add_tracker(rust::Str tracker_url, std::uint8_t tier) const {
-  lt::torrent_handle h = m_torrent_handle;
+rust::Vec<OrphanedTorrent> Session::orphaned_torrents() const {
+  std::lock_guard<std::mutex> lock(m_orphans_mutex);
 
-  if (!h.is_valid()) {
-    return;
+  rust::Vec<OrphanedTorrent> ret;
+  for (auto const& [info_hash, atp] : m_orphans) {
+    OrphanedTorrent o;
+    o.info_hash = info_hash;
+    o.info_hashes = cast_info_hashes(atp.ti ? atp.ti->info_hashes() : atp.info_hashes);
+    o.name = atp.ti ? atp.ti->name() : atp.name;
+    o.save_path = atp.save_path;
+    ret.push_back(std::move(o));
   }
-
-  lt::announce_entry ae(tracker_url.data());
-  ae.tier = tier;
-  h.add_tracker(ae);
+  return ret;
 }
 
-void TorrentHandle::scrape_tracker() const {
-  lt::torrent_handle h = m_torrent_handle;
+rust::Vec<AddTorrentResult> Session::retry_orphaned_torrents() const {
+  std::lock_guard<std::mutex> lock(m_orphans_mutex);
 
-  if (!h.is_valid()) {
-    return;
-  }
+  rust::Vec<AddTorrentResult> ret;
+  for (auto it = m_orphans.begin(); it != m_orphans.end();) {
+    // the save path isn't created, it would hide a drive mounted later
+    lt::error_code ec;
+    if (!lt::exists(it->second.save_path, ec) || ec) {
+      ++it;
+      continue;
+    }
 
-  h.scrape_tracker();
+    AddTorrentResult r = make_add_torrent_result(it->second);
+    lt::torrent_handle h = lt_session->add_torrent(it->second, ec);
+    if (ec == lt::errors::duplicate_torrent) {
+      // the application added it again in the meantime
+      ec.clear();
+      h = lt_session->find_torrent(it->second.info_hashes.get_best());
+    }
+    if (ec) {
+      // it stays orphaned, the caller gets the error
+      r.error_kind = classify_add_torrent_error(ec);
+      r.error = cast_error_code(ec);
+      ret.push_back(std::move(r));
+      ++it;
+      continue;
+    }
+
+    r.handle = std::make_unique<TorrentHandle>(h, const_cast<Session*>(this));
+    ret.push_back(std::move(r));
+    it = m_orphans.erase(it);
+  }
+  return ret;
 }
 
-void TorrentHandle::force_recheck() const {
-  lt::torrent_handle h = m_torrent_handle;
+AddTorrentResult Session::relocate_orphaned_torrent(rust::Str info_hash_str,
+                                                    rust::Str save_path) const {
+  std::lock_guard<std::mutex> lock(m_orphans_mutex);
 
-  if (!h.is_valid()) {
-    return;
+  auto it = find_orphan(rust_str_to_string(info_hash_str));
+  if (it == m_orphans.end()) {
+    AddTorrentResult ret = make_add_torrent_error(AddTorrentError::NotOrphaned, lt::error_code());
+    ret.info_hash = rust_str_to_string(info_hash_str);
+    return ret;
   }
 
-  h.force_recheck();
-}
+  lt::add_torrent_params atp = it->second;
+  atp.save_path = lt::canonicalize_path(rust_str_to_string(save_path));
 
-void TorrentHandle::force_reannounce() const {
-  lt::torrent_handle h = m_torrent_handle;
+  AddTorrentResult ret = make_add_torrent_result(atp);
 
-  if (!h.is_valid()) {
-    return;
+  lt::error_code ec = check_save_path(atp.save_path);
+  if (ec) {
+    ret.error_kind = AddTorrentError::InvalidSavePath;
+    ret.error = cast_error_code(ec);
+    return ret;
+  }
+
+  lt::torrent_handle h = lt_session->add_torrent(std::move(atp), ec);
+  if (ec) {
+    ret.error_kind = classify_add_torrent_error(ec);
+    ret.error = cast_error_code(ec);
+    return ret;
   }
 
-  h.force_reannounce();
+  m_orphans.erase(it);
+  ret.handle = std::make_unique<TorrentHandle>(h, const_cast<Session*>(this));
+  return ret;
 }
 
-void TorrentHandle::force_dht_announce() const {
-  lt::torrent_handle h = m_torrent_handle;
+bool Session::forget_orphaned_torrent(rust::Str info_hash_str) const {
+  std::lock_guard<std::mutex> lock(m_orphans_mutex);
 
-  if (!h.is_valid()) {
-    return;
+  auto it = find_orphan(rust_str_to_string(info_hash_str));
+  if (it == m_orphans.end()) {
+    return false;
   }
 
-  h.force_dht_announce();
+  // throws if it can't be removed from the store, it's then still orphaned
+  m_resume_store->remove(it->first);
+  m_orphans.erase(it);
+  return true;
 }
 
-void TorrentHandle::force_lsd_announce() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
+rust::Vec<Log> Session::get_logs() const {
+  std::vector<LogEvent> events = m_logs.drain();
+  rust::Vec<Log> ret;
+  ret.reserve(events.size());
+  for (auto const& e : events) {
+    ret.push_back(cast_log(e));
   }
+  return ret;
+}
 
-  h.force_lsd_announce();
+rust::Vec<Log> Session::get_logs_filtered(LogFilter const& filter) const {
+  std::string const info_hash(filter.info_hash);
+  std::vector<LogEvent> events =
+      m_logs.drain([&](LogEvent const& e) { return match_log_filter(filter, info_hash, e); });
+  rust::Vec<Log> ret;
+  ret.reserve(events.size());
+  for (auto const& e : events) {
+    ret.push_back(cast_log(e));
+  }
+  return ret;
 }
 
-void TorrentHandle::clear_error() const {
-  lt::torrent_handle h = m_torrent_handle;
+std::uint64_t Session::dropped_logs() const { return m_logs.dropped(); }
 
+TorrentHandle::TorrentHandle(lt::torrent_handle h, Session* ses)
+    : m_torrent_handle(h), m_session(ses) {}
+TorrentHandle::~TorrentHandle() {}
+
+namespace {
+
+// calls `f` with the handle, throws ``invalid_handle`` if the torrent was
+// removed and the error code of the lt::system_error thrown by libtorrent
+template <typename F>
+auto checked_call(lt::torrent_handle h, F f) -> decltype(f(h)) {
   if (!h.is_valid()) {
-    return;
+    throw_invalid_handle();
   }
 
-  h.clear_error();
+  try {
+    return f(h);
+  } catch (lt::system_error const& e) {
+    throw_error_code(e.code());
+  }
 }
 
-void TorrentHandle::set_upload_limit(int limit) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
+// the metadata of the torrent, throws ``no_metadata`` for a magnet link
+// whose metadata wasn't received yet
+std::shared_ptr<const lt::torrent_info> checked_torrent_file(lt::torrent_handle const& h) {
+  std::shared_ptr<const lt::torrent_info> tf = h.torrent_file();
+  if (!tf) {
+    throw_no_metadata();
   }
+  return tf;
+}
 
-  h.set_upload_limit(limit);
+void check_file_index(lt::torrent_info const& ti, std::int32_t index) {
+  if (index < 0 || index >= ti.num_files()) {
+    throw_invalid_file_index(index);
+  }
 }
 
-int TorrentHandle::upload_limit() const {
-  lt::torrent_handle h = m_torrent_handle;
+} // namespace
 
-  return h.upload_limit();
+void TorrentHandle::add_tracker(rust::Str tracker_url, std::uint8_t tier) const {
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    lt::announce_entry ae(rust_str_to_string(tracker_url));
+    ae.tier = tier;
+    h.add_tracker(ae);
+  });
 }
 
-void TorrentHandle::set_download_limit(int limit) const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::scrape_tracker() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.scrape_tracker(); });
+}
 
-  if (!h.is_valid()) {
-    return;
-  }
+void TorrentHandle::force_recheck() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.force_recheck(); });
+}
 
-  h.set_download_limit(limit);
+void TorrentHandle::force_reannounce() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.force_reannounce(); });
 }
 
-int TorrentHandle::download_limit() const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::force_dht_announce() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.force_dht_announce(); });
+}
 
-  return h.download_limit();
+void TorrentHandle::force_lsd_announce() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.force_lsd_announce(); });
 }
 
-void TorrentHandle::clear_peers() const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::clear_error() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.clear_error(); });
+}
 
-  if (!h.is_valid()) {
-    return;
-  }
+void TorrentHandle::set_upload_limit(int limit) const {
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) { h.set_upload_limit(limit); });
+}
 
-  h.clear_peers();
+int TorrentHandle::upload_limit() const {
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) { return h.upload_limit(); });
 }
 
-void TorrentHandle::set_max_uploads(int max_uploads) const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::set_download_limit(int limit) const {
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) { h.set_download_limit(limit); });
+}
 
-  if (!h.is_valid()) {
-    return;
-  }
+int TorrentHandle::download_limit() const {
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) { return h.download_limit(); });
+}
 
-  h.set_max_uploads(max_uploads);
+void TorrentHandle::clear_peers() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.clear_peers(); });
 }
 
-int TorrentHandle::max_uploads() const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::set_max_uploads(int max_uploads) const {
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) { h.set_max_uploads(max_uploads); });
+}
 
-  return h.max_uploads();
+int TorrentHandle::max_uploads() const {
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) { return h.max_uploads(); });
 }
 
 void TorrentHandle::set_max_connections(int max_connections) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.set_max_connections(max_connections);
+  checked_call(m_torrent_handle,
+               [&](lt::torrent_handle& h) { h.set_max_connections(max_connections); });
 }
 
 int TorrentHandle::max_connections() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  return h.max_connections();
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) { return h.max_connections(); });
 }
 
 void TorrentHandle::pause(uint8_t flags) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.pause(lt::pause_flags_t(flags));
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) { h.pause(lt::pause_flags_t(flags)); });
 }
 
 void TorrentHandle::resume() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.resume();
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.resume(); });
 }
 
 std::uint64_t TorrentHandle::flags() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return 0;
-  }
-
-  return static_cast<std::uint64_t>(h.flags());
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    return static_cast<std::uint64_t>(h.flags());
+  });
 }
 
 void TorrentHandle::set_flags(std::uint64_t flags) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.set_flags(lt::torrent_flags_t(flags));
+  checked_call(m_torrent_handle,
+               [&](lt::torrent_handle& h) { h.set_flags(lt::torrent_flags_t(flags)); });
 }
 
 void TorrentHandle::set_flags_with_mask(std::uint64_t flags, std::uint64_t mask) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.set_flags(lt::torrent_flags_t(flags), lt::torrent_flags_t(mask));
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    h.set_flags(lt::torrent_flags_t(flags), lt::torrent_flags_t(mask));
+  });
 }
 
 void TorrentHandle::unset_flags(std::uint64_t flags) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.unset_flags(lt::torrent_flags_t(flags));
+  checked_call(m_torrent_handle,
+               [&](lt::torrent_handle& h) { h.unset_flags(lt::torrent_flags_t(flags)); });
 }
 
+// libtorrent ignores the file priorities of a torrent without metadata and
+// out of range file indices, they are errors here
 void TorrentHandle::set_file_priority(std::int32_t index, std::uint8_t priority) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
-
-  h.file_priority(static_cast<lt::file_index_t>(index),
-                  static_cast<lt::download_priority_t>(priority));
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    check_file_index(*checked_torrent_file(h), index);
+    h.file_priority(static_cast<lt::file_index_t>(index),
+                    static_cast<lt::download_priority_t>(priority));
+  });
 }
 
 std::uint8_t TorrentHandle::get_file_priority(std::int32_t index) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return 0;
-  }
-
-  return static_cast<std::uint8_t>(h.file_priority(static_cast<lt::file_index_t>(index)));
+  return checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    check_file_index(*checked_torrent_file(h), index);
+    return static_cast<std::uint8_t>(h.file_priority(static_cast<lt::file_index_t>(index)));
+  });
 }
 
 void TorrentHandle::set_prioritize_files(
     rust::Slice<const std::uint8_t> const files) const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return;
-  }
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    std::shared_ptr<const lt::torrent_info> tf = checked_torrent_file(h);
+    if (files.size() > static_cast<std::size_t>(tf->num_files())) {
+      throw_invalid_file_index(tf->num_files());
+    }
 
-  std::vector<lt::download_priority_t> priorities;
-  for (auto p : files) {
-    priorities.push_back(static_cast<lt::download_priority_t>(p));
-  }
+    std::vector<lt::download_priority_t> priorities;
+    for (auto p : files) {
+      priorities.push_back(static_cast<lt::download_priority_t>(p));
+    }
 
-  h.prioritize_files(priorities);
+    h.prioritize_files(priorities);
+  });
 }
 
 rust::Vec<std::uint8_t> TorrentHandle::get_file_priorities() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return rust::Vec<std::uint8_t>();
-  }
-
-  rust::Vec<std::uint8_t> ret;
-  std::vector<lt::download_priority_t> priorities = h.get_file_priorities();
-  for (auto p : priorities) {
-    ret.push_back(static_cast<std::uint8_t>(p));
-  }
-
-  return ret;
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    rust::Vec<std::uint8_t> ret;
+    std::vector<lt::download_priority_t> priorities = h.get_file_priorities();
+    for (auto p : priorities) {
+      ret.push_back(static_cast<std::uint8_t>(p));
+    }
+    return ret;
+  });
 }
 
 TorrentInfo TorrentHandle::get_torrent_info() const {
-  lt::torrent_handle h = m_torrent_handle;
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    return cast_torrent_info(*checked_torrent_file(h));
+  });
+}
 
-  if (!h.is_valid()) {
-    return TorrentInfo();
-  }
+rust::Vec<std::uint8_t> TorrentHandle::get_torrent_file() const {
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    // the piece layers are needed to write v2 torrents
+    std::shared_ptr<const lt::torrent_info> tf = h.torrent_file_with_hashes();
+    if (!tf) {
+      throw_no_metadata();
+    }
 
-  std::shared_ptr<const lt::torrent_info> tf = h.torrent_file();
-  return cast_torrent_info(*tf);
-}
+    lt::add_torrent_params atp;
+    atp.ti = std::const_pointer_cast<lt::torrent_info>(tf);
+    std::vector<char> buf = lt::write_torrent_file_buf(atp, {});
 
-rust::Vec<PeerInfo> TorrentHandle::get_peers() const {
-  lt::torrent_handle h = m_torrent_handle;
+    rust::Vec<std::uint8_t> ret;
+    ret.reserve(buf.size());
+    for (auto c : buf) {
+      ret.push_back(static_cast<std::uint8_t>(c));
+    }
+    return ret;
+  });
+}
 
-  if (!h.is_valid()) {
-    return rust::Vec<PeerInfo>();
-  }
+void TorrentHandle::post_peer_info() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.post_peer_info(); });
+}
 
-  h.post_peer_info();
-  m_session->pop_alerts();
+rust::Vec<PeerInfo> TorrentHandle::peers() const {
+  lt::torrent_handle h = m_torrent_handle;
 
   rust::Vec<PeerInfo> ret;
-  std::vector<lt::peer_info> peers = m_session->m_peer_state.get_peers(h);
+  std::vector<lt::peer_info> peers;
+  {
+    std::lock_guard<std::mutex> lock(m_session->m_pop_alerts_mutex);
+    peers = m_session->m_peer_state.get_peers(h);
+  }
   for (auto& peer : peers) {
     PeerInfo pi;
     pi.client = peer.client;
@@ -1319,22 +2253,24 @@ rust::Vec<PeerInfo> TorrentHandle::get_peers() const {
   return ret;
 }
 
-rust::Vec<std::int64_t> TorrentHandle::get_file_progress(bool piece_granularity) const {
-  lt::torrent_handle h = m_torrent_handle;
+void TorrentHandle::post_file_progress(bool piece_granularity) const {
+  checked_call(m_torrent_handle, [&](lt::torrent_handle& h) {
+    if (piece_granularity) {
+      h.post_file_progress(lt::torrent_handle::piece_granularity);
+    } else {
+      h.post_file_progress({});
+    }
+  });
+}
 
-  if (!h.is_valid()) {
-    return rust::Vec<std::int64_t>();
-  }
+rust::Vec<std::int64_t> TorrentHandle::file_progress() const {
+  lt::torrent_handle h = m_torrent_handle;
 
-  if (piece_granularity) {
-    h.post_file_progress(lt::torrent_handle::piece_granularity);
-  } else {
-    h.post_file_progress({});
+  std::vector<std::int64_t> progress;
+  {
+    std::lock_guard<std::mutex> lock(m_session->m_pop_alerts_mutex);
+    progress = m_session->m_file_progress_state.get_file_progress(h);
   }
-  m_session->pop_alerts();
-
-  std::vector<std::int64_t> progress =
-      m_session->m_file_progress_state.get_file_progress(h);
   rust::Vec<std::int64_t> ret;
   ret.reserve(progress.size());
   for (auto& p : progress) {
@@ -1343,18 +2279,19 @@ rust::Vec<std::int64_t> TorrentHandle::get_file_progress(bool piece_granularity)
   return ret;
 }
 
-PieceInfo TorrentHandle::get_piece_info() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return PieceInfo();
-  }
+void TorrentHandle::post_download_queue() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.post_download_queue(); });
+}
 
-  h.post_download_queue();
-  m_session->pop_alerts();
+PieceInfo TorrentHandle::piece_info() const {
+  lt::torrent_handle h = m_torrent_handle;
 
   PieceInfo ret;
-  auto pair = m_session->m_piece_info_state.get_piece_info(h);
+  std::pair<std::vector<lt::partial_piece_info>, std::vector<lt::block_info>> pair;
+  {
+    std::lock_guard<std::mutex> lock(m_session->m_pop_alerts_mutex);
+    pair = m_session->m_piece_info_state.get_piece_info(h);
+  }
 
   for (auto& p : pair.first) {
     PartialPieceInfo ppi;
@@ -1392,18 +2329,18 @@ PieceInfo TorrentHandle::get_piece_info() const {
   return ret;
 }
 
-rust::Vec<std::int32_t> TorrentHandle::get_piece_availability() const {
+void TorrentHandle::post_piece_availability() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.post_piece_availability(); });
+}
+
+rust::Vec<std::int32_t> TorrentHandle::piece_availability() const {
   lt::torrent_handle h = m_torrent_handle;
 
-  if (!h.is_valid()) {
-    return rust::Vec<std::int32_t>();
+  std::vector<std::int32_t> availability;
+  {
+    std::lock_guard<std::mutex> lock(m_session->m_pop_alerts_mutex);
+    availability = m_session->m_piece_availability_state.get_piece_availability(h);
   }
-
-  h.post_piece_availability();
-  m_session->pop_alerts();
-
-  std::vector<std::int32_t> availability =
-      m_session->m_piece_availability_state.get_piece_availability(h);
   rust::Vec<std::int32_t> ret;
   ret.reserve(availability.size());
   for (auto& p : availability) {
@@ -1412,17 +2349,18 @@ rust::Vec<std::int32_t> TorrentHandle::get_piece_availability() const {
   return ret;
 }
 
-rust::Vec<AnnounceEntry> TorrentHandle::get_trackers() const {
+void TorrentHandle::post_trackers() const {
+  checked_call(m_torrent_handle, [](lt::torrent_handle& h) { h.post_trackers(); });
+}
+
+rust::Vec<AnnounceEntry> TorrentHandle::trackers() const {
   lt::torrent_handle h = m_torrent_handle;
 
-  if (!h.is_valid()) {
-    return rust::Vec<AnnounceEntry>();
+  std::vector<lt::announce_entry> trackers;
+  {
+    std::lock_guard<std::mutex> lock(m_session->m_pop_alerts_mutex);
+    trackers = m_session->m_tracker_state.get_trackers(h);
   }
-
-  h.post_trackers();
-  m_session->pop_alerts();
-
-  std::vector<lt::announce_entry> trackers = m_session->m_tracker_state.get_trackers(h);
   rust::Vec<AnnounceEntry> ret;
   ret.reserve(trackers.size());
   for (auto& p : trackers) {
@@ -1476,21 +2414,26 @@ rust::Vec<AnnounceEntry> TorrentHandle::get_trackers() const {
 }
 
 TorrentStatus TorrentHandle::get_torrent_status() const {
-  lt::torrent_handle h = m_torrent_handle;
+  return checked_call(m_torrent_handle, [this](lt::torrent_handle& h) {
+    return cast_torrent_status(m_session->m_torrent_state.get_torrent_status(h));
+  });
+}
 
-  lt::torrent_status ts = m_session->m_torrent_state.get_torrent_status(h);
+rust::String TorrentHandle::info_hash() const {
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    return rust::String(best_hex(h.info_hashes()));
+  });
+}
 
-  return cast_torrent_status(ts);
+InfoHashes TorrentHandle::info_hashes() const {
+  return checked_call(m_torrent_handle,
+                      [](lt::torrent_handle& h) { return cast_info_hashes(h.info_hashes()); });
 }
 
 rust::String TorrentHandle::make_magnet_uri() const {
-  lt::torrent_handle h = m_torrent_handle;
-
-  if (!h.is_valid()) {
-    return rust::String();
-  }
-
-  return lt::make_magnet_uri(h);
+  return checked_call(m_torrent_handle, [](lt::torrent_handle& h) {
+    return rust::String(lt::make_magnet_uri(h));
+  });
 }
 
 } // namespace libtorrent_wrapper
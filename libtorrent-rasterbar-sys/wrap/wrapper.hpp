@@ -7,14 +7,32 @@
 #include "rust/cxx.h"
 #include "states.hpp"
 #include "time.hpp"
+#include "utils.hpp"
 
+#include <atomic>
+#include <condition_variable>
 #include <deque>
+#include <map>
 #include <memory>
+#include <mutex>
+#include <set>
 
 namespace libtorrent_wrapper {
 
 // shared types
-struct ParamPair;
+struct HostPort;
+struct Setting;
+struct SettingError;
+enum class SettingType : std::uint8_t;
+struct InfoHashes;
+struct RenamedFile;
+struct AddTorrentParams;
+struct AddTorrentResult;
+enum class AddTorrentError : std::uint8_t;
+struct ParseTorrentInfoResult;
+enum class TorrentInfoError : std::uint8_t;
+struct CreateTorrentParams;
+enum class TorrentVersion : std::uint8_t;
 struct DHTNode;
 struct FileEntry;
 struct TorrentInfo;
@@ -27,7 +45,26 @@ struct AnnounceInfoHash;
 struct AnnounceEndpoint;
 struct AnnounceEntry;
 struct Log;
+struct LogFilter;
+enum class LogLevel : std::uint8_t;
 struct TwoSessionStats;
+struct ErrorCode;
+struct OperationError;
+struct Alert;
+struct ResumeEntry;
+struct ResumeLoadFailure;
+struct OrphanedTorrent;
+struct SaveResumeSummary;
+struct ShutdownReport;
+enum class ResumeRecovery : std::uint8_t;
+enum class LogOverflowPolicy : std::uint8_t;
+struct SessionOptions;
+enum class StatsPost : std::uint8_t;
+
+// rust types
+struct AlertSink;
+struct CreateTorrentProgress;
+struct ResumeStoreBox;
 
 class TorrentHandle;
 
@@ -35,17 +72,25 @@ class Session {
   friend class TorrentHandle;
 
 public:
-  Session(lt::session_params params, std::uint32_t save_state_flags,
-          std::string session_state_path, std::string resume_dir, std::string torrent_dir,
-          std::uint32_t log_size);
+  Session(lt::session_params params, SessionOptions const& options,
+          rust::Box<ResumeStoreBox> resume_store, rust::Box<AlertSink> alert_sink);
   ~Session();
 
-  void add_torrent(rust::Str torrent_path,
-                   rust::Slice<const ParamPair> torrent_param_list) const;
+  AddTorrentResult add_torrent(rust::Str torrent_path, AddTorrentParams const& params) const;
 
-  void add_magnet(rust::Str magnet_uri,
-                  rust::Slice<const ParamPair> torrent_param_list) const;
+  AddTorrentResult add_magnet(rust::Str magnet_uri, AddTorrentParams const& params) const;
 
+  AddTorrentResult async_add_torrent(rust::Str torrent_path, AddTorrentParams const& params) const;
+
+  AddTorrentResult async_add_magnet(rust::Str magnet_uri, AddTorrentParams const& params) const;
+
+  AddTorrentResult add_torrent_bytes(rust::Slice<const std::uint8_t> data,
+                                     AddTorrentParams const& params) const;
+
+  AddTorrentResult async_add_torrent_bytes(rust::Slice<const std::uint8_t> data,
+                                           AddTorrentParams const& params) const;
+
+  // throws an operation_error if there is no such torrent, see utils.hpp
   std::unique_ptr<TorrentHandle> get_torrent_handle(rust::Str info_hash_str) const;
 
   void remove_torrent(rust::Str info_hash_str, bool delete_files) const;
@@ -56,35 +101,120 @@ public:
   void resume() const;
   bool is_paused() const;
 
+  SettingError apply_settings(rust::Slice<const Setting> settings) const;
+  rust::Vec<Setting> get_settings() const;
+
   rust::Vec<TorrentInfo> get_torrents() const;
 
   rust::Vec<TorrentStatus> get_all_torrent_status() const;
 
   void poll_alerts();
 
-  rust::Vec<Log> get_logs();
+  // drains the log messages, may be called from any thread
+  rust::Vec<Log> get_logs() const;
+
+  // drains the log messages matching `filter`, the others are kept
+  rust::Vec<Log> get_logs_filtered(LogFilter const& filter) const;
+
+  // the number of log messages dropped because the log was full
+  std::uint64_t dropped_logs() const;
+
+  // how often the alert thread posts the session stats, torrent updates or
+  // dht stats, 0 disables it
+  void set_post_interval(StatsPost post, std::uint32_t interval_ms) const;
+
+  rust::Vec<ResumeLoadFailure> resume_load_failures() const;
+
+  // requests the resume data of every torrent and waits up to `timeout_ms`
+  // for it to be saved. Must not be called from the alert thread.
+  SaveResumeSummary save_all_resume_data(std::uint32_t timeout_ms) const;
+
+  // saves the session state to the session state path or the resume store
+  void save_state() const;
+
+  // pauses the session, waits up to `timeout_ms` for the resume data of
+  // every torrent, stops the alert thread and saves the session state. The
+  // session only can be destroyed afterwards.
+  ShutdownReport shutdown(std::uint32_t timeout_ms) const;
+
+  rust::Vec<OrphanedTorrent> orphaned_torrents() const;
+  rust::Vec<AddTorrentResult> retry_orphaned_torrents() const;
+  AddTorrentResult relocate_orphaned_torrent(rust::Str info_hash_str, rust::Str save_path) const;
+  bool forget_orphaned_torrent(rust::Str info_hash_str) const;
 
 private:
-  void add_torrent_from_parmas(lt::add_torrent_params atp,
-                               rust::Slice<const ParamPair> torrent_param_list) const;
+  AddTorrentResult add_torrent_from_params(lt::add_torrent_params atp, AddTorrentParams const& params,
+                                           bool async) const;
+  lt::error_code load_resume_data(lt::add_torrent_params& atp, AddTorrentParams const& params) const;
+
+  // adds the torrents of the resume store, throws if they can't be loaded
+  void load_all_resume_data();
+
+  // the orphaned torrent with the v1 or v2 info-hash `hex`, m_orphans_mutex
+  // must be held
+  std::map<std::string, lt::add_torrent_params>::iterator find_orphan(std::string const& hex) const;
+
+  // passes the resume data to the resume store, returns the error or an
+  // empty string
+  std::string save_resume_data(lt::add_torrent_params const& params) const;
 
-  std::string get_resume_file_path(lt::sha1_hash info_hash) const;
-  std::string get_torrent_file_path(lt::sha1_hash info_hash) const;
+  // records the outcome of a save_resume_data() request for the callers of
+  // save_all_resume_data() waiting for it
+  void resume_data_done(std::string const& info_hash, std::string const& error) const;
 
-  void load_all_resume_data() const;
+  // requests the resume data of the torrents which need it saved and saves
+  // the session state
+  void autosave() const;
 
   void pop_alerts();
 
   bool handle_alert(lt::alert* a);
 
-  lt::torrent_handle find_torrent_handle(rust::Str info_hash_str) const;
+  // reports a diagnostic of the wrapper to the log and the AlertSink,
+  // nothing is printed. Called from any thread, possibly with locks held,
+  // so it never waits for room in the log
+  void log(LogLevel level, std::string message,
+           lt::info_hash_t const& info_hashes = lt::info_hash_t()) const;
+
+  // passes a log event to the AlertSink and the log. Only the alert thread
+  // outside of m_pop_alerts_mutex may block on a full log
+  void push_log(LogEvent e, bool may_block) const;
 
-  void save_all_resume() const;
+  // the handle of the torrent, invalid if there is no such torrent. Throws
+  // ``invalid_info_hash`` if the info-hash isn't hex
+  lt::torrent_handle find_torrent_handle(rust::Str info_hash_str) const;
 
   std::uint32_t m_save_state_flags;
   std::string m_session_state_path;
-  std::string m_resume_dir;
-  std::string m_torrent_dir;
+  mutable std::mutex m_save_state_mutex; // serializes save_state
+  rust::Box<ResumeStoreBox> m_resume_store;
+  std::string m_recovery_save_path;
+  std::vector<ResumeLoadFailure> m_resume_load_failures;
+
+  // the torrents of the resume store whose save path didn't exist when the
+  // session was created, by the hex of their best info-hash
+  mutable std::map<std::string, lt::add_torrent_params> m_orphans;
+  mutable std::mutex m_orphans_mutex; // protects m_orphans
+
+  // the outstanding resume data requests of a save_all_resume_data() call
+  struct ResumeBatch {
+    std::set<std::string> pending;
+    std::vector<std::string> saved;
+    std::vector<std::pair<std::string, std::string>> failed;
+  };
+  mutable std::vector<ResumeBatch*> m_resume_batches;
+  mutable std::mutex m_resume_mutex; // protects m_resume_batches
+  mutable std::condition_variable m_resume_cv;
+
+  std::uint32_t m_autosave_interval_ms; // 0 disables the autosave
+  lt::time_point m_last_autosave;
+  std::uint32_t m_alert_poll_interval_ms;
+
+  // the intervals of the session stats, torrent updates and dht stats posts,
+  // indexed by StatsPost, 0 disables a post
+  static constexpr std::size_t num_stats_posts = 3;
+  mutable std::atomic<std::uint32_t> m_post_interval_ms[num_stats_posts];
+  lt::time_point m_last_post[num_stats_posts];
 
   std::shared_ptr<lt::session> lt_session;
 
@@ -98,48 +228,57 @@ private:
   PieceAvailabilityState m_piece_availability_state;
   TrackerState m_tracker_state;
 
-  std::mutex m_pop_alerts_mutex; // protects pop_alerts
+  std::mutex m_pop_alerts_mutex; // protects pop_alerts and the states above
 
-  bool m_running;
+  mutable std::atomic<bool> m_running;
+  mutable std::atomic<bool> m_shut_down{false};
   std::shared_ptr<std::thread> m_thread;
 
-  std::uint32_t m_log_size;
-  std::deque<std::pair<lt::time_point, std::string>> m_events; // for log
+  mutable LogQueue m_logs;
+
+  rust::Box<AlertSink> m_alert_sink; // receives the typed alerts
 };
 
-// The default values of the session settings are set for a regular
-// bittorrent client running on a desktop system. There are functions that
-// can set the session settings to pre set settings for other environments.
-// These can be used for the basis, and should be tweaked to fit your needs
-// better.
+// Create a new session. ``settings`` are applied over the session state
+// loaded from ``session_state_path``, an empty path disables loading and
+// saving it. The torrents of ``resume_store`` are added back.
 //
-// ``min_memory_usage`` returns settings that will use the minimal amount of
-// RAM, at the potential expense of upload and download performance. It
-// adjusts the socket buffer sizes, disables the disk cache, lowers the send
-// buffer watermarks so that each connection only has at most one block in
-// use at any one time. It lowers the outstanding blocks send to the disk
-// I/O thread so that connections only have one block waiting to be flushed
-// to disk at any given time. It lowers the max number of peers in the peer
-// list for torrents. It performs multiple smaller reads when it hashes
-// pieces, instead of reading it all into memory before hashing.
-//
-// This configuration is intended to be the starting point for embedded
-// devices. It will significantly reduce memory usage.
-//
-// ``high_performance_seed`` returns settings optimized for a seed box,
-// serving many peers and that doesn't do any downloading. It has a 128 MB
-// disk cache and has a limit of 400 files in its file pool. It support fast
-// upload rates by allowing large send buffers.
-//
-// ``session_param_list`` is a list of key-value pairs that will be used to
-// override the default values.
-std::unique_ptr<Session> create_session(bool min_memory_usage, bool high_performance_seed,
-                                        rust::Slice<const ParamPair> session_param_list,
-                                        std::uint32_t save_state_flags,
-                                        rust::Str session_state_path,
-                                        rust::Str resume_dir, rust::Str torrent_dir,
-                                        std::uint32_t log_size);
+// ``alert_sink`` receives the typed alerts of the session.
+std::unique_ptr<Session> create_session(rust::Slice<const Setting> settings,
+                                        SessionOptions const& options,
+                                        rust::Box<ResumeStoreBox> resume_store,
+                                        rust::Box<AlertSink> alert_sink);
+
+// parses a setting from its string form
+Setting parse_setting(rust::Str name, rust::Str value);
 
+// every setting with its default value
+rust::Vec<Setting> default_settings();
+
+// the settings changed by the presets
+rust::Vec<Setting> min_memory_usage_settings();
+rust::Vec<Setting> high_performance_seed_settings();
+
+// parses the bencoded contents of a .torrent file
+ParseTorrentInfoResult parse_torrent_info(rust::Slice<const std::uint8_t> data);
+
+// creates a torrent from a file or directory and returns the bencoded
+// .torrent file
+rust::Vec<std::uint8_t> create_torrent(CreateTorrentParams const& params,
+                                       CreateTorrentProgress const& progress);
+
+// classifies the error of an add_torrent_alert
+AddTorrentError classify_add_torrent_error(ErrorCode const& error);
+
+// keeps the error of the exception passed to rust for take_operation_error(),
+// nullptr for any other exception
+void keep_operation_error(operation_error const* e);
+
+// takes the error kept on this thread
+OperationError take_operation_error();
+
+// the operations throw an operation_error once the torrent is removed and for
+// the errors reported by libtorrent, see utils.hpp
 class TorrentHandle {
 public:
   TorrentHandle(lt::torrent_handle lt_torrent_handle, Session* session);
@@ -327,17 +466,33 @@ public:
   void set_prioritize_files(rust::Slice<const std::uint8_t> const files) const;
   rust::Vec<std::uint8_t> get_file_priorities() const;
 
+  // the metadata of the torrent, without the .torrent file
   TorrentInfo get_torrent_info() const;
 
-  rust::Vec<PeerInfo> get_peers() const;
+  // the bencoded .torrent file, written on every call
+  rust::Vec<std::uint8_t> get_torrent_file() const;
 
-  rust::Vec<std::int64_t> get_file_progress(bool piece_granularity) const;
+  // the ``post_*()`` functions only request the information from libtorrent.
+  // The reply alert is cached by the session and forwarded to the AlertSink,
+  // after which it can be read with ``peers()``, ``file_progress()``,
+  // ``piece_info()``, ``piece_availability()`` and ``trackers()``.
+  void post_peer_info() const;
+  void post_file_progress(bool piece_granularity) const;
+  void post_download_queue() const;
+  void post_piece_availability() const;
+  void post_trackers() const;
 
-  PieceInfo get_piece_info() const;
+  rust::Vec<PeerInfo> peers() const;
+  rust::Vec<std::int64_t> file_progress() const;
+  PieceInfo piece_info() const;
+  rust::Vec<std::int32_t> piece_availability() const;
+  rust::Vec<AnnounceEntry> trackers() const;
 
-  rust::Vec<std::int32_t> get_piece_availability() const;
+  // the info-hash of the torrent as hex, see ``TorrentInfo::info_hash``
+  rust::String info_hash() const;
 
-  rust::Vec<AnnounceEntry> get_trackers() const;
+  // the v1 and v2 info-hashes of the torrent as hex
+  InfoHashes info_hashes() const;
 
   TorrentStatus get_torrent_status() const;
 
@@ -350,4 +505,23 @@ private:
 
 } // namespace libtorrent_wrapper
 
+namespace rust {
+namespace behavior {
+
+// passes the exceptions to rust like the default of cxx, and keeps the
+// error of an operation_error for take_operation_error()
+template <typename Try, typename Fail>
+static void trycatch(Try&& func, Fail&& fail) noexcept try {
+  func();
+} catch (libtorrent_wrapper::operation_error const& e) {
+  libtorrent_wrapper::keep_operation_error(&e);
+  fail(e.what());
+} catch (std::exception const& e) {
+  libtorrent_wrapper::keep_operation_error(nullptr);
+  fail(e.what());
+}
+
+} // namespace behavior
+} // namespace rust
+
 #endif
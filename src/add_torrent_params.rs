@@ -0,0 +1,341 @@
+use std::net::SocketAddr;
+
+use libtorrent_rasterbar_sys::{
+    ffi::{self, AddTorrentError, AddTorrentResult, HostPort, RenamedFile},
+    flags::TorrentFlags,
+};
+
+use crate::{DownloadPriority, InfoHashes, LTError, LTResult, LTTorrentHandle};
+
+/// libtorrent/storage_defs.hpp
+///
+/// types of storage allocation used for add_torrent_params::storage_mode.
+#[allow(non_camel_case_types)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    /// All pieces will be written to their final position, all files will be
+    /// allocated in full when the torrent is first started. This mode minimizes
+    /// fragmentation but could be a costly operation.
+    storage_mode_allocate = 0,
+
+    /// All pieces will be written to the place where they belong and sparse files
+    /// will be used. This is the recommended, and default mode.
+    storage_mode_sparse = 1,
+}
+
+/// libtorrent/add_torrent_params.hpp
+///
+/// the parameters used when adding a torrent with ``LTSession::add_torrent``
+/// or ``LTSession::add_magnet``. Create it with ``AddTorrentParams::builder``.
+///
+/// Everything that is not set keeps the value from the torrent file, magnet
+/// link or resume data. ``AddTorrentParams::default()`` overrides nothing.
+#[derive(Debug, Clone, Default)]
+pub struct AddTorrentParams {
+    inner: ffi::AddTorrentParams,
+}
+
+impl AddTorrentParams {
+    pub fn builder() -> AddTorrentParamsBuilder {
+        AddTorrentParamsBuilder::default()
+    }
+
+    pub(crate) fn as_ffi(&self) -> &ffi::AddTorrentParams {
+        &self.inner
+    }
+}
+
+/// a torrent added with ``LTSession::add_torrent`` or ``LTSession::add_magnet``
+pub struct AddedTorrent {
+    pub handle: LTTorrentHandle,
+
+    /// the v1 (SHA-1) and v2 (SHA-256) info-hashes. A torrent added by a
+    /// magnet link only has the info-hashes of the link.
+    pub info_hashes: InfoHashes,
+}
+
+impl AddedTorrent {
+    pub(crate) fn new(handle: LTTorrentHandle, r: AddTorrentResult) -> AddedTorrent {
+        AddedTorrent {
+            handle,
+            info_hashes: r.info_hashes.into(),
+        }
+    }
+}
+
+/// maps a failed add to its ``LTError``. Errors that are not classified are
+/// turned into ``other``, e.g. ``LTError::FailedToAddTorrent``.
+pub(crate) fn add_torrent_error(
+    kind: AddTorrentError,
+    message: String,
+    r: &AddTorrentResult,
+    other: fn(String) -> LTError,
+) -> LTError {
+    match kind {
+        AddTorrentError::Duplicate => LTError::DuplicateTorrent(r.info_hash.clone()),
+        AddTorrentError::InvalidMetadata => LTError::InvalidMetadata(message),
+        AddTorrentError::InvalidSavePath => LTError::InvalidSavePath(r.save_path.clone(), message),
+        AddTorrentError::NotOrphaned => LTError::NotOrphaned(r.info_hash.clone()),
+        _ => other(message),
+    }
+}
+
+/// builds an ``AddTorrentParams``, the values are validated by ``build``.
+#[derive(Debug, Clone, Default)]
+pub struct AddTorrentParamsBuilder {
+    save_path: Option<String>,
+    name: Option<String>,
+    trackers: Vec<(String, u8)>,
+    dht_nodes: Vec<(String, u16)>,
+    flags: Option<TorrentFlags>,
+    storage_mode: Option<StorageMode>,
+    max_uploads: Option<i32>,
+    max_connections: Option<i32>,
+    upload_limit: Option<i32>,
+    download_limit: Option<i32>,
+    file_priorities: Vec<u8>,
+    renamed_files: Vec<(i32, String)>,
+    web_seeds: Vec<String>,
+    peers: Vec<SocketAddr>,
+    resume_data: Option<Vec<u8>>,
+}
+
+impl AddTorrentParamsBuilder {
+    /// the path where the torrent is or will be stored.
+    pub fn save_path(mut self, save_path: impl Into<String>) -> Self {
+        self.save_path = Some(save_path.into());
+        self
+    }
+
+    /// the name of the torrent, used until the metadata is received.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// adds a tracker (http, https or udp) in the given tier. Trackers of
+    /// the torrent file or magnet link are kept.
+    pub fn tracker(mut self, url: impl Into<String>, tier: u8) -> Self {
+        self.trackers.push((url.into(), tier));
+        self
+    }
+
+    /// adds a dht node to bootstrap the DHT from.
+    pub fn dht_node(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.dht_nodes.push((host.into(), port));
+        self
+    }
+
+    /// flags controlling aspects of this torrent and how it's added, see
+    /// ``TorrentFlags``.
+    pub fn flags(mut self, flags: TorrentFlags) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
+    pub fn storage_mode(mut self, storage_mode: StorageMode) -> Self {
+        self.storage_mode = Some(storage_mode);
+        self
+    }
+
+    /// the maximum number of unchoked peers, -1 means unlimited.
+    pub fn max_uploads(mut self, max_uploads: i32) -> Self {
+        self.max_uploads = Some(max_uploads);
+        self
+    }
+
+    /// the maximum number of connections, at least 2. -1 means unlimited.
+    pub fn max_connections(mut self, max_connections: i32) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// the upload rate limit in bytes per second, -1 means unlimited.
+    pub fn upload_limit(mut self, upload_limit: i32) -> Self {
+        self.upload_limit = Some(upload_limit);
+        self
+    }
+
+    /// the download rate limit in bytes per second, -1 means unlimited.
+    pub fn download_limit(mut self, download_limit: i32) -> Self {
+        self.download_limit = Some(download_limit);
+        self
+    }
+
+    /// the initial priorities of the files, by file index. Files not covered
+    /// get the default priority, or ``dont_download`` if the
+    /// ``default_dont_download`` flag is set.
+    pub fn file_priorities(mut self, priorities: impl IntoIterator<Item = DownloadPriority>) -> Self {
+        self.file_priorities = priorities.into_iter().map(|p| p as u8).collect();
+        self
+    }
+
+    /// renames the file at ``file_index`` before the torrent is added.
+    pub fn rename_file(mut self, file_index: i32, path: impl Into<String>) -> Self {
+        self.renamed_files.push((file_index, path.into()));
+        self
+    }
+
+    /// adds a web seed (BEP 19), an http or https url.
+    pub fn web_seed(mut self, url: impl Into<String>) -> Self {
+        self.web_seeds.push(url.into());
+        self
+    }
+
+    /// adds a peer to connect to once the torrent is added.
+    pub fn peer(mut self, peer: SocketAddr) -> Self {
+        self.peers.push(peer);
+        self
+    }
+
+    /// bencoded resume data as written by libtorrent. If not set, the resume
+    /// data of the torrent in the session's ``ResumeStore`` is used, if any.
+    pub fn resume_data(mut self, resume_data: Vec<u8>) -> Self {
+        self.resume_data = Some(resume_data);
+        self
+    }
+
+    pub fn build(self) -> LTResult<AddTorrentParams> {
+        let mut p = ffi::AddTorrentParams::default();
+
+        if let Some(save_path) = self.save_path {
+            not_empty("save_path", &save_path)?;
+            p.save_path = save_path;
+        }
+
+        if let Some(name) = self.name {
+            not_empty("name", &name)?;
+            p.name = name;
+        }
+
+        for (url, tier) in self.trackers {
+            check_url(&url, &["http", "https", "udp"]).map_err(|reason| invalid("tracker", reason))?;
+            p.trackers.push(url);
+            p.tracker_tiers.push(tier as i32);
+        }
+
+        for (host, port) in self.dht_nodes {
+            not_empty("dht_node", &host)?;
+            not_zero_port("dht_node", port)?;
+            p.dht_nodes.push(HostPort { host, port });
+        }
+
+        if let Some(flags) = self.flags {
+            p.has_flags = true;
+            p.flags = flags.bits();
+        }
+
+        if let Some(storage_mode) = self.storage_mode {
+            p.has_storage_mode = true;
+            p.storage_mode = storage_mode as u8;
+        }
+
+        if let Some(v) = self.max_uploads {
+            p.has_max_uploads = true;
+            p.max_uploads = limit("max_uploads", v)?;
+        }
+
+        if let Some(v) = self.max_connections {
+            p.has_max_connections = true;
+            p.max_connections = connections_limit("max_connections", v)?;
+        }
+
+        if let Some(v) = self.upload_limit {
+            p.has_upload_limit = true;
+            p.upload_limit = limit("upload_limit", v)?;
+        }
+
+        if let Some(v) = self.download_limit {
+            p.has_download_limit = true;
+            p.download_limit = limit("download_limit", v)?;
+        }
+
+        p.file_priorities = self.file_priorities;
+
+        for (file_index, path) in self.renamed_files {
+            if file_index < 0 {
+                return Err(invalid("renamed_files", format!("negative file index {file_index}")));
+            }
+            not_empty("renamed_files", &path)?;
+            p.renamed_files.push(RenamedFile { file_index, path });
+        }
+
+        for url in self.web_seeds {
+            check_url(&url, &["http", "https"]).map_err(|reason| invalid("web_seed", reason))?;
+            p.url_seeds.push(url);
+        }
+
+        for peer in self.peers {
+            not_zero_port("peer", peer.port())?;
+            p.peers.push(HostPort {
+                host: peer.ip().to_string(),
+                port: peer.port(),
+            });
+        }
+
+        if let Some(resume_data) = self.resume_data {
+            if resume_data.is_empty() {
+                return Err(invalid("resume_data", "empty".to_string()));
+            }
+            p.resume_data = resume_data;
+        }
+
+        Ok(AddTorrentParams { inner: p })
+    }
+}
+
+fn invalid(param: &'static str, reason: String) -> LTError {
+    LTError::InvalidAddTorrentParam(param, reason)
+}
+
+fn not_empty(param: &'static str, value: &str) -> LTResult<()> {
+    if value.trim().is_empty() {
+        return Err(invalid(param, "empty".to_string()));
+    }
+    Ok(())
+}
+
+fn not_zero_port(param: &'static str, port: u16) -> LTResult<()> {
+    if port == 0 {
+        return Err(invalid(param, "port 0".to_string()));
+    }
+    Ok(())
+}
+
+/// -1 means unlimited, anything below is invalid
+fn limit(param: &'static str, value: i32) -> LTResult<i32> {
+    if value < -1 {
+        return Err(invalid(param, format!("{value} is less than -1")));
+    }
+    Ok(value)
+}
+
+/// libtorrent needs at least 2 connections per torrent
+fn connections_limit(param: &'static str, value: i32) -> LTResult<i32> {
+    if value != -1 && value < 2 {
+        return Err(invalid(param, format!("{value} is neither -1 nor at least 2")));
+    }
+    Ok(value)
+}
+
+/// checks that ``url`` has one of ``schemes`` and a host, returning the reason it is rejected
+pub(crate) fn check_url(url: &str, schemes: &[&str]) -> Result<(), String> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(format!("{url:?} is not a url"));
+    };
+
+    if !schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)) {
+        return Err(format!("{url:?} has unsupported scheme {scheme:?}"));
+    }
+
+    if rest.is_empty() || rest.starts_with('/') {
+        return Err(format!("{url:?} has no host"));
+    }
+
+    if url.contains(char::is_whitespace) {
+        return Err(format!("{url:?} contains whitespace"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use std::{
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        Mutex, MutexGuard,
+        mpsc::{Receiver, channel},
+    },
+};
+
+use libtorrent_rasterbar_sys::ffi::{self, AlertKind};
+use serde::Serialize;
+
+use crate::{errors::ErrorCode, info_hash::InfoHashes};
+
+/// libtorrent/alert_types.hpp
+///
+/// the alerts of a session which are delivered to the subscribers, see
+/// ``LTSession::subscribe``.
+///
+/// ``info_hashes`` are the info-hashes of the torrent the alert belongs to.
+/// ``error`` is ``None`` if libtorrent reported the failure without an error
+/// code.
+#[derive(Serialize, Debug, Clone)]
+pub enum Alert {
+    /// a torrent was added to the session. ``error`` is set if adding the
+    /// torrent failed.
+    TorrentAdded {
+        info_hashes: InfoHashes,
+        name: String,
+        error: Option<ErrorCode>,
+    },
+
+    /// a torrent was removed from the session
+    TorrentRemoved { info_hashes: InfoHashes },
+
+    /// a torrent switched from being a downloader to a seed. It will only be
+    /// generated once per torrent.
+    TorrentFinished { info_hashes: InfoHashes },
+
+    /// a torrent was paused
+    TorrentPaused { info_hashes: InfoHashes },
+
+    /// a torrent was resumed
+    TorrentResumed { info_hashes: InfoHashes },
+
+    /// a torrent was paused because of an error. ``path`` is the file the
+    /// error is attributed to, if any.
+    TorrentError {
+        info_hashes: InfoHashes,
+        path: String,
+        error: Option<ErrorCode>,
+    },
+
+    /// a torrent changed state, see ``State``
+    StateChanged {
+        info_hashes: InfoHashes,
+        state: u8,
+        prev_state: u8,
+    },
+
+    /// the metadata of a torrent added via a magnet link was received
+    MetadataReceived { info_hashes: InfoHashes },
+
+    /// a tracker announce succeeded
+    TrackerReply {
+        info_hashes: InfoHashes,
+        tracker_url: String,
+        num_peers: i32,
+    },
+
+    /// a tracker announce failed
+    TrackerError {
+        info_hashes: InfoHashes,
+        tracker_url: String,
+        times_in_row: i32,
+        error: Option<ErrorCode>,
+    },
+
+    /// a file of a torrent completed downloading
+    FileCompleted { info_hashes: InfoHashes, file_index: i32 },
+
+    /// a file of a torrent could not be read or written
+    FileError {
+        info_hashes: InfoHashes,
+        path: String,
+        error: Option<ErrorCode>,
+    },
+
+    /// a piece failed the hash check
+    HashFailed { info_hashes: InfoHashes, piece_index: i32 },
+
+    /// the storage of a torrent was moved to ``path``
+    StorageMoved { info_hashes: InfoHashes, path: String },
+
+    /// moving the storage of a torrent failed
+    StorageMovedFailed {
+        info_hashes: InfoHashes,
+        path: String,
+        error: Option<ErrorCode>,
+    },
+
+    /// the peers of a torrent were received, see ``LTTorrentHandle::get_peers``
+    PeerInfoReceived { info_hashes: InfoHashes },
+
+    /// the file progress of a torrent was received, see
+    /// ``LTTorrentHandle::get_file_progress``
+    FileProgressReceived { info_hashes: InfoHashes },
+
+    /// the download queue of a torrent was received, see
+    /// ``LTTorrentHandle::get_piece_info``
+    PieceInfoReceived { info_hashes: InfoHashes },
+
+    /// the piece availability of a torrent was received, see
+    /// ``LTTorrentHandle::get_piece_availability``
+    PieceAvailabilityReceived { info_hashes: InfoHashes },
+
+    /// the trackers of a torrent were received, see
+    /// ``LTTorrentHandle::get_trackers``
+    TrackerListReceived { info_hashes: InfoHashes },
+
+    /// the session is listening on ``endpoint`` (ip:port)
+    ListenSucceeded { endpoint: String },
+
+    /// the session failed to listen on ``endpoint`` (ip:port)
+    ListenFailed { endpoint: String, error: Option<ErrorCode> },
+}
+
+impl Alert {
+    /// returns ``None`` for alert kinds this version does not know about
+    pub(crate) fn from_ffi(a: ffi::Alert) -> Option<Alert> {
+        let info_hashes = InfoHashes::from(a.info_hashes);
+        let error = ErrorCode::from_ffi(a.error);
+
+        let alert = match a.kind {
+            AlertKind::TorrentAdded => Alert::TorrentAdded {
+                info_hashes,
+                name: a.torrent_name,
+                error,
+            },
+            AlertKind::TorrentRemoved => Alert::TorrentRemoved { info_hashes },
+            AlertKind::TorrentFinished => Alert::TorrentFinished { info_hashes },
+            AlertKind::TorrentPaused => Alert::TorrentPaused { info_hashes },
+            AlertKind::TorrentResumed => Alert::TorrentResumed { info_hashes },
+            AlertKind::TorrentError => Alert::TorrentError {
+                info_hashes,
+                path: a.path,
+                error,
+            },
+            AlertKind::StateChanged => Alert::StateChanged {
+                info_hashes,
+                state: a.state,
+                prev_state: a.prev_state,
+            },
+            AlertKind::MetadataReceived => Alert::MetadataReceived { info_hashes },
+            AlertKind::TrackerReply => Alert::TrackerReply {
+                info_hashes,
+                tracker_url: a.tracker_url,
+                num_peers: a.count,
+            },
+            AlertKind::TrackerError => Alert::TrackerError {
+                info_hashes,
+                tracker_url: a.tracker_url,
+                times_in_row: a.count,
+                error,
+            },
+            AlertKind::FileCompleted => Alert::FileCompleted {
+                info_hashes,
+                file_index: a.file_index,
+            },
+            AlertKind::FileError => Alert::FileError {
+                info_hashes,
+                path: a.path,
+                error,
+            },
+            AlertKind::HashFailed => Alert::HashFailed {
+                info_hashes,
+                piece_index: a.piece_index,
+            },
+            AlertKind::StorageMoved => Alert::StorageMoved {
+                info_hashes,
+                path: a.path,
+            },
+            AlertKind::StorageMovedFailed => Alert::StorageMovedFailed {
+                info_hashes,
+                path: a.path,
+                error,
+            },
+            AlertKind::PeerInfo => Alert::PeerInfoReceived { info_hashes },
+            AlertKind::FileProgress => Alert::FileProgressReceived { info_hashes },
+            AlertKind::PieceInfo => Alert::PieceInfoReceived { info_hashes },
+            AlertKind::PieceAvailability => Alert::PieceAvailabilityReceived { info_hashes },
+            AlertKind::TrackerList => Alert::TrackerListReceived { info_hashes },
+            AlertKind::ListenSucceeded => Alert::ListenSucceeded { endpoint: a.endpoint },
+            AlertKind::ListenFailed => Alert::ListenFailed {
+                endpoint: a.endpoint,
+                error,
+            },
+            _ => return None,
+        };
+
+        Some(alert)
+    }
+
+    /// the info-hashes of the torrent this alert belongs to, or ``None`` for
+    /// session-level alerts
+    pub fn info_hashes(&self) -> Option<&InfoHashes> {
+        match self {
+            Alert::TorrentAdded { info_hashes, .. }
+            | Alert::TorrentRemoved { info_hashes }
+            | Alert::TorrentFinished { info_hashes }
+            | Alert::TorrentPaused { info_hashes }
+            | Alert::TorrentResumed { info_hashes }
+            | Alert::TorrentError { info_hashes, .. }
+            | Alert::StateChanged { info_hashes, .. }
+            | Alert::MetadataReceived { info_hashes }
+            | Alert::TrackerReply { info_hashes, .. }
+            | Alert::TrackerError { info_hashes, .. }
+            | Alert::FileCompleted { info_hashes, .. }
+            | Alert::FileError { info_hashes, .. }
+            | Alert::HashFailed { info_hashes, .. }
+            | Alert::StorageMoved { info_hashes, .. }
+            | Alert::StorageMovedFailed { info_hashes, .. }
+            | Alert::PeerInfoReceived { info_hashes }
+            | Alert::FileProgressReceived { info_hashes }
+            | Alert::PieceInfoReceived { info_hashes }
+            | Alert::PieceAvailabilityReceived { info_hashes }
+            | Alert::TrackerListReceived { info_hashes } => Some(info_hashes),
+            Alert::ListenSucceeded { .. } | Alert::ListenFailed { .. } => None,
+        }
+    }
+}
+
+/// a subscriber returns false once it is no longer interested in alerts
+type Subscriber = Box<dyn Fn(&Alert) -> bool + Send>;
+
+/// fans out the alerts of a session to all subscribers
+pub(crate) struct AlertDispatcher {
+    /// ``None`` once the session is dropped
+    subscribers: Mutex<Option<Vec<Subscriber>>>,
+}
+
+impl Default for AlertDispatcher {
+    fn default() -> Self {
+        AlertDispatcher {
+            subscribers: Mutex::new(Some(Vec::new())),
+        }
+    }
+}
+
+impl AlertDispatcher {
+    fn lock(&self) -> MutexGuard<'_, Option<Vec<Subscriber>>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// a subscriber added after ``close`` is dropped right away, so its
+    /// receiver is disconnected
+    pub(crate) fn add_subscriber(&self, subscriber: Subscriber) {
+        if let Some(subscribers) = self.lock().as_mut() {
+            subscribers.push(subscriber);
+        }
+    }
+
+    /// drops the subscribers, called when the session is dropped
+    pub(crate) fn close(&self) {
+        *self.lock() = None;
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<Alert> {
+        let (tx, rx) = channel();
+        self.add_subscriber(Box::new(move |alert| tx.send(alert.clone()).is_ok()));
+        rx
+    }
+
+    pub(crate) fn dispatch(&self, alert: ffi::Alert) {
+        let Some(alert) = Alert::from_ffi(alert) else {
+            return;
+        };
+
+        // drop the subscribers whose receiver is gone and those which panic,
+        // the panic must not unwind into the alert thread of the session
+        if let Some(subscribers) = self.lock().as_mut() {
+            subscribers.retain(|subscriber| catch_unwind(AssertUnwindSafe(|| subscriber(&alert))).unwrap_or(false));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use futures::{
+    StreamExt,
+    channel::mpsc::{UnboundedReceiver, unbounded},
+};
+use libtorrent_rasterbar_sys::ffi::{
+    self, AddTorrentError, AddTorrentResult, TorrentHandle, classify_add_torrent_error,
+};
+
+use crate::{
+    AddTorrentParams, AddedTorrent, Alert, AnnounceEntry, InfoHashes, LTError, LTResult, LTSession, LTTorrentHandle,
+    PeerInfo, PieceInfo, TorrentInfo, add_torrent_params::add_torrent_error, alert::AlertDispatcher,
+};
+
+/// the typed alerts of a session as a ``futures::Stream``
+pub type AlertStream = UnboundedReceiver<Alert>;
+
+impl AlertDispatcher {
+    pub(crate) fn subscribe_stream(&self) -> AlertStream {
+        let (tx, rx) = unbounded();
+        self.add_subscriber(Box::new(move |alert| tx.unbounded_send(alert.clone()).is_ok()));
+        rx
+    }
+}
+
+impl LTSession {
+    /// subscribes to the typed alerts of the session as a stream.
+    ///
+    /// Every stream receives every alert posted after it was created. The
+    /// subscription ends when the stream is dropped.
+    pub fn alert_stream(&self) -> AlertStream {
+        self.inner.alert_dispatcher.subscribe_stream()
+    }
+
+    /// the awaitable version of ``add_torrent``
+    pub async fn add_torrent_async(&self, torrent_path: &str, params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        // subscribe before adding, so the add_torrent_alert can't be missed
+        let alerts = self.inner.alert_dispatcher.subscribe_stream();
+
+        let r = self
+            .inner
+            .async_add_torrent(torrent_path, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))?;
+
+        self.wait_for_added(alerts, r, LTError::FailedToAddTorrent).await
+    }
+
+    /// the awaitable version of ``add_magnet``
+    pub async fn add_magnet_async(&self, magnet_uri: &str, params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        let alerts = self.inner.alert_dispatcher.subscribe_stream();
+
+        let r = self
+            .inner
+            .async_add_magnet(magnet_uri, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddMagnet(e.to_string()))?;
+
+        self.wait_for_added(alerts, r, LTError::FailedToAddMagnet).await
+    }
+
+    /// the awaitable version of ``add_torrent_bytes``
+    pub async fn add_torrent_bytes_async(&self, data: &[u8], params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        let alerts = self.inner.alert_dispatcher.subscribe_stream();
+
+        let r = self
+            .inner
+            .async_add_torrent_bytes(data, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))?;
+
+        self.wait_for_added(alerts, r, LTError::FailedToAddTorrent).await
+    }
+
+    /// the awaitable version of ``add_torrent_info``
+    pub async fn add_torrent_info_async(
+        &self,
+        info: &TorrentInfo,
+        params: &AddTorrentParams,
+    ) -> LTResult<AddedTorrent> {
+        self.add_torrent_bytes_async(info.torrent_bytes()?, params).await
+    }
+
+    async fn wait_for_added(
+        &self,
+        mut alerts: AlertStream,
+        r: AddTorrentResult,
+        other: fn(String) -> LTError,
+    ) -> LTResult<AddedTorrent> {
+        if r.error_kind != AddTorrentError::None {
+            return Err(add_torrent_error(r.error_kind, r.error.message.clone(), &r, other));
+        }
+
+        let added = InfoHashes::from(r.info_hashes.clone());
+        while let Some(alert) = alerts.next().await {
+            let Alert::TorrentAdded { info_hashes, error, .. } = alert else {
+                continue;
+            };
+            if !info_hashes.matches(&added) {
+                continue;
+            }
+
+            if let Some(error) = error {
+                let kind = classify_add_torrent_error(&ffi::ErrorCode {
+                    category: error.category,
+                    value: error.value,
+                    message: error.message.clone(),
+                });
+                return Err(add_torrent_error(kind, error.message, &r, other));
+            }
+
+            let handle = self.get_torrent_handle(&r.info_hash.parse()?)?;
+            return Ok(AddedTorrent::new(handle, r));
+        }
+
+        Err(other("the session is closed".to_string()))
+    }
+}
+
+impl LTTorrentHandle {
+    /// posts a query for this torrent and waits for its reply alert.
+    ///
+    /// Unlike the blocking queries there is no timeout, wrap the future in
+    /// the timer of your runtime instead.
+    async fn wait_for_reply_async<P, R>(&self, post: P, is_reply: R) -> LTResult<()>
+    where
+        P: FnOnce(&TorrentHandle) -> Result<(), cxx::Exception>,
+        R: Fn(&Alert) -> bool,
+    {
+        let info_hashes = self.info_hashes()?;
+
+        // subscribe before posting, so the reply can't be missed. The stream
+        // ends if the session is dropped meanwhile.
+        let mut alerts = self.session()?.alert_dispatcher.subscribe_stream();
+        self.call(post)?;
+
+        while let Some(alert) = alerts.next().await {
+            if !alert.info_hashes().is_some_and(|h| h.matches(&info_hashes)) {
+                continue;
+            }
+            if is_reply(&alert) {
+                return Ok(());
+            }
+            if let Alert::TorrentRemoved { .. } = alert {
+                break;
+            }
+        }
+
+        Err(LTError::InvalidTorrentHandle)
+    }
+
+    /// the awaitable version of ``get_peers``
+    pub async fn get_peers_async(&self) -> LTResult<Vec<PeerInfo>> {
+        self.wait_for_reply_async(|h| h.post_peer_info(), |a| matches!(a, Alert::PeerInfoReceived { .. }))
+            .await?;
+
+        Ok(self.call(|h| Ok(h.peers()))?.into_iter().map(PeerInfo::from).collect())
+    }
+
+    /// the awaitable version of ``get_file_progress``
+    pub async fn get_file_progress_async(&self, piece_granularity: bool) -> LTResult<Vec<i64>> {
+        self.wait_for_reply_async(
+            |h| h.post_file_progress(piece_granularity),
+            |a| matches!(a, Alert::FileProgressReceived { .. }),
+        )
+        .await?;
+
+        self.call(|h| Ok(h.file_progress()))
+    }
+
+    /// the awaitable version of ``get_piece_info``
+    pub async fn get_piece_info_async(&self) -> LTResult<PieceInfo> {
+        self.wait_for_reply_async(
+            |h| h.post_download_queue(),
+            |a| matches!(a, Alert::PieceInfoReceived { .. }),
+        )
+        .await?;
+
+        Ok(self.call(|h| Ok(h.piece_info()))?.into())
+    }
+
+    /// the awaitable version of ``get_piece_availability``
+    pub async fn get_piece_availability_async(&self) -> LTResult<Vec<i32>> {
+        self.wait_for_reply_async(
+            |h| h.post_piece_availability(),
+            |a| matches!(a, Alert::PieceAvailabilityReceived { .. }),
+        )
+        .await?;
+
+        self.call(|h| Ok(h.piece_availability()))
+    }
+
+    /// the awaitable version of ``get_trackers``
+    pub async fn get_trackers_async(&self) -> LTResult<Vec<AnnounceEntry>> {
+        self.wait_for_reply_async(
+            |h| h.post_trackers(),
+            |a| matches!(a, Alert::TrackerListReceived { .. }),
+        )
+        .await?;
+
+        Ok(self
+            .call(|h| Ok(h.trackers()))?
+            .into_iter()
+            .map(AnnounceEntry::from)
+            .collect())
+    }
+}
@@ -1,3 +1,7 @@
+use std::{fmt, io, path::PathBuf};
+
+use libtorrent_rasterbar_sys::ffi::{self, OperationErrorKind};
+use serde::Serialize;
 use thiserror::Error;
 
 pub type LTResult<T> = Result<T, LTError>;
@@ -7,9 +11,141 @@ pub enum LTError {
     #[error("Failed to create session: {0}")]
     FailedToCreateSession(String),
 
+    #[error("Failed to save session state: {0}")]
+    FailedToSaveState(String),
+
     #[error("Failed to add torrent: {0}")]
     FailedToAddTorrent(String),
 
     #[error("Failed to add magnet: {0}")]
     FailedToAddMagnet(String),
+
+    #[error("Torrent already exists: {0}")]
+    DuplicateTorrent(String),
+
+    #[error("Invalid torrent metadata: {0}")]
+    InvalidMetadata(String),
+
+    #[error("Invalid bencoding: {0}")]
+    InvalidBencode(String),
+
+    #[error("Torrent has no info dictionary: {0}")]
+    MissingInfoDict(String),
+
+    #[error("Invalid piece length: {0}")]
+    InvalidPieceLength(String),
+
+    #[error("I/O error on {0}: {1}")]
+    Io(PathBuf, #[source] io::Error),
+
+    #[error("Invalid save path {0}: {1}")]
+    InvalidSavePath(String, String),
+
+    #[error("Invalid add torrent param {0}: {1}")]
+    InvalidAddTorrentParam(&'static str, String),
+
+    #[error("Invalid torrent builder param {0}: {1}")]
+    InvalidTorrentBuilderParam(&'static str, String),
+
+    #[error("Failed to create torrent: {0}")]
+    FailedToCreateTorrent(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("Invalid torrent handle")]
+    InvalidTorrentHandle,
+
+    #[error("Invalid info-hash: {0:?}")]
+    InvalidInfoHash(String),
+
+    #[error("Invalid file index: {0}")]
+    InvalidFileIndex(i32),
+
+    #[error("Torrent has no metadata yet")]
+    NoMetadata,
+
+    #[error("libtorrent error: {0}")]
+    Libtorrent(ErrorCode),
+
+    #[error("Invalid setting {0}: {1}")]
+    InvalidSetting(String, String),
+
+    #[error("Invalid settings profile: {0}")]
+    InvalidSettingsProfile(String),
+
+    #[error("Invalid session config {0}: {1}")]
+    InvalidSessionConfig(&'static str, String),
+
+    #[error("Not an orphaned torrent: {0}")]
+    NotOrphaned(String),
+
+    #[error("Resume store error: {0}")]
+    ResumeStore(String),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error on {0}: {1}")]
+    Sqlite(PathBuf, #[source] rusqlite::Error),
+
+    #[error("Timed out waiting for {0}")]
+    Timeout(&'static str),
+}
+
+/// libtorrent/error_code.hpp
+///
+/// an error reported by libtorrent
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ErrorCode {
+    /// the name of the error category, e.g. "libtorrent", "system", "bdecode"
+    pub category: String,
+    pub value: i32,
+    pub message: String,
+}
+
+impl ErrorCode {
+    /// returns ``None`` if the error code holds no error
+    pub(crate) fn from_ffi(ec: ffi::ErrorCode) -> Option<ErrorCode> {
+        if ec.value == 0 {
+            return None;
+        }
+
+        Some(ErrorCode {
+            category: ec.category,
+            value: ec.value,
+            message: ec.message,
+        })
+    }
+}
+
+impl From<cxx::Exception> for LTError {
+    /// maps the exceptions thrown by the operations of the session and the
+    /// torrent handles by their ``OperationError``, see
+    /// libtorrent-rasterbar-sys/wrap/utils.hpp
+    fn from(e: cxx::Exception) -> LTError {
+        let err = ffi::take_operation_error();
+
+        match err.kind {
+            OperationErrorKind::InvalidHandle => LTError::InvalidTorrentHandle,
+            OperationErrorKind::InvalidInfoHash => LTError::InvalidInfoHash(err.info_hash),
+            OperationErrorKind::InvalidFileIndex => LTError::InvalidFileIndex(err.file_index),
+            OperationErrorKind::NoMetadata => LTError::NoMetadata,
+            OperationErrorKind::ErrorCode => LTError::Libtorrent(ErrorCode {
+                category: err.error.category,
+                value: err.error.value,
+                message: err.error.message,
+            }),
+            // any other exception of the wrapper
+            _ => LTError::Libtorrent(ErrorCode {
+                category: "exception".to_string(),
+                value: -1,
+                message: e.what().to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} {}", self.category, self.value, self.message)
+    }
 }
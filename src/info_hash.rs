@@ -0,0 +1,216 @@
+use std::{fmt, str::FromStr};
+
+use libtorrent_rasterbar_sys::ffi;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::LTError;
+
+/// libtorrent/sha1_hash.hpp
+///
+/// a SHA-1 digest, the info-hash of a BitTorrent v1 torrent
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sha1Hash(pub [u8; 20]);
+
+/// libtorrent/sha1_hash.hpp
+///
+/// a SHA-256 digest, the info-hash of a BitTorrent v2 torrent
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Sha256Hash(pub [u8; 32]);
+
+macro_rules! impl_hash {
+    ($name:ident, $len:literal) => {
+        impl $name {
+            /// parses the hex encoded digest, returns ``None`` if it's not
+            /// valid hex of the right length
+            pub fn from_hex(hex: &str) -> Option<$name> {
+                let mut bytes = [0u8; $len];
+                decode_hex(hex, &mut bytes)?;
+                Some($name(bytes))
+            }
+
+            /// parses the base32 (RFC 4648) encoded digest, as used by some
+            /// magnet links. Padding is optional and case is ignored.
+            pub fn from_base32(base32: &str) -> Option<$name> {
+                let mut bytes = [0u8; $len];
+                decode_base32(base32, &mut bytes)?;
+                Some($name(bytes))
+            }
+
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for b in self.0 {
+                    write!(f, "{b:02x}")?;
+                }
+                Ok(())
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    };
+}
+
+impl_hash!(Sha1Hash, 20);
+impl_hash!(Sha256Hash, 32);
+
+fn decode_hex(hex: &str, out: &mut [u8]) -> Option<()> {
+    // from_str_radix alone would accept a sign, e.g. "+f"
+    if hex.len() != out.len() * 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    for (i, pair) in hex.as_bytes().chunks(2).enumerate() {
+        let s = std::str::from_utf8(pair).ok()?;
+        out[i] = u8::from_str_radix(s, 16).ok()?;
+    }
+
+    Some(())
+}
+
+fn decode_base32(base32: &str, out: &mut [u8]) -> Option<()> {
+    let base32 = base32.trim_end_matches('=');
+    if base32.len() != (out.len() * 8).div_ceil(5) {
+        return None;
+    }
+
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    let mut i = 0;
+    for c in base32.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out[i] = (buffer >> bits) as u8;
+            i += 1;
+        }
+    }
+
+    // the unused low bits of the last character must be zero
+    if buffer & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+
+    Some(())
+}
+
+/// the info-hash identifying a torrent in a session, either a v1 (SHA-1) or
+/// a v2 (SHA-256) info-hash.
+///
+/// It is parsed from 40 (v1) or 64 (v2) hex digits, or from the base32 form
+/// of magnet links. ``Display`` and serde use the hex form.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum InfoHash {
+    V1(Sha1Hash),
+    V2(Sha256Hash),
+}
+
+impl FromStr for InfoHash {
+    type Err = LTError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let info_hash = match s.trim_end_matches('=').len() {
+            40 => Sha1Hash::from_hex(s).map(InfoHash::V1),
+            64 => Sha256Hash::from_hex(s).map(InfoHash::V2),
+            32 => Sha1Hash::from_base32(s).map(InfoHash::V1),
+            52 => Sha256Hash::from_base32(s).map(InfoHash::V2),
+            _ => None,
+        };
+        info_hash.ok_or_else(|| LTError::InvalidInfoHash(s.to_string()))
+    }
+}
+
+impl fmt::Display for InfoHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InfoHash::V1(h) => h.fmt(f),
+            InfoHash::V2(h) => h.fmt(f),
+        }
+    }
+}
+
+impl From<Sha1Hash> for InfoHash {
+    fn from(h: Sha1Hash) -> Self {
+        InfoHash::V1(h)
+    }
+}
+
+impl From<Sha256Hash> for InfoHash {
+    fn from(h: Sha256Hash) -> Self {
+        InfoHash::V2(h)
+    }
+}
+
+impl Serialize for InfoHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InfoHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// libtorrent/info_hash.hpp
+///
+/// class holding the info-hash of a torrent. It can hold a v1 info-hash
+/// (SHA-1) or a v2 info-hash (SHA-256) or both (a hybrid torrent).
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InfoHashes {
+    pub v1: Option<Sha1Hash>,
+    pub v2: Option<Sha256Hash>,
+}
+
+impl InfoHashes {
+    pub fn has_v1(&self) -> bool {
+        self.v1.is_some()
+    }
+
+    pub fn has_v2(&self) -> bool {
+        self.v2.is_some()
+    }
+
+    /// the v1 info-hash if there is one, otherwise the v2 info-hash. Either
+    /// can be used to look up the torrent.
+    pub fn best(&self) -> Option<InfoHash> {
+        self.v1.map(InfoHash::V1).or(self.v2.map(InfoHash::V2))
+    }
+
+    /// true if both refer to the same torrent, i.e. they share the v1 or the
+    /// v2 info-hash. A torrent added by a v1 magnet link learns its v2
+    /// info-hash only with the metadata.
+    pub fn matches(&self, other: &InfoHashes) -> bool {
+        (self.v1.is_some() && self.v1 == other.v1) || (self.v2.is_some() && self.v2 == other.v2)
+    }
+}
+
+impl From<ffi::InfoHashes> for InfoHashes {
+    fn from(h: ffi::InfoHashes) -> Self {
+        Self {
+            v1: Sha1Hash::from_hex(&h.v1),
+            v2: Sha256Hash::from_hex(&h.v2),
+        }
+    }
+}
@@ -1,127 +1,358 @@
+use std::{
+    ops::Deref,
+    sync::{
+        Arc, Weak,
+        mpsc::{Receiver, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
+
 use cxx::UniquePtr;
 
-use libtorrent_rasterbar_sys::ffi::{ParamPair, Session, TorrentHandle, create_session};
+use libtorrent_rasterbar_sys::{
+    AlertSink, ResumeStoreBox,
+    ffi::{AddTorrentError, AddTorrentResult, Session, SessionOptions, TorrentHandle, create_session},
+};
 
-pub use libtorrent_rasterbar_sys::flags::{
-    BandwidthStateFlags, ConnectionType, PauseFlags, PeerFlags, PeerSourceFlags, SaveStateFlags, TorrentFlags,
+pub use libtorrent_rasterbar_sys::{
+    ResumeStore,
+    ffi::ResumeEntry,
+    flags::{
+        AlertCategory, BandwidthStateFlags, ConnectionType, PauseFlags, PeerFlags, PeerSourceFlags, SaveStateFlags,
+        TorrentFlags,
+    },
 };
 
+mod add_torrent_params;
+mod alert;
 mod announce_entry;
+#[cfg(feature = "async")]
+mod asynchronous;
 mod download_priority;
 mod errors;
+mod info_hash;
 mod log;
 mod peer_info;
 mod piece_info;
+mod resume_store;
+mod session_config;
 mod session_stats;
+mod settings_pack;
+mod settings_profile;
+#[cfg(feature = "sqlite")]
+mod sqlite_store;
+mod torrent_builder;
 mod torrent_info;
 mod torrent_status;
+#[cfg(feature = "tracing")]
+mod tracing_log;
+
+mod test_session;
 
-mod tests;
+use add_torrent_params::add_torrent_error;
+use alert::AlertDispatcher;
+use session_config::{invalid_config, path_str, post_interval_ms};
 
+pub use add_torrent_params::{AddTorrentParams, AddTorrentParamsBuilder, AddedTorrent, StorageMode};
+pub use alert::Alert;
 pub use announce_entry::AnnounceEntry;
+#[cfg(feature = "async")]
+pub use asynchronous::AlertStream;
 pub use download_priority::DownloadPriority;
-pub use errors::{LTError, LTResult};
-pub use log::Log;
+pub use errors::{ErrorCode, LTError, LTResult};
+pub use info_hash::{InfoHash, InfoHashes, Sha1Hash, Sha256Hash};
+pub use log::{Log, LogFilter, LogLevel, LogOverflowPolicy, LogTarget};
 pub use peer_info::PeerInfo;
 pub use piece_info::PieceInfo;
-pub use session_stats::{Metrics, SessionStats};
+pub use resume_store::{
+    DirResumeStore, OrphanedTorrent, ResumeLoadFailure, ResumeRecovery, SaveResumeFailure, SaveResumeSummary,
+    ShutdownReport,
+};
+pub use session_config::{SessionBuilder, SessionConfig};
+pub use session_stats::{Metrics, SessionStats, StatsPost};
+pub use settings_pack::{SettingValue, SettingsPack};
+pub use settings_profile::{SettingsPreset, SettingsProfile};
+#[cfg(feature = "sqlite")]
+pub use sqlite_store::SqliteStore;
+pub use torrent_builder::{TorrentBuilder, TorrentVersion};
 pub use torrent_info::TorrentInfo;
 pub use torrent_status::{State, TorrentStatus};
 
 /// the main libtorrent-rasterbar API.
 pub struct LTSession {
-    inner: UniquePtr<Session>,
+    inner: Arc<SessionInner>,
+    ephemeral: bool,
+}
+
+/// the session shared by ``LTSession`` with its torrent handles. The handles
+/// hold it weakly, so they fail once the session is dropped instead of using
+/// the destroyed session.
+pub(crate) struct SessionInner {
+    session: UniquePtr<Session>,
+    pub(crate) alert_dispatcher: Arc<AlertDispatcher>,
 }
 
+impl Deref for SessionInner {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl Drop for SessionInner {
+    fn drop(&mut self) {
+        // the session posts alerts until it's destroyed, the subscribers are
+        // disconnected afterwards
+        self.session = UniquePtr::null();
+        self.alert_dispatcher.close();
+    }
+}
+
+unsafe impl Sync for SessionInner {}
+unsafe impl Send for SessionInner {}
+
 impl LTSession {
-    /// creates a new session.
-    ///
-    /// The default values of the session settings are set for a regular
-    /// bittorrent client running on a desktop system. There are functions that
-    /// can set the session settings to pre set settings for other environments.
-    /// These can be used for the basis, and should be tweaked to fit your needs
-    /// better.
-    ///
-    /// ``min_memory_usage`` returns settings that will use the minimal amount of
-    /// RAM, at the potential expense of upload and download performance. It
-    /// adjusts the socket buffer sizes, disables the disk cache, lowers the send
-    /// buffer watermarks so that each connection only has at most one block in
-    /// use at any one time. It lowers the outstanding blocks send to the disk
-    /// I/O thread so that connections only have one block waiting to be flushed
-    /// to disk at any given time. It lowers the max number of peers in the peer
-    /// list for torrents. It performs multiple smaller reads when it hashes
-    /// pieces, instead of reading it all into memory before hashing.
-    ///
-    /// This configuration is intended to be the starting point for embedded
-    /// devices. It will significantly reduce memory usage.
-    ///
-    /// ``high_performance_seed`` returns settings optimized for a seed box,
-    /// serving many peers and that doesn't do any downloading. It has a 128 MB
-    /// disk cache and has a limit of 400 files in its file pool. It support fast
-    /// upload rates by allowing large send buffers.
-    ///
-    /// ``session_param_list`` is a list of key-value pairs that will be used to
-    /// override the default values.
-    /// The definations and default values of the session settings are in
+    /// creates a new session from ``config``.
+    ///
+    /// The default settings are set for a regular bittorrent client running
+    /// on a desktop system, ``SettingsPreset`` has presets for other
+    /// environments. The definations and default values of the session
+    /// settings are in
     /// libtorrent/include/settings_pack.hpp
     /// libtorrent/src/settings_pack.cpp
-    pub fn new(
-        min_memory_usage: bool,
-        high_performance_seed: bool,
-        session_param_list: &[(&str, &str)],
-        save_state_flags: u32,
-        session_state_path: &str,
-        resume_dir: &str,
-        torrent_dir: &str,
-        log_size: u32,
-    ) -> LTResult<Self> {
-        let params: Vec<_> = session_param_list
-            .iter()
-            .map(|(k, v)| ParamPair { key: k, value: v })
-            .collect();
+    ///
+    /// The resume data of the torrents is kept in ``config.resume_dir``, see
+    /// ``DirResumeStore``.
+    pub fn from_config(config: &SessionConfig) -> LTResult<Self> {
+        config.validate()?;
+
+        let resume_store = match &config.resume_dir {
+            Some(resume_dir) => {
+                let mut store = DirResumeStore::new(resume_dir)?;
+                if let Some(torrent_dir) = &config.torrent_dir {
+                    store = store.with_torrent_dir(torrent_dir)?;
+                }
+                ResumeStoreBox::new(store)
+            }
+            None => ResumeStoreBox::none(),
+        };
+        let ephemeral = config.session_state_path.is_none() && config.resume_dir.is_none();
+        LTSession::create(config, resume_store, ephemeral)
+    }
+
+    /// creates a new session from ``config`` which keeps the resume data of
+    /// its torrents in ``resume_store``. The torrents of the store are added
+    /// back to the session.
+    ///
+    /// ``config`` must not have a ``resume_dir`` or ``torrent_dir``.
+    pub fn with_resume_store(config: &SessionConfig, resume_store: impl ResumeStore + 'static) -> LTResult<Self> {
+        config.validate()?;
+
+        for (param, dir) in [("resume_dir", &config.resume_dir), ("torrent_dir", &config.torrent_dir)] {
+            if dir.is_some() {
+                return Err(invalid_config(param, "must not be set with a resume store".to_string()));
+            }
+        }
 
+        LTSession::create(config, ResumeStoreBox::new(resume_store), false)
+    }
+
+    fn create(config: &SessionConfig, resume_store: Box<ResumeStoreBox>, ephemeral: bool) -> LTResult<Self> {
+        let alert_dispatcher = Arc::new(AlertDispatcher::default());
+        let dispatcher = alert_dispatcher.clone();
+
+        let alert_sink = AlertSink::new(move |alert| dispatcher.dispatch(alert));
+        #[cfg(feature = "tracing")]
+        let alert_sink = alert_sink.with_logs(tracing_log::emit);
+
+        let options = SessionOptions {
+            save_state_flags: config.save_state_flags.bits(),
+            session_state_path: path_str("session_state_path", &config.session_state_path)?.to_string(),
+            recovery_save_path: path_str("recovery_save_path", &config.recovery_save_path)?.to_string(),
+            log_size: config.log_capacity,
+            log_overflow_policy: config.log_overflow.into(),
+            alert_poll_interval_ms: config.alert_poll_interval.as_millis() as u32,
+            session_stats_interval_ms: config.session_stats_interval.as_millis() as u32,
+            torrent_updates_interval_ms: config.torrent_updates_interval.as_millis() as u32,
+            dht_stats_interval_ms: config.dht_stats_interval.as_millis() as u32,
+            autosave_interval_ms: config.autosave_interval.map_or(0, |d| d.as_millis() as u32),
+        };
         let ses = create_session(
-            min_memory_usage,
-            high_performance_seed,
-            &params,
-            save_state_flags,
-            session_state_path,
-            resume_dir,
-            torrent_dir,
-            log_size,
+            &config.profile.effective_settings().to_ffi(),
+            &options,
+            resume_store,
+            alert_sink,
         )
         .map_err(|e| LTError::FailedToCreateSession(e.to_string()))?;
 
-        Ok(Self { inner: ses })
+        Ok(Self {
+            inner: Arc::new(SessionInner {
+                session: ses,
+                alert_dispatcher,
+            }),
+            ephemeral,
+        })
     }
 
-    /// adds a torrent file.
-    pub fn add_torrent(&self, torrent_path: &str, torrent_param_list: &[(&str, &str)]) -> LTResult<()> {
-        let params: Vec<_> = torrent_param_list
-            .iter()
-            .map(|(k, v)| ParamPair { key: k, value: v })
-            .collect();
+    /// true if the session keeps nothing on disk: it has neither a
+    /// ``session_state_path`` nor a ``resume_dir`` or ``ResumeStore``, so
+    /// its torrents and state are gone once it's dropped.
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
 
+    /// the torrents of the ``ResumeStore`` whose resume data couldn't be
+    /// read when the session was created, and whether they were recovered
+    /// from the backup of the resume data or from their .torrent file. Empty
+    /// if every torrent was added back.
+    pub fn resume_load_failures(&self) -> Vec<ResumeLoadFailure> {
         self.inner
-            .add_torrent(torrent_path, &params)
-            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))
+            .resume_load_failures()
+            .into_iter()
+            .map(ResumeLoadFailure::from)
+            .collect()
     }
 
-    /// adds a magnet link.
-    pub fn add_magnet(&self, magnet_uri: &str, torrent_param_list: &[(&str, &str)]) -> LTResult<()> {
-        let params: Vec<_> = torrent_param_list
-            .iter()
-            .map(|(k, v)| ParamPair { key: k, value: v })
-            .collect();
+    /// the torrents of the ``ResumeStore`` which weren't added when the
+    /// session was created because their save path didn't exist. They stay
+    /// in the store until they're added again or forgotten.
+    pub fn orphaned_torrents(&self) -> Vec<OrphanedTorrent> {
+        self.inner
+            .orphaned_torrents()
+            .into_iter()
+            .map(OrphanedTorrent::from)
+            .collect()
+    }
 
+    /// adds the orphaned torrents whose save path exists by now, e.g. after
+    /// the drive was mounted, and returns the outcome for each of them. The
+    /// others stay orphaned, as do those which fail to be added.
+    pub fn retry_orphaned_torrents(&self) -> Vec<LTResult<AddedTorrent>> {
         self.inner
-            .add_magnet(magnet_uri, &params)
-            .map_err(|e| LTError::FailedToAddMagnet(e.to_string()))
+            .retry_orphaned_torrents()
+            .into_iter()
+            .map(|r| self.added_torrent(r, LTError::FailedToAddTorrent))
+            .collect()
+    }
+
+    /// adds an orphaned torrent with ``save_path`` instead of the one which
+    /// doesn't exist, e.g. after its files were moved. ``save_path`` is
+    /// created if it doesn't exist. Fails with ``LTError::NotOrphaned`` if
+    /// the torrent isn't orphaned.
+    pub fn relocate_orphaned_torrent(&self, info_hash: &InfoHash, save_path: &str) -> LTResult<AddedTorrent> {
+        let r = self
+            .inner
+            .relocate_orphaned_torrent(&info_hash.to_string(), save_path)
+            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))?;
+
+        self.added_torrent(r, LTError::FailedToAddTorrent)
+    }
+
+    /// removes an orphaned torrent from the ``ResumeStore`` for good. Returns
+    /// false if it isn't orphaned.
+    pub fn forget_orphaned_torrent(&self, info_hash: &InfoHash) -> LTResult<bool> {
+        self.inner
+            .forget_orphaned_torrent(&info_hash.to_string())
+            .map_err(|e| LTError::ResumeStore(e.to_string()))
+    }
+
+    /// saves the session state to the ``session_state_path`` or the
+    /// ``ResumeStore``, like when the session is dropped. Does nothing for a
+    /// session which keeps no session state.
+    pub fn save_state(&self) -> LTResult<()> {
+        self.inner
+            .save_state()
+            .map_err(|e| LTError::FailedToSaveState(e.to_string()))
+    }
+
+    /// requests the resume data of every torrent and blocks until it's
+    /// saved to the ``ResumeStore`` or ``timeout`` passed. The summary is
+    /// empty for a session without a store.
+    ///
+    /// Must not be called from an alert callback, the alerts with the resume
+    /// data are handled on that thread. For the same reason the logs must be
+    /// drained meanwhile with ``LogOverflowPolicy::Block``, a full log blocks
+    /// that thread and the call times out.
+    pub fn save_all_resume_data(&self, timeout: Duration) -> SaveResumeSummary {
+        let timeout_ms = timeout.as_millis().min(u32::MAX as u128) as u32;
+        self.inner.save_all_resume_data(timeout_ms).into()
+    }
+
+    /// shuts the session down: pauses it, waits up to ``timeout`` for the
+    /// resume data of every torrent to be saved and saves the session state.
+    /// The report tells which torrents were saved, failed or timed out.
+    ///
+    /// Dropping a session does the same with a timeout of 30s, without a
+    /// report.
+    pub fn shutdown(self, timeout: Duration) -> ShutdownReport {
+        let timeout_ms = timeout.as_millis().min(u32::MAX as u128) as u32;
+        self.inner.shutdown(timeout_ms).into()
+    }
+
+    /// a builder for a session, starting from ``SessionConfig::default()``
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::new()
+    }
+
+    /// adds a torrent file and blocks until it's added.
+    ///
+    /// the values set in ``params`` override the ones of the torrent file and
+    /// its resume data.
+    pub fn add_torrent(&self, torrent_path: &str, params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        let r = self
+            .inner
+            .add_torrent(torrent_path, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))?;
+
+        self.added_torrent(r, LTError::FailedToAddTorrent)
+    }
+
+    /// adds a magnet link and blocks until it's added.
+    ///
+    /// the values set in ``params`` override the ones of the magnet link and
+    /// its resume data.
+    pub fn add_magnet(&self, magnet_uri: &str, params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        let r = self
+            .inner
+            .add_magnet(magnet_uri, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddMagnet(e.to_string()))?;
+
+        self.added_torrent(r, LTError::FailedToAddMagnet)
     }
 
-    /// removes a torrent
-    pub fn remove_torrent(&self, info_hash_str: &str, delete_files: bool) {
-        self.inner.remove_torrent(info_hash_str, delete_files)
+    /// adds a torrent from the bencoded contents of a .torrent file and
+    /// blocks until it's added.
+    pub fn add_torrent_bytes(&self, data: &[u8], params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        let r = self
+            .inner
+            .add_torrent_bytes(data, params.as_ffi())
+            .map_err(|e| LTError::FailedToAddTorrent(e.to_string()))?;
+
+        self.added_torrent(r, LTError::FailedToAddTorrent)
+    }
+
+    /// adds a torrent that was loaded with ``TorrentInfo::from_bytes`` or
+    /// ``TorrentInfo::from_file``, see ``add_torrent_bytes``. The infos of
+    /// ``get_torrents`` and ``LTTorrentHandle::get_torrent_info`` have no
+    /// .torrent file, add ``LTTorrentHandle::get_torrent_file`` instead.
+    pub fn add_torrent_info(&self, info: &TorrentInfo, params: &AddTorrentParams) -> LTResult<AddedTorrent> {
+        self.add_torrent_bytes(info.torrent_bytes()?, params)
+    }
+
+    fn added_torrent(&self, mut r: AddTorrentResult, other: fn(String) -> LTError) -> LTResult<AddedTorrent> {
+        if r.error_kind != AddTorrentError::None {
+            return Err(add_torrent_error(r.error_kind, r.error.message.clone(), &r, other));
+        }
+
+        let handle = LTTorrentHandle::new(std::mem::replace(&mut r.handle, UniquePtr::null()), &self.inner);
+        Ok(AddedTorrent::new(handle, r))
+    }
+
+    /// removes a torrent. Returns ``LTError::InvalidTorrentHandle`` if there
+    /// is no such torrent.
+    pub fn remove_torrent(&self, info_hash: &InfoHash, delete_files: bool) -> LTResult<()> {
+        Ok(self.inner.remove_torrent(&info_hash.to_string(), delete_files)?)
     }
 
     /// get the session stats
@@ -131,9 +362,25 @@ impl LTSession {
         }
     }
 
-    /// get the torrent handle by info hash
-    pub fn get_torrent_handle(&self, info_hash_str: &str) -> LTTorrentHandle {
-        LTTorrentHandle::new(self.inner.get_torrent_handle(info_hash_str))
+    /// how often ``post`` is posted, zero stops posting it, like
+    /// ``SessionBuilder::post_interval``. Stats which aren't posted don't
+    /// change, e.g. without ``StatsPost::TorrentUpdates`` the torrent status
+    /// is only updated on request. Fails with ``LTError::InvalidSessionConfig``
+    /// for an interval other than zero which is shorter than 1ms or longer
+    /// than ``u32::MAX`` ms.
+    pub fn set_post_interval(&self, post: StatsPost, interval: Duration) -> LTResult<()> {
+        let interval_ms = post_interval_ms(interval).map_err(|reason| invalid_config("post_interval", reason))?;
+        self.inner.set_post_interval(post.into(), interval_ms);
+        Ok(())
+    }
+
+    /// get the torrent handle by its v1 or v2 info-hash. Returns
+    /// ``LTError::InvalidTorrentHandle`` if there is no such torrent.
+    pub fn get_torrent_handle(&self, info_hash: &InfoHash) -> LTResult<LTTorrentHandle> {
+        Ok(LTTorrentHandle::new(
+            self.inner.get_torrent_handle(&info_hash.to_string())?,
+            &self.inner,
+        ))
     }
 
     pub fn pause(&self) {
@@ -148,6 +395,22 @@ impl LTSession {
         self.inner.is_paused()
     }
 
+    /// applies ``settings`` to the running session without restarting it,
+    /// settings that are not in the pack keep their current value.
+    pub fn apply_settings(&self, settings: &SettingsPack) -> LTResult<()> {
+        let err = self.inner.apply_settings(&settings.to_ffi());
+        if !err.name.is_empty() {
+            return Err(LTError::InvalidSetting(err.name, err.message));
+        }
+        Ok(())
+    }
+
+    /// the effective value of every setting of the session. Use
+    /// ``SettingsPack::diff_defaults`` to get the changed ones.
+    pub fn get_settings(&self) -> SettingsPack {
+        SettingsPack::from_ffi(self.inner.get_settings())
+    }
+
     /// Get the list of torrents in the session
     pub fn get_torrents(&self) -> Vec<TorrentInfo> {
         self.inner.get_torrents().into_iter().map(TorrentInfo::from).collect()
@@ -161,29 +424,117 @@ impl LTSession {
             .collect()
     }
 
-    pub fn get_logs(&mut self) -> Vec<Log> {
-        self.inner.pin_mut().get_logs().into_iter().map(Log::from).collect()
+    /// drains the log messages of the alerts which aren't handled by the
+    /// session and the diagnostics of the session, the oldest first
+    pub fn get_logs(&self) -> Vec<Log> {
+        self.inner.get_logs().into_iter().map(Log::from).collect()
+    }
+
+    /// drains the log messages matching ``filter``, e.g. those of a torrent.
+    /// The others are kept for later calls.
+    pub fn get_logs_filtered(&self, filter: &LogFilter) -> Vec<Log> {
+        self.inner
+            .get_logs_filtered(&filter.to_ffi())
+            .into_iter()
+            .map(Log::from)
+            .collect()
+    }
+
+    /// the number of log messages dropped because the log was full, see
+    /// ``SessionConfig::log_overflow``
+    pub fn dropped_logs(&self) -> u64 {
+        self.inner.dropped_logs()
+    }
+
+    /// subscribes to the typed alerts of the session.
+    ///
+    /// Every subscriber receives every alert posted after it subscribed. The
+    /// subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Alert> {
+        self.inner.alert_dispatcher.subscribe()
     }
 }
 
 unsafe impl Sync for LTSession {}
 unsafe impl Send for LTSession {}
 
+/// a torrent of the session.
+///
+/// The operations return ``LTError::InvalidTorrentHandle`` once the torrent
+/// is removed or the session is dropped, and ``LTError::Libtorrent`` with the
+/// error code if libtorrent fails.
 pub struct LTTorrentHandle {
     inner: UniquePtr<TorrentHandle>,
+    session: Weak<SessionInner>,
 }
 
+unsafe impl Sync for LTTorrentHandle {}
+unsafe impl Send for LTTorrentHandle {}
+
 impl LTTorrentHandle {
-    fn new(handle: UniquePtr<TorrentHandle>) -> LTTorrentHandle {
-        LTTorrentHandle { inner: handle }
+    fn new(handle: UniquePtr<TorrentHandle>, session: &Arc<SessionInner>) -> LTTorrentHandle {
+        LTTorrentHandle {
+            inner: handle,
+            session: Arc::downgrade(session),
+        }
     }
 
+    /// false once the torrent is removed or the session is dropped
     pub fn is_valid(&self) -> bool {
-        self.inner.is_valid()
+        self.call(|h| Ok(h.is_valid())).unwrap_or(false)
+    }
+
+    pub(crate) fn session(&self) -> LTResult<Arc<SessionInner>> {
+        self.session.upgrade().ok_or(LTError::InvalidTorrentHandle)
+    }
+
+    /// calls ``f`` with the handle while the session is kept alive
+    pub(crate) fn call<T, F>(&self, f: F) -> LTResult<T>
+    where
+        F: FnOnce(&TorrentHandle) -> Result<T, cxx::Exception>,
+    {
+        let _session = self.session()?;
+        Ok(f(&self.inner)?)
+    }
+
+    /// posts a query for this torrent and waits up to ``timeout`` for its
+    /// reply alert.
+    ///
+    /// ``what`` names the query in ``LTError::Timeout``.
+    fn wait_for_reply<P, R>(&self, post: P, is_reply: R, what: &'static str, timeout: Duration) -> LTResult<()>
+    where
+        P: FnOnce(&TorrentHandle) -> Result<(), cxx::Exception>,
+        R: Fn(&Alert) -> bool,
+    {
+        let info_hashes = self.info_hashes()?;
+        let deadline = Instant::now() + timeout;
+
+        // subscribe before posting, so the reply can't be missed. The
+        // receiver is disconnected if the session is dropped meanwhile.
+        let alerts = self.session()?.alert_dispatcher.subscribe();
+        self.call(post)?;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let alert = alerts.recv_timeout(remaining).map_err(|e| match e {
+                RecvTimeoutError::Timeout => LTError::Timeout(what),
+                RecvTimeoutError::Disconnected => LTError::InvalidTorrentHandle,
+            })?;
+
+            if !alert.info_hashes().is_some_and(|h| h.matches(&info_hashes)) {
+                continue;
+            }
+            if is_reply(&alert) {
+                return Ok(());
+            }
+            if let Alert::TorrentRemoved { .. } = alert {
+                return Err(LTError::InvalidTorrentHandle);
+            }
+        }
     }
 
-    pub fn add_tracker(&self, tracker_url: &str, tier: u8) {
-        self.inner.add_tracker(tracker_url, tier);
+    pub fn add_tracker(&self, tracker_url: &str, tier: u8) -> LTResult<()> {
+        self.call(|h| h.add_tracker(tracker_url, tier))
     }
 
     /// ``scrape_tracker()`` will send a scrape request to a tracker. By
@@ -197,8 +548,8 @@ impl LTTorrentHandle {
     /// ``num_incomplete`` fields in the torrent_status struct once it
     /// completes. When it completes, it will generate a scrape_reply_alert.
     /// If it fails, it will generate a scrape_failed_alert.
-    pub fn scrape_tracker(&self) {
-        self.inner.scrape_tracker();
+    pub fn scrape_tracker(&self) -> LTResult<()> {
+        self.call(|h| h.scrape_tracker())
     }
 
     /// ``force_recheck`` puts the torrent back in a state where it assumes to
@@ -209,8 +560,8 @@ impl LTTorrentHandle {
     /// will start connecting to peers again, as normal.
     /// The torrent will be placed last in queue, i.e. its queue position
     /// will be the highest of all torrents in the session.
-    pub fn force_recheck(&self) {
-        self.inner.force_recheck();
+    pub fn force_recheck(&self) -> LTResult<()> {
+        self.call(|h| h.force_recheck())
     }
 
     /// ``force_reannounce()`` will force this torrent to do another tracker
@@ -233,18 +584,18 @@ impl LTTorrentHandle {
     ///
     /// ``force_lsd_announce`` will announce the torrent on LSD
     /// immediately.
-    pub fn force_reannounce(&self) {
-        self.inner.force_reannounce();
+    pub fn force_reannounce(&self) -> LTResult<()> {
+        self.call(|h| h.force_reannounce())
     }
-    pub fn force_dht_announce(&self) {
-        self.inner.force_dht_announce();
+    pub fn force_dht_announce(&self) -> LTResult<()> {
+        self.call(|h| h.force_dht_announce())
     }
-    pub fn force_lsd_announce(&self) {
-        self.inner.force_lsd_announce();
+    pub fn force_lsd_announce(&self) -> LTResult<()> {
+        self.call(|h| h.force_lsd_announce())
     }
 
-    pub fn clear_error(&self) {
-        self.inner.clear_error();
+    pub fn clear_error(&self) -> LTResult<()> {
+        self.call(|h| h.clear_error())
     }
 
     /// ``set_upload_limit`` will limit the upload bandwidth used by this
@@ -261,24 +612,24 @@ impl LTTorrentHandle {
     /// setting, for upload and download, respectively.
     ///
     /// Local peers are not rate limited by default. see peer-classes_.
-    pub fn set_upload_limit(&self, limit: i32) {
-        self.inner.set_upload_limit(limit);
+    pub fn set_upload_limit(&self, limit: i32) -> LTResult<()> {
+        self.call(|h| h.set_upload_limit(limit))
     }
-    pub fn upload_limit(&self) -> i32 {
-        self.inner.upload_limit()
+    pub fn upload_limit(&self) -> LTResult<i32> {
+        self.call(|h| h.upload_limit())
     }
-    pub fn set_download_limit(&self, limit: i32) {
-        self.inner.set_download_limit(limit);
+    pub fn set_download_limit(&self, limit: i32) -> LTResult<()> {
+        self.call(|h| h.set_download_limit(limit))
     }
-    pub fn download_limit(&self) -> i32 {
-        self.inner.download_limit()
+    pub fn download_limit(&self) -> LTResult<i32> {
+        self.call(|h| h.download_limit())
     }
 
     /// This will disconnect all peers and clear the peer list for this
     /// torrent. New peers will have to be acquired before resuming, from
     /// trackers, DHT or local service discovery, for example.
-    pub fn clear_peers(&self) {
-        self.inner.clear_peers();
+    pub fn clear_peers(&self) -> LTResult<()> {
+        self.call(|h| h.clear_peers())
     }
 
     /// ``set_max_uploads()`` sets the maximum number of peers that's unchoked
@@ -288,11 +639,11 @@ impl LTTorrentHandle {
     /// settings_pack.
     ///
     /// ``max_uploads()`` returns the current settings.
-    pub fn set_max_uploads(&self, max_uploads: i32) {
-        self.inner.set_max_uploads(max_uploads);
+    pub fn set_max_uploads(&self, max_uploads: i32) -> LTResult<()> {
+        self.call(|h| h.set_max_uploads(max_uploads))
     }
-    pub fn max_uploads(&self) -> i32 {
-        self.inner.max_uploads()
+    pub fn max_uploads(&self) -> LTResult<i32> {
+        self.call(|h| h.max_uploads())
     }
 
     /// ``set_max_connections()`` sets the maximum number of connection this
@@ -304,11 +655,11 @@ impl LTTorrentHandle {
     /// ``connections_limit`` in settings_pack.
     ///
     /// ``max_connections()`` returns the current settings.
-    pub fn set_max_connections(&self, max_connections: i32) {
-        self.inner.set_max_connections(max_connections);
+    pub fn set_max_connections(&self, max_connections: i32) -> LTResult<()> {
+        self.call(|h| h.set_max_connections(max_connections))
     }
-    pub fn max_connections(&self) -> i32 {
-        self.inner.max_connections()
+    pub fn max_connections(&self) -> LTResult<i32> {
+        self.call(|h| h.max_connections())
     }
 
     /// sets and gets the torrent state flags. See torrent_flags_t.
@@ -324,8 +675,8 @@ impl LTTorrentHandle {
     /// The `seed_mode` flag is special, it can only be cleared once the
     /// torrent has been added, and it can only be set as part of the
     /// add_torrent_params flags, when adding the torrent.
-    pub fn flags(&self) -> u64 {
-        self.inner.flags()
+    pub fn flags(&self) -> LTResult<u64> {
+        self.call(|h| h.flags())
     }
 
     /// sets and gets the torrent state flags. See torrent_flags_t.
@@ -343,8 +694,8 @@ impl LTTorrentHandle {
     /// add_torrent_params flags, when adding the torrent.
     ///
     /// flags: TorrentFlags
-    pub fn set_flags(&self, flags: u64) {
-        self.inner.set_flags(flags);
+    pub fn set_flags(&self, flags: u64) -> LTResult<()> {
+        self.call(|h| h.set_flags(flags))
     }
 
     /// sets and gets the torrent state flags. See torrent_flags_t.
@@ -362,8 +713,8 @@ impl LTTorrentHandle {
     /// add_torrent_params flags, when adding the torrent.
     ///
     /// flags: TorrentFlags
-    pub fn set_flags_with_mask(&self, flags: u64, mask: u64) {
-        self.inner.set_flags_with_mask(flags, mask);
+    pub fn set_flags_with_mask(&self, flags: u64, mask: u64) -> LTResult<()> {
+        self.call(|h| h.set_flags_with_mask(flags, mask))
     }
 
     /// sets and gets the torrent state flags. See torrent_flags_t.
@@ -381,25 +732,17 @@ impl LTTorrentHandle {
     /// add_torrent_params flags, when adding the torrent.
     ///
     /// flags: TorrentFlags
-    pub fn unset_flags(&self, flags: u64) {
-        self.inner.unset_flags(flags);
+    pub fn unset_flags(&self, flags: u64) -> LTResult<()> {
+        self.call(|h| h.unset_flags(flags))
     }
 
-    pub fn pause(&self) {
-        if !self.is_valid() {
-            return;
-        }
-
-        self.unset_flags(TorrentFlags::auto_managed.bits());
-        self.inner.pause(PauseFlags::graceful_pause.bits());
+    pub fn pause(&self) -> LTResult<()> {
+        self.unset_flags(TorrentFlags::auto_managed.bits())?;
+        self.call(|h| h.pause(PauseFlags::graceful_pause.bits()))
     }
 
-    pub fn resume(&self) {
-        if !self.is_valid() {
-            return;
-        }
-
-        self.set_flags(TorrentFlags::auto_managed.bits());
+    pub fn resume(&self) -> LTResult<()> {
+        self.set_flags(TorrentFlags::auto_managed.bits())
     }
 
     /// ``index`` must be in the range [0, number_of_files).
@@ -423,8 +766,10 @@ impl LTTorrentHandle {
     /// again for those pieces.
     ///
     /// You cannot set the file priorities on a torrent that does not yet have
-    /// metadata or a torrent that is a seed. ``file_priority(int, int)`` and
-    /// prioritize_files() are both no-ops for such torrents.
+    /// metadata, ``set_file_priority``, ``get_file_priority`` and
+    /// ``set_prioritize_files`` return ``LTError::NoMetadata`` for such
+    /// torrents and ``LTError::InvalidFileIndex`` for an ``index`` out of
+    /// range. Setting them on a seed is a no-op.
     ///
     /// Since changing file priorities may involve disk operations (of moving
     /// files in- and out of the part file), the internal accounting of file
@@ -440,48 +785,116 @@ impl LTTorrentHandle {
     /// Moving data from a file into the part file is currently not
     /// supported. If a file has its priority set to 0 *after* it has already
     /// been created, it will not be moved into the partfile.
-    pub fn set_file_priority(&self, index: i32, priority: u8) {
-        self.inner.set_file_priority(index, priority);
+    pub fn set_file_priority(&self, index: i32, priority: u8) -> LTResult<()> {
+        self.call(|h| h.set_file_priority(index, priority))
+    }
+    pub fn get_file_priority(&self, index: i32) -> LTResult<u8> {
+        self.call(|h| h.get_file_priority(index))
+    }
+    pub fn set_prioritize_files(&self, files: &[u8]) -> LTResult<()> {
+        self.call(|h| h.set_prioritize_files(files))
     }
-    pub fn get_file_priority(&self, index: i32) -> u8 {
-        self.inner.get_file_priority(index)
+    pub fn get_file_priorities(&self) -> LTResult<Vec<u8>> {
+        self.call(|h| h.get_file_priorities())
     }
-    pub fn set_prioritize_files(&self, files: &[u8]) {
-        self.inner.set_prioritize_files(files);
+
+    /// the metadata of the torrent, ``LTError::NoMetadata`` for a magnet link
+    /// whose metadata wasn't received yet. The .torrent file isn't included,
+    /// see ``get_torrent_file``.
+    pub fn get_torrent_info(&self) -> LTResult<TorrentInfo> {
+        Ok(self.call(|h| h.get_torrent_info())?.into())
     }
-    pub fn get_file_priorities(&self) -> Vec<u8> {
-        self.inner.get_file_priorities()
+
+    /// the bencoded .torrent file of the torrent, e.g. to add it to another
+    /// session with ``LTSession::add_torrent_bytes``. It's written on every
+    /// call.
+    pub fn get_torrent_file(&self) -> LTResult<Vec<u8>> {
+        self.call(|h| h.get_torrent_file())
     }
 
-    pub fn get_torrent_info(&self) -> TorrentInfo {
-        self.inner.get_torrent_info().into()
+    /// requests the peers of the torrent and waits up to ``timeout`` for
+    /// libtorrent to reply.
+    ///
+    /// Returns ``LTError::Timeout`` if the reply doesn't arrive in time and
+    /// ``LTError::InvalidTorrentHandle`` if the torrent is removed meanwhile.
+    pub fn get_peers(&self, timeout: Duration) -> LTResult<Vec<PeerInfo>> {
+        self.wait_for_reply(
+            |h| h.post_peer_info(),
+            |a| matches!(a, Alert::PeerInfoReceived { .. }),
+            "peer info",
+            timeout,
+        )?;
+
+        Ok(self.call(|h| Ok(h.peers()))?.into_iter().map(PeerInfo::from).collect())
     }
 
-    pub fn get_peers(&self) -> Vec<PeerInfo> {
-        self.inner.get_peers().into_iter().map(PeerInfo::from).collect()
+    /// requests the file progress of the torrent and waits up to ``timeout``
+    /// for libtorrent to reply, see ``get_peers``.
+    pub fn get_file_progress(&self, piece_granularity: bool, timeout: Duration) -> LTResult<Vec<i64>> {
+        self.wait_for_reply(
+            |h| h.post_file_progress(piece_granularity),
+            |a| matches!(a, Alert::FileProgressReceived { .. }),
+            "file progress",
+            timeout,
+        )?;
+
+        self.call(|h| Ok(h.file_progress()))
     }
 
-    pub fn get_file_progress(&self, piece_granularity: bool) -> Vec<i64> {
-        self.inner.get_file_progress(piece_granularity)
+    /// requests the download queue of the torrent and waits up to
+    /// ``timeout`` for libtorrent to reply, see ``get_peers``.
+    pub fn get_piece_info(&self, timeout: Duration) -> LTResult<PieceInfo> {
+        self.wait_for_reply(
+            |h| h.post_download_queue(),
+            |a| matches!(a, Alert::PieceInfoReceived { .. }),
+            "piece info",
+            timeout,
+        )?;
+
+        Ok(self.call(|h| Ok(h.piece_info()))?.into())
     }
 
-    pub fn get_piece_info(&self) -> PieceInfo {
-        self.inner.get_piece_info().into()
+    /// requests the piece availability of the torrent and waits up to
+    /// ``timeout`` for libtorrent to reply, see ``get_peers``.
+    pub fn get_piece_availability(&self, timeout: Duration) -> LTResult<Vec<i32>> {
+        self.wait_for_reply(
+            |h| h.post_piece_availability(),
+            |a| matches!(a, Alert::PieceAvailabilityReceived { .. }),
+            "piece availability",
+            timeout,
+        )?;
+
+        self.call(|h| Ok(h.piece_availability()))
     }
 
-    pub fn get_piece_availability(&self) -> Vec<i32> {
-        self.inner.get_piece_availability()
+    /// requests the trackers of the torrent and waits up to ``timeout`` for
+    /// libtorrent to reply, see ``get_peers``.
+    pub fn get_trackers(&self, timeout: Duration) -> LTResult<Vec<AnnounceEntry>> {
+        self.wait_for_reply(
+            |h| h.post_trackers(),
+            |a| matches!(a, Alert::TrackerListReceived { .. }),
+            "tracker list",
+            timeout,
+        )?;
+
+        Ok(self
+            .call(|h| Ok(h.trackers()))?
+            .into_iter()
+            .map(AnnounceEntry::from)
+            .collect())
     }
 
-    pub fn get_trackers(&self) -> Vec<AnnounceEntry> {
-        self.inner.get_trackers().into_iter().map(AnnounceEntry::from).collect()
+    pub fn get_torrent_status(&self) -> LTResult<TorrentStatus> {
+        Ok(self.call(|h| h.get_torrent_status())?.into())
     }
 
-    pub fn get_torrent_status(&self) -> TorrentStatus {
-        self.inner.get_torrent_status().into()
+    pub fn make_magnet_uri(&self) -> LTResult<String> {
+        self.call(|h| h.make_magnet_uri())
     }
 
-    pub fn make_magnet_uri(&self) -> String {
-        self.inner.make_magnet_uri()
+    /// the v1 and v2 info-hashes of the torrent. A torrent added by a v1
+    /// magnet link gets its v2 info-hash once the metadata is received.
+    pub fn info_hashes(&self) -> LTResult<InfoHashes> {
+        Ok(self.call(|h| h.info_hashes())?.into())
     }
 }
@@ -1,17 +1,182 @@
-use libtorrent_rasterbar_sys::ffi;
-use serde::Serialize;
+use std::net::SocketAddr;
 
-#[derive(Serialize, Debug)]
+use libtorrent_rasterbar_sys::{ffi, flags::AlertCategory};
+use serde::{Deserialize, Serialize};
+
+use crate::InfoHash;
+
+/// a log message of a session, of an alert which isn't handled by the
+/// session or of a diagnostic of the wrapper
+#[derive(Serialize, Debug, Clone)]
 pub struct Log {
     pub message: String,
+    /// milliseconds
     pub timestamp: i64,
+    /// the type of the alert, e.g. ``torrent_log``, ``None`` for a
+    /// diagnostic of the wrapper
+    pub alert_type: Option<String>,
+    /// the categories of the alert, empty for a diagnostic
+    pub category: AlertCategory,
+    pub level: LogLevel,
+    pub target: LogTarget,
+    /// the v1 info-hash of the torrent, or the v2 one if it has none
+    pub info_hash: Option<InfoHash>,
+    /// ``None`` if there is no torrent or its name isn't known yet
+    pub torrent_name: Option<String>,
+    pub peer: Option<SocketAddr>,
+}
+
+/// what happens to a log message when the log of a session is full, see
+/// ``SessionConfig::log_overflow``
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogOverflowPolicy {
+    /// the oldest message is dropped, the log keeps the latest messages
+    #[default]
+    DropOldest,
+    /// the new message is dropped, the log keeps the first messages
+    DropNewest,
+    /// the alert thread waits until ``LTSession::get_logs`` makes room. No
+    /// alerts are handled meanwhile, so the logs must be drained regularly.
+    /// Torrent handle queries don't wait for it, and the messages of the
+    /// wrapper logged from other threads are dropped like with
+    /// ``DropNewest``. A session which shuts down stops waiting, while
+    /// ``LTSession::save_all_resume_data`` times out.
+    Block,
+}
+
+impl From<LogOverflowPolicy> for ffi::LogOverflowPolicy {
+    fn from(policy: LogOverflowPolicy) -> Self {
+        match policy {
+            LogOverflowPolicy::DropOldest => ffi::LogOverflowPolicy::DropOldest,
+            LogOverflowPolicy::DropNewest => ffi::LogOverflowPolicy::DropNewest,
+            LogOverflowPolicy::Block => ffi::LogOverflowPolicy::Block,
+        }
+    }
 }
 
 impl From<ffi::Log> for Log {
     fn from(log: ffi::Log) -> Log {
+        let non_empty = |s: String| (!s.is_empty()).then_some(s);
         Log {
             message: log.message,
             timestamp: log.timestamp,
+            alert_type: non_empty(log.alert_type),
+            category: AlertCategory::from_bits_retain(log.category),
+            level: log.level.into(),
+            target: log.target.into(),
+            info_hash: log.info_hash.parse().ok(),
+            torrent_name: non_empty(log.torrent_name),
+            peer: log.peer.parse().ok(),
+        }
+    }
+}
+
+/// the severity of a log message, more verbose levels compare greater
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    #[default]
+    Trace,
+}
+
+impl From<ffi::LogLevel> for LogLevel {
+    fn from(level: ffi::LogLevel) -> Self {
+        match level {
+            ffi::LogLevel::Error => LogLevel::Error,
+            ffi::LogLevel::Warn => LogLevel::Warn,
+            ffi::LogLevel::Info => LogLevel::Info,
+            ffi::LogLevel::Debug => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+impl From<LogLevel> for ffi::LogLevel {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => ffi::LogLevel::Error,
+            LogLevel::Warn => ffi::LogLevel::Warn,
+            LogLevel::Info => ffi::LogLevel::Info,
+            LogLevel::Debug => ffi::LogLevel::Debug,
+            LogLevel::Trace => ffi::LogLevel::Trace,
+        }
+    }
+}
+
+/// where a log message comes from, derived from the type and category of
+/// the alert
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTarget {
+    /// a diagnostic of the session wrapper, e.g. a failed resume data save
+    Wrapper,
+    Session,
+    Torrent,
+    Peer,
+    Dht,
+    Tracker,
+    Storage,
+    PortMapping,
+}
+
+impl From<ffi::LogTarget> for LogTarget {
+    fn from(target: ffi::LogTarget) -> Self {
+        match target {
+            ffi::LogTarget::Session => LogTarget::Session,
+            ffi::LogTarget::Torrent => LogTarget::Torrent,
+            ffi::LogTarget::Peer => LogTarget::Peer,
+            ffi::LogTarget::Dht => LogTarget::Dht,
+            ffi::LogTarget::Tracker => LogTarget::Tracker,
+            ffi::LogTarget::Storage => LogTarget::Storage,
+            ffi::LogTarget::PortMapping => LogTarget::PortMapping,
+            _ => LogTarget::Wrapper,
+        }
+    }
+}
+
+/// selects the log messages drained by ``LTSession::get_logs_filtered``,
+/// by default all of them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    info_hash: Option<InfoHash>,
+    categories: AlertCategory,
+    max_level: LogLevel,
+}
+
+impl LogFilter {
+    pub fn new() -> LogFilter {
+        LogFilter::default()
+    }
+
+    /// only the messages of the torrent with the v1 or v2 ``info_hash``
+    pub fn torrent(mut self, info_hash: InfoHash) -> Self {
+        self.info_hash = Some(info_hash);
+        self
+    }
+
+    /// only the messages of alerts in any of ``categories``. Diagnostics of
+    /// the wrapper have no category and are left out.
+    pub fn categories(mut self, categories: AlertCategory) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    /// only the messages at ``level`` or more severe
+    pub fn max_level(mut self, level: LogLevel) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    pub(crate) fn to_ffi(&self) -> ffi::LogFilter {
+        ffi::LogFilter {
+            info_hash: self.info_hash.map(|h| h.to_string()).unwrap_or_default(),
+            categories: self.categories.bits(),
+            max_level: self.max_level.into(),
         }
     }
 }
@@ -0,0 +1,346 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use libtorrent_rasterbar_sys::{
+    ResumeStore,
+    ffi::{self, ResumeEntry},
+};
+use serde::Serialize;
+
+use crate::{InfoHash, InfoHashes, LTError, LTResult};
+
+/// The default ``ResumeStore``, used for the ``resume_dir`` and
+/// ``torrent_dir`` of a ``SessionConfig``.
+///
+/// The resume data of a torrent is kept in ``<resume_dir>/<info-hash>.resume``
+/// and, if there is a torrent directory, a copy of its .torrent file in
+/// ``<torrent_dir>/<info-hash>.torrent``.
+///
+/// Files are written to a temporary file which is then renamed, so a crash
+/// never leaves a truncated file behind. Every write has its own temporary
+/// file, so concurrent saves of a torrent don't clobber each other. The
+/// previous resume data is kept in ``<info-hash>.resume.bak`` and used if the
+/// current one can't be read.
+#[derive(Debug, Clone)]
+pub struct DirResumeStore {
+    resume_dir: PathBuf,
+    torrent_dir: Option<PathBuf>,
+}
+
+impl DirResumeStore {
+    /// a store in ``resume_dir``, which is created if it doesn't exist
+    pub fn new(resume_dir: impl Into<PathBuf>) -> LTResult<DirResumeStore> {
+        let resume_dir = resume_dir.into();
+        create_dir(&resume_dir)?;
+        Ok(DirResumeStore {
+            resume_dir,
+            torrent_dir: None,
+        })
+    }
+
+    /// also keeps a copy of the .torrent files in ``torrent_dir``, which is
+    /// created if it doesn't exist
+    pub fn with_torrent_dir(mut self, torrent_dir: impl Into<PathBuf>) -> LTResult<DirResumeStore> {
+        let torrent_dir = torrent_dir.into();
+        create_dir(&torrent_dir)?;
+        self.torrent_dir = Some(torrent_dir);
+        Ok(self)
+    }
+
+    pub fn resume_dir(&self) -> &Path {
+        &self.resume_dir
+    }
+
+    pub fn torrent_dir(&self) -> Option<&Path> {
+        self.torrent_dir.as_deref()
+    }
+
+    fn resume_file(&self, info_hash: &str) -> PathBuf {
+        self.resume_dir.join(format!("{info_hash}.resume"))
+    }
+
+    fn backup_file(&self, info_hash: &str) -> PathBuf {
+        self.resume_dir.join(format!("{info_hash}.resume.bak"))
+    }
+
+    fn torrent_file(&self, info_hash: &str) -> Option<PathBuf> {
+        self.torrent_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{info_hash}.torrent")))
+    }
+}
+
+impl ResumeStore for DirResumeStore {
+    fn load_all(&self) -> io::Result<Vec<ResumeEntry>> {
+        // a crash between renaming the resume file to the backup and the new
+        // one to the resume file leaves only the backup
+        let mut info_hashes = BTreeSet::new();
+        for dir_entry in fs::read_dir(&self.resume_dir)? {
+            let path = dir_entry?.path();
+            let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if let Some(info_hash) = name
+                .strip_suffix(".resume")
+                .or_else(|| name.strip_suffix(".resume.bak"))
+            {
+                info_hashes.insert(info_hash.to_string());
+            }
+        }
+
+        let mut entries = Vec::new();
+        for info_hash in info_hashes {
+            // a file which can't be read is like a missing one, the session
+            // then falls back to the backup or the .torrent file
+            let resume_data = fs::read(self.resume_file(&info_hash)).unwrap_or_default();
+            let backup = fs::read(self.backup_file(&info_hash)).unwrap_or_default();
+            let torrent = match self.torrent_file(&info_hash) {
+                Some(file) => fs::read(file).unwrap_or_default(),
+                None => Vec::new(),
+            };
+
+            entries.push(ResumeEntry {
+                info_hash,
+                resume_data,
+                backup,
+                torrent,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn load(&self, info_hash: &str) -> io::Result<Option<Vec<u8>>> {
+        match read_if_exists(&self.resume_file(info_hash))? {
+            Some(resume_data) => Ok(Some(resume_data)),
+            None => read_if_exists(&self.backup_file(info_hash)),
+        }
+    }
+
+    fn save(&self, info_hash: &str, resume_data: &[u8], torrent: Option<&[u8]>) -> io::Result<()> {
+        let resume_file = self.resume_file(info_hash);
+        let tmp_file = write_tmp(&resume_file, resume_data)?;
+        // a concurrent save may have moved it to the backup already
+        match fs::rename(&resume_file, self.backup_file(info_hash)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                let _ = fs::remove_file(&tmp_file);
+                return Err(e);
+            }
+            _ => {}
+        }
+        fs::rename(tmp_file, resume_file)?;
+        sync_dir(&self.resume_dir)?;
+
+        if let (Some(dir), Some(file), Some(torrent)) = (&self.torrent_dir, self.torrent_file(info_hash), torrent)
+            && !file.exists()
+        {
+            let tmp_file = write_tmp(&file, torrent)?;
+            fs::rename(tmp_file, file)?;
+            sync_dir(dir)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, info_hash: &str) -> io::Result<()> {
+        remove_if_exists(&self.resume_file(info_hash))?;
+        remove_if_exists(&self.backup_file(info_hash))?;
+        if let Some(file) = self.torrent_file(info_hash) {
+            remove_if_exists(&file)?;
+        }
+        Ok(())
+    }
+}
+
+fn create_dir(dir: &Path) -> LTResult<()> {
+    fs::create_dir_all(dir).map_err(|e| LTError::Io(dir.to_path_buf(), e))
+}
+
+/// writes ``data`` to ``<path>.<pid>.<n>.tmp`` and syncs it to disk, so it
+/// can be renamed to ``path``. The name is unique to the write, another
+/// thread or process saving the same file uses its own.
+fn write_tmp(path: &Path, data: &[u8]) -> io::Result<PathBuf> {
+    static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut tmp_file = path.as_os_str().to_owned();
+    let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    tmp_file.push(format!(".{}.{n}.tmp", std::process::id()));
+    let tmp_file = PathBuf::from(tmp_file);
+
+    let write = || -> io::Result<()> {
+        let mut file = File::create(&tmp_file)?;
+        file.write_all(data)?;
+        file.sync_all()
+    };
+    if let Err(e) = write() {
+        let _ = fs::remove_file(&tmp_file);
+        return Err(e);
+    }
+    Ok(tmp_file)
+}
+
+/// syncs the entries of ``dir`` to disk, so a rename in it survives a crash
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// directories can't be opened to sync them on windows
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// how a torrent whose resume data couldn't be read was added back to the
+/// session, see ``LTSession::resume_load_failures``
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeRecovery {
+    /// it wasn't, the torrent is missing from the session but still in the
+    /// ``ResumeStore``
+    NotRecovered,
+    /// from the previous generation of the resume data, the progress since
+    /// then is downloaded again
+    Backup,
+    /// from the stored .torrent file into ``SessionConfig::recovery_save_path``,
+    /// the files which are there are checked
+    TorrentFile,
+}
+
+/// a torrent of the ``ResumeStore`` whose resume data couldn't be read when
+/// the session was created
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResumeLoadFailure {
+    /// the hex of the best info-hash, the key of the torrent in the store
+    pub info_hash: String,
+    /// why the resume data couldn't be read
+    pub error: String,
+    pub recovery: ResumeRecovery,
+}
+
+impl From<ffi::ResumeLoadFailure> for ResumeLoadFailure {
+    fn from(f: ffi::ResumeLoadFailure) -> Self {
+        let recovery = match f.recovery {
+            ffi::ResumeRecovery::Backup => ResumeRecovery::Backup,
+            ffi::ResumeRecovery::TorrentFile => ResumeRecovery::TorrentFile,
+            _ => ResumeRecovery::NotRecovered,
+        };
+        Self {
+            info_hash: f.info_hash,
+            error: f.error,
+            recovery,
+        }
+    }
+}
+
+/// a torrent of the ``ResumeStore`` whose save path didn't exist when the
+/// session was created, e.g. because it's on a drive which wasn't mounted
+/// yet. It stays in the store, see ``LTSession::orphaned_torrents``.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OrphanedTorrent {
+    pub info_hashes: InfoHashes,
+    /// empty for a magnet link without metadata
+    pub name: String,
+    /// the save path which doesn't exist
+    pub save_path: String,
+}
+
+impl From<ffi::OrphanedTorrent> for OrphanedTorrent {
+    fn from(o: ffi::OrphanedTorrent) -> Self {
+        Self {
+            info_hashes: o.info_hashes.into(),
+            name: o.name,
+            save_path: o.save_path,
+        }
+    }
+}
+
+/// a torrent whose resume data couldn't be saved, see
+/// ``LTSession::save_all_resume_data``
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveResumeFailure {
+    pub info_hash: InfoHash,
+    pub error: String,
+}
+
+/// the outcome of ``LTSession::save_all_resume_data``
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveResumeSummary {
+    /// the torrents whose resume data was saved to the ``ResumeStore``
+    pub saved: Vec<InfoHash>,
+    /// the torrents whose resume data libtorrent or the store failed to save
+    pub failed: Vec<SaveResumeFailure>,
+    /// the torrents whose resume data didn't arrive before the timeout
+    pub timed_out: Vec<InfoHash>,
+}
+
+impl SaveResumeSummary {
+    /// true if the resume data of every torrent was saved
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.timed_out.is_empty()
+    }
+}
+
+impl From<ffi::SaveResumeSummary> for SaveResumeSummary {
+    fn from(s: ffi::SaveResumeSummary) -> Self {
+        // the session only reports valid info-hashes
+        let parse =
+            |info_hashes: Vec<String>| -> Vec<InfoHash> { info_hashes.iter().filter_map(|s| s.parse().ok()).collect() };
+        Self {
+            saved: parse(s.saved),
+            failed: s
+                .failed
+                .into_iter()
+                .filter_map(|f| {
+                    Some(SaveResumeFailure {
+                        info_hash: f.info_hash.parse().ok()?,
+                        error: f.error,
+                    })
+                })
+                .collect(),
+            timed_out: parse(s.timed_out),
+        }
+    }
+}
+
+/// the outcome of ``LTSession::shutdown``
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    pub resume_data: SaveResumeSummary,
+    /// why the session state couldn't be saved
+    pub state_error: Option<String>,
+}
+
+impl ShutdownReport {
+    /// true if the resume data of every torrent and the session state were
+    /// saved
+    pub fn is_clean(&self) -> bool {
+        self.resume_data.is_complete() && self.state_error.is_none()
+    }
+}
+
+impl From<ffi::ShutdownReport> for ShutdownReport {
+    fn from(r: ffi::ShutdownReport) -> Self {
+        Self {
+            resume_data: r.resume_data.into(),
+            state_error: (!r.state_error.is_empty()).then_some(r.state_error),
+        }
+    }
+}
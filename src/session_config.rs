@@ -0,0 +1,382 @@
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use libtorrent_rasterbar_sys::flags::SaveStateFlags;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    LTError, LTResult, LTSession, LogOverflowPolicy, ResumeStore, SettingValue, SettingsPack, SettingsPreset,
+    SettingsProfile, StatsPost,
+};
+
+/// the configuration of a session, see ``LTSession::from_config``. Build it
+/// with ``SessionBuilder`` or deserialize it, e.g. from a section of a
+/// service config:
+///
+/// ```toml
+/// save_state_flags = "save_dht_state"
+/// session_state_path = "/var/lib/app/session.state"
+/// resume_dir = "/var/lib/app/resume"
+/// torrent_dir = "/var/lib/app/torrents"
+/// log_capacity = 1000
+/// log_overflow = "drop_oldest"
+/// alert_poll_interval_ms = 500
+/// session_stats_interval_ms = 5000
+/// torrent_updates_interval_ms = 100
+/// dht_stats_interval_ms = 0
+/// autosave_interval_ms = 300000
+///
+/// [profile]
+/// preset = "high_performance_seed"
+///
+/// [profile.settings]
+/// user_agent = "app/1.0"
+/// alert_mask = "error,status,storage,tracker"
+/// ```
+///
+/// Everything is optional, the defaults are those of ``SessionConfig::default``.
+/// Without ``session_state_path``, ``resume_dir`` and a ``ResumeStore`` the
+/// session keeps nothing on disk, see ``LTSession::is_ephemeral``.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// the session settings, a preset and the settings applied over it
+    pub profile: SettingsProfile,
+
+    /// what is saved to and loaded from ``session_state_path``. The default
+    /// is ``save_dht_state``, the settings come from ``profile``.
+    pub save_state_flags: SaveStateFlags,
+
+    /// where the session state is saved when the session is dropped and
+    /// loaded from when it's created. ``None`` doesn't save it.
+    pub session_state_path: Option<PathBuf>,
+
+    /// the directory of the resume data of the torrents, which are added
+    /// back when the session is created, see ``DirResumeStore``. ``None``
+    /// doesn't save resume data, unless the session is created with a
+    /// ``ResumeStore``.
+    pub resume_dir: Option<PathBuf>,
+
+    /// the directory where a copy of the .torrent file of every torrent is
+    /// kept. ``None`` doesn't keep copies. Requires ``resume_dir``.
+    pub torrent_dir: Option<PathBuf>,
+
+    /// where a torrent is added from its stored .torrent file if neither its
+    /// resume data nor the backup of it can be read. Its files are checked,
+    /// so with the old save path nothing is downloaded again. ``None``
+    /// leaves such torrents out, see ``LTSession::resume_load_failures``.
+    pub recovery_save_path: Option<PathBuf>,
+
+    /// the number of log messages kept for ``LTSession::get_logs``
+    pub log_capacity: u32,
+
+    /// what happens to a log message when ``log_capacity`` messages weren't
+    /// drained yet
+    pub log_overflow: LogOverflowPolicy,
+
+    /// the longest the alert thread waits for an alert, it handles alerts
+    /// as soon as they're posted
+    #[serde(rename = "alert_poll_interval_ms", with = "millis")]
+    pub alert_poll_interval: Duration,
+
+    /// how often the session stats are posted, zero doesn't post them
+    #[serde(rename = "session_stats_interval_ms", with = "millis")]
+    pub session_stats_interval: Duration,
+
+    /// how often the status of the torrents is updated, zero doesn't update
+    /// it
+    #[serde(rename = "torrent_updates_interval_ms", with = "millis")]
+    pub torrent_updates_interval: Duration,
+
+    /// how often the dht stats are posted, zero doesn't post them
+    #[serde(rename = "dht_stats_interval_ms", with = "millis")]
+    pub dht_stats_interval: Duration,
+
+    /// how often the resume data of the torrents which changed since it was
+    /// last saved is flushed to the ``ResumeStore``, along with the session
+    /// state. ``None`` only saves resume data on libtorrent events (e.g. a
+    /// finished or paused torrent) and when the session is dropped.
+    #[serde(
+        rename = "autosave_interval_ms",
+        with = "opt_millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub autosave_interval: Option<Duration>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            profile: SettingsProfile::default(),
+            save_state_flags: SaveStateFlags::save_dht_state,
+            session_state_path: None,
+            resume_dir: None,
+            torrent_dir: None,
+            recovery_save_path: None,
+            log_capacity: 1000,
+            log_overflow: LogOverflowPolicy::DropOldest,
+            alert_poll_interval: Duration::from_millis(500),
+            session_stats_interval: Duration::from_millis(500),
+            torrent_updates_interval: Duration::from_millis(500),
+            dht_stats_interval: Duration::from_millis(500),
+            autosave_interval: None,
+        }
+    }
+}
+
+impl SessionConfig {
+    pub(crate) fn validate(&self) -> LTResult<()> {
+        if self.log_capacity == 0 {
+            return Err(invalid_config("log_capacity", "0".to_string()));
+        }
+
+        let ms = self.alert_poll_interval.as_millis();
+        if ms == 0 || ms > u32::MAX as u128 {
+            return Err(invalid_config(
+                "alert_poll_interval",
+                format!("{:?} is not between 1ms and {}ms", self.alert_poll_interval, u32::MAX),
+            ));
+        }
+
+        for (param, interval) in [
+            ("session_stats_interval", self.session_stats_interval),
+            ("torrent_updates_interval", self.torrent_updates_interval),
+            ("dht_stats_interval", self.dht_stats_interval),
+        ] {
+            post_interval_ms(interval).map_err(|reason| invalid_config(param, reason))?;
+        }
+
+        if let Some(interval) = self.autosave_interval {
+            let ms = interval.as_millis();
+            if ms == 0 || ms > u32::MAX as u128 {
+                return Err(invalid_config(
+                    "autosave_interval",
+                    format!("{interval:?} is not between 1ms and {}ms", u32::MAX),
+                ));
+            }
+        }
+
+        for (param, path) in [
+            ("session_state_path", &self.session_state_path),
+            ("resume_dir", &self.resume_dir),
+            ("torrent_dir", &self.torrent_dir),
+        ] {
+            path_str(param, path)?;
+        }
+
+        path_str("recovery_save_path", &self.recovery_save_path)?;
+
+        if self.torrent_dir.is_some() && self.resume_dir.is_none() {
+            return Err(invalid_config("torrent_dir", "requires a resume_dir".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// the interval of a ``StatsPost`` in milliseconds, zero doesn't post it.
+/// Intervals are whole milliseconds, shorter ones other than zero are
+/// rejected rather than rounded to either.
+pub(crate) fn post_interval_ms(interval: Duration) -> Result<u32, String> {
+    let ms = interval.as_millis();
+    if interval.is_zero() {
+        Ok(0)
+    } else if ms == 0 || ms > u32::MAX as u128 {
+        Err(format!(
+            "{interval:?} is neither zero nor between 1ms and {}ms",
+            u32::MAX
+        ))
+    } else {
+        Ok(ms as u32)
+    }
+}
+
+/// the path as passed to the session, empty for ``None``
+pub(crate) fn path_str<'a>(param: &'static str, path: &'a Option<PathBuf>) -> LTResult<&'a str> {
+    let Some(path) = path else {
+        return Ok("");
+    };
+
+    match path.to_str() {
+        Some("") => Err(invalid_config(param, "empty".to_string())),
+        Some(s) => Ok(s),
+        None => Err(invalid_config(param, format!("{path:?} is not valid UTF-8"))),
+    }
+}
+
+pub(crate) fn invalid_config(param: &'static str, reason: String) -> LTError {
+    LTError::InvalidSessionConfig(param, reason)
+}
+
+/// builds a session, see ``SessionConfig`` for the defaults.
+#[derive(Clone, Default)]
+pub struct SessionBuilder {
+    config: SessionConfig,
+    settings: Vec<(String, SettingValue)>,
+    resume_store: Option<Arc<dyn ResumeStore>>,
+}
+
+impl fmt::Debug for SessionBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionBuilder")
+            .field("config", &self.config)
+            .field("settings", &self.settings)
+            .field("resume_store", &self.resume_store.is_some())
+            .finish()
+    }
+}
+
+impl SessionBuilder {
+    pub fn new() -> SessionBuilder {
+        SessionBuilder::default()
+    }
+
+    pub fn from_config(config: SessionConfig) -> SessionBuilder {
+        SessionBuilder {
+            config,
+            settings: Vec::new(),
+            resume_store: None,
+        }
+    }
+
+    /// the preset the settings are applied over
+    pub fn preset(mut self, preset: SettingsPreset) -> Self {
+        self.config.profile.preset = preset;
+        self
+    }
+
+    /// replaces the preset and the settings set so far
+    pub fn profile(mut self, profile: SettingsProfile) -> Self {
+        self.config.profile = profile;
+        self.settings.clear();
+        self
+    }
+
+    /// sets all settings of ``settings``. The settings are applied in the
+    /// order they are set, with those of ``setting``.
+    pub fn settings(mut self, settings: &SettingsPack) -> Self {
+        self.settings
+            .extend(settings.iter().map(|(name, value)| (name.to_string(), value.clone())));
+        self
+    }
+
+    /// sets a single setting, it's validated by ``build``, see
+    /// ``SettingsPack::set``. A later ``setting`` or ``settings`` overrides
+    /// it.
+    pub fn setting(mut self, name: impl Into<String>, value: impl Into<SettingValue>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn save_state_flags(mut self, flags: SaveStateFlags) -> Self {
+        self.config.save_state_flags = flags;
+        self
+    }
+
+    pub fn session_state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.session_state_path = Some(path.into());
+        self
+    }
+
+    pub fn resume_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.resume_dir = Some(dir.into());
+        self
+    }
+
+    pub fn torrent_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.torrent_dir = Some(dir.into());
+        self
+    }
+
+    pub fn recovery_save_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.recovery_save_path = Some(path.into());
+        self
+    }
+
+    /// keeps the resume data in ``store`` instead of ``resume_dir``, see
+    /// ``LTSession::with_resume_store``
+    pub fn resume_store(mut self, store: impl ResumeStore + 'static) -> Self {
+        self.resume_store = Some(Arc::new(store));
+        self
+    }
+
+    pub fn log_capacity(mut self, capacity: u32) -> Self {
+        self.config.log_capacity = capacity;
+        self
+    }
+
+    pub fn log_overflow(mut self, policy: LogOverflowPolicy) -> Self {
+        self.config.log_overflow = policy;
+        self
+    }
+
+    pub fn alert_poll_interval(mut self, interval: Duration) -> Self {
+        self.config.alert_poll_interval = interval;
+        self
+    }
+
+    /// how often ``post`` is posted, zero doesn't post it. See
+    /// ``LTSession::set_post_interval``
+    pub fn post_interval(mut self, post: StatsPost, interval: Duration) -> Self {
+        match post {
+            StatsPost::SessionStats => self.config.session_stats_interval = interval,
+            StatsPost::TorrentUpdates => self.config.torrent_updates_interval = interval,
+            StatsPost::DhtStats => self.config.dht_stats_interval = interval,
+        }
+        self
+    }
+
+    pub fn autosave_interval(mut self, interval: Duration) -> Self {
+        self.config.autosave_interval = Some(interval);
+        self
+    }
+
+    /// the validated configuration
+    pub fn config(mut self) -> LTResult<SessionConfig> {
+        for (name, value) in self.settings {
+            self.config.profile.settings.set(&name, value)?;
+        }
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    pub fn build(mut self) -> LTResult<LTSession> {
+        let resume_store = self.resume_store.take();
+        let config = self.config()?;
+        match resume_store {
+            Some(store) => LTSession::with_resume_store(&config, store),
+            None => LTSession::from_config(&config),
+        }
+    }
+}
+
+/// (de)serializes a ``Duration`` as milliseconds
+mod millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(d.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// (de)serializes an ``Option<Duration>`` as milliseconds
+mod opt_millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match d {
+            Some(d) => serializer.serialize_some(&(d.as_millis() as u64)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+    }
+}
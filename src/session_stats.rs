@@ -553,3 +553,25 @@ impl From<ffi::TwoSessionStats> for TwoSessionStats {
 pub struct SessionStats {
     pub two_session_stats: TwoSessionStats,
 }
+
+/// the stats which are posted periodically by a session, see
+/// ``LTSession::set_post_interval``
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsPost {
+    /// the counters of ``LTSession::get_stats``
+    SessionStats,
+    /// the torrent status of ``LTTorrentHandle::get_torrent_status``
+    TorrentUpdates,
+    /// the dht stats
+    DhtStats,
+}
+
+impl From<StatsPost> for ffi::StatsPost {
+    fn from(post: StatsPost) -> Self {
+        match post {
+            StatsPost::SessionStats => ffi::StatsPost::SessionStats,
+            StatsPost::TorrentUpdates => ffi::StatsPost::TorrentUpdates,
+            StatsPost::DhtStats => ffi::StatsPost::DhtStats,
+        }
+    }
+}
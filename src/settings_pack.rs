@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use libtorrent_rasterbar_sys::ffi::{self, SettingType, default_settings, parse_setting};
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use crate::{LTError, LTResult};
+
+/// the value of a setting, libtorrent settings are strings, integers or bools
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Str(String),
+    Int(i32),
+    Bool(bool),
+}
+
+impl SettingValue {
+    fn type_name(&self) -> &'static str {
+        match self {
+            SettingValue::Str(_) => "string",
+            SettingValue::Int(_) => "integer",
+            SettingValue::Bool(_) => "bool",
+        }
+    }
+
+    fn same_type(&self, other: &SettingValue) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl From<&str> for SettingValue {
+    fn from(v: &str) -> Self {
+        SettingValue::Str(v.to_string())
+    }
+}
+
+impl From<String> for SettingValue {
+    fn from(v: String) -> Self {
+        SettingValue::Str(v)
+    }
+}
+
+impl From<i32> for SettingValue {
+    fn from(v: i32) -> Self {
+        SettingValue::Int(v)
+    }
+}
+
+impl From<bool> for SettingValue {
+    fn from(v: bool) -> Self {
+        SettingValue::Bool(v)
+    }
+}
+
+/// libtorrent/settings_pack.hpp
+///
+/// a set of session settings by name. Every setting known to libtorrent can
+/// be set, ``SettingsPack::set`` rejects unknown names and values of the
+/// wrong type.
+///
+/// A pack only holds the settings that were set, applying it to a session
+/// leaves the others untouched. ``SettingsPack::defaults`` holds every
+/// setting with its default value and ``LTSession::get_settings`` every
+/// setting with its current value.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct SettingsPack {
+    values: BTreeMap<String, SettingValue>,
+}
+
+impl SettingsPack {
+    /// an empty pack, overriding nothing
+    pub fn new() -> SettingsPack {
+        SettingsPack::default()
+    }
+
+    /// every setting with its libtorrent default value
+    pub fn defaults() -> SettingsPack {
+        SettingsPack {
+            values: defaults().clone(),
+        }
+    }
+
+    /// the libtorrent default value of ``name``, ``None`` for unknown settings
+    pub fn default_value(name: &str) -> Option<&'static SettingValue> {
+        defaults().get(name)
+    }
+
+    /// sets ``name`` to ``value``. Fails with ``LTError::InvalidSetting`` if
+    /// there is no such setting or if the value has the wrong type.
+    ///
+    /// Integer and bool settings can also be given in the string form of
+    /// libtorrent's config files: enum value names (``"pe_forced"``),
+    /// ``"on"``/``"off"`` or, for ``alert_mask``, a comma separated list of
+    /// categories (``"error,status,storage"``).
+    pub fn set(&mut self, name: &str, value: impl Into<SettingValue>) -> LTResult<&mut Self> {
+        let mut value = value.into();
+        let Some(default) = SettingsPack::default_value(name) else {
+            return Err(LTError::InvalidSetting(name.to_string(), "unknown setting".to_string()));
+        };
+
+        if let (SettingValue::Str(s), false) = (&value, matches!(default, SettingValue::Str(_))) {
+            let setting =
+                parse_setting(name, s).map_err(|e| LTError::InvalidSetting(name.to_string(), e.to_string()))?;
+            value = setting_from_ffi(setting).1;
+        }
+
+        if !default.same_type(&value) {
+            return Err(LTError::InvalidSetting(
+                name.to_string(),
+                format!("expected {}, got {}", default.type_name(), value.type_name()),
+            ));
+        }
+
+        self.values.insert(name.to_string(), value);
+        Ok(self)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.values.get(name)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(SettingValue::Str(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        match self.values.get(name) {
+            Some(SettingValue::Int(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.values.get(name) {
+            Some(SettingValue::Bool(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// removes ``name`` from the pack, applying the pack then leaves it
+    /// untouched
+    pub fn remove(&mut self, name: &str) -> Option<SettingValue> {
+        self.values.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SettingValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// sets all settings of ``other`` in this pack
+    pub fn merge(&mut self, other: &SettingsPack) {
+        for (k, v) in &other.values {
+            self.values.insert(k.clone(), v.clone());
+        }
+    }
+
+    /// the settings of this pack which are not in ``base`` or have a
+    /// different value there
+    pub fn diff(&self, base: &SettingsPack) -> SettingsPack {
+        let values = self
+            .values
+            .iter()
+            .filter(|(k, v)| base.values.get(*k) != Some(*v))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        SettingsPack { values }
+    }
+
+    /// the settings of this pack which differ from the libtorrent defaults,
+    /// e.g. ``ses.get_settings().diff_defaults()`` gives everything that was
+    /// changed on a session
+    pub fn diff_defaults(&self) -> SettingsPack {
+        let values = self
+            .values
+            .iter()
+            .filter(|(k, v)| SettingsPack::default_value(k) != Some(*v))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        SettingsPack { values }
+    }
+
+    pub(crate) fn to_ffi(&self) -> Vec<ffi::Setting> {
+        self.values
+            .iter()
+            .map(|(name, value)| {
+                let mut s = ffi::Setting {
+                    name: name.clone(),
+                    kind: SettingType::Str,
+                    str_value: String::new(),
+                    int_value: 0,
+                    bool_value: false,
+                };
+                match value {
+                    SettingValue::Str(v) => s.str_value = v.clone(),
+                    SettingValue::Int(v) => {
+                        s.kind = SettingType::Int;
+                        s.int_value = *v;
+                    }
+                    SettingValue::Bool(v) => {
+                        s.kind = SettingType::Bool;
+                        s.bool_value = *v;
+                    }
+                }
+                s
+            })
+            .collect()
+    }
+
+    pub(crate) fn from_ffi(settings: Vec<ffi::Setting>) -> SettingsPack {
+        SettingsPack {
+            values: settings.into_iter().map(setting_from_ffi).collect(),
+        }
+    }
+}
+
+/// every key and value is validated like ``SettingsPack::set``
+impl<'de> Deserialize<'de> for SettingsPack {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = BTreeMap::<String, SettingValue>::deserialize(deserializer)?;
+
+        let mut pack = SettingsPack::new();
+        for (name, value) in values {
+            pack.set(&name, value).map_err(de::Error::custom)?;
+        }
+        Ok(pack)
+    }
+}
+
+fn setting_from_ffi(s: ffi::Setting) -> (String, SettingValue) {
+    let value = match s.kind {
+        SettingType::Int => SettingValue::Int(s.int_value),
+        SettingType::Bool => SettingValue::Bool(s.bool_value),
+        _ => SettingValue::Str(s.str_value),
+    };
+    (s.name, value)
+}
+
+fn defaults() -> &'static BTreeMap<String, SettingValue> {
+    static DEFAULTS: OnceLock<BTreeMap<String, SettingValue>> = OnceLock::new();
+    DEFAULTS.get_or_init(|| default_settings().into_iter().map(setting_from_ffi).collect())
+}
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use libtorrent_rasterbar_sys::ffi::{high_performance_seed_settings, min_memory_usage_settings};
+use serde::{Deserialize, Serialize};
+
+use crate::{LTError, LTResult, SettingsPack};
+
+/// libtorrent/session.hpp
+///
+/// the settings a profile starts from, before its own settings are applied
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsPreset {
+    /// the libtorrent defaults, for a regular bittorrent client running on a
+    /// desktop system
+    #[default]
+    Default,
+
+    /// ``min_memory_usage()``, uses the minimal amount of RAM at the expense
+    /// of upload and download performance. Intended for embedded devices.
+    MinMemoryUsage,
+
+    /// ``high_performance_seed()``, optimized for a seed box serving many
+    /// peers and not doing any downloading.
+    HighPerformanceSeed,
+}
+
+impl SettingsPreset {
+    /// the settings the preset changes from the defaults
+    pub fn settings(&self) -> SettingsPack {
+        match self {
+            SettingsPreset::Default => SettingsPack::new(),
+            SettingsPreset::MinMemoryUsage => SettingsPack::from_ffi(min_memory_usage_settings()),
+            SettingsPreset::HighPerformanceSeed => SettingsPack::from_ffi(high_performance_seed_settings()),
+        }
+    }
+}
+
+/// a named set of session settings layered over a preset, which can be
+/// loaded from and written to TOML or JSON:
+///
+/// ```toml
+/// name = "seedbox"
+/// preset = "high_performance_seed"
+///
+/// [settings]
+/// upload_rate_limit = 10000000
+/// enable_dht = false
+/// ```
+///
+/// Every setting is checked against libtorrent's settings when the profile
+/// is loaded, unknown names and values of the wrong type are rejected.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsProfile {
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub preset: SettingsPreset,
+
+    /// applied over the settings of the preset
+    #[serde(default, skip_serializing_if = "SettingsPack::is_empty")]
+    pub settings: SettingsPack,
+}
+
+impl SettingsProfile {
+    pub fn new(name: impl Into<String>, preset: SettingsPreset) -> SettingsProfile {
+        SettingsProfile {
+            name: name.into(),
+            preset,
+            settings: SettingsPack::new(),
+        }
+    }
+
+    pub fn from_toml(s: &str) -> LTResult<SettingsProfile> {
+        toml::from_str(s).map_err(|e| LTError::InvalidSettingsProfile(e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> LTResult<SettingsProfile> {
+        serde_json::from_str(s).map_err(|e| LTError::InvalidSettingsProfile(e.to_string()))
+    }
+
+    /// loads a profile from a .toml or .json file
+    pub fn from_file(path: impl AsRef<Path>) -> LTResult<SettingsProfile> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|e| LTError::Io(path.to_path_buf(), e))?;
+
+        let profile = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => SettingsProfile::from_toml(&s),
+            Some("json") => SettingsProfile::from_json(&s),
+            _ => Err(LTError::InvalidSettingsProfile(
+                "expected a .toml or .json file".to_string(),
+            )),
+        };
+        profile.map_err(|e| match e {
+            LTError::InvalidSettingsProfile(e) => LTError::InvalidSettingsProfile(format!("{}: {e}", path.display())),
+            e => e,
+        })
+    }
+
+    pub fn to_toml(&self) -> LTResult<String> {
+        toml::to_string(self).map_err(|e| LTError::InvalidSettingsProfile(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> LTResult<String> {
+        serde_json::to_string_pretty(self).map_err(|e| LTError::InvalidSettingsProfile(e.to_string()))
+    }
+
+    /// the settings of the preset with the settings of the profile applied
+    pub fn effective_settings(&self) -> SettingsPack {
+        let mut settings = self.preset.settings();
+        settings.merge(&self.settings);
+        settings
+    }
+
+    /// the profile with the preset resolved, i.e. every setting that differs
+    /// from the libtorrent defaults is listed. Useful to dump what a profile
+    /// actually sets.
+    pub fn effective(&self) -> SettingsProfile {
+        SettingsProfile {
+            name: self.name.clone(),
+            preset: SettingsPreset::Default,
+            settings: self.effective_settings().diff_defaults(),
+        }
+    }
+}
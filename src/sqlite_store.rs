@@ -0,0 +1,249 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use libtorrent_rasterbar_sys::{ResumeStore, ffi::ResumeEntry};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::{DirResumeStore, InfoHashes, LTError, LTResult};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS torrents (
+        info_hash TEXT PRIMARY KEY NOT NULL,
+        resume_data BLOB NOT NULL,
+        backup BLOB,
+        torrent BLOB,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS metadata (
+        info_hash TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        PRIMARY KEY (info_hash, key)
+    );
+    CREATE TABLE IF NOT EXISTS session_state (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        state BLOB NOT NULL
+    );
+";
+
+/// A ``ResumeStore`` keeping the resume data, the .torrent files, the
+/// session state and metadata of the application in a single SQLite
+/// database. Requires the ``sqlite`` feature.
+///
+/// Every write is a transaction, so the database is consistent after a
+/// crash. Like ``DirResumeStore`` it keeps the previous resume data of every
+/// torrent as a backup. With many torrents this starts up a lot faster than a directory
+/// with a file per torrent, and it's a single file to back up.
+///
+/// Pass it to ``SessionBuilder::resume_store``, a session then also keeps
+/// its session state in the database unless it has a ``session_state_path``.
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// opens the database at ``path``, it's created if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> LTResult<SqliteStore> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).map_err(|e| LTError::Sqlite(path.to_path_buf(), e))?;
+        SqliteStore::init(path.to_path_buf(), conn)
+    }
+
+    /// a database which only lives as long as the store
+    pub fn open_in_memory() -> LTResult<SqliteStore> {
+        let path = PathBuf::from(":memory:");
+        let conn = Connection::open_in_memory().map_err(|e| LTError::Sqlite(path.clone(), e))?;
+        SqliteStore::init(path, conn)
+    }
+
+    fn init(path: PathBuf, conn: Connection) -> LTResult<SqliteStore> {
+        let init = || -> rusqlite::Result<()> {
+            // another process (e.g. a standby node) may have the database open
+            conn.busy_timeout(Duration::from_secs(5))?;
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+            conn.execute_batch(SCHEMA)
+        };
+        init().map_err(|e| LTError::Sqlite(path.clone(), e))?;
+
+        Ok(SqliteStore {
+            path,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// sets the metadata ``key`` of a torrent to ``value``. The metadata is
+    /// removed with the torrent.
+    ///
+    /// The metadata is keyed like the resume data, by the best of
+    /// ``info_hashes``, so pass all info-hashes known for the torrent (e.g.
+    /// ``AddedTorrent::info_hashes``) rather than just its v2 info-hash.
+    pub fn set_metadata(&self, info_hashes: &InfoHashes, key: &str, value: &str) -> LTResult<()> {
+        let info_hash = store_key(info_hashes)?;
+        self.conn()
+            .execute(
+                "INSERT INTO metadata (info_hash, key, value) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (info_hash, key) DO UPDATE SET value = excluded.value",
+                params![info_hash, key, value],
+            )
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    pub fn remove_metadata(&self, info_hashes: &InfoHashes, key: &str) -> LTResult<()> {
+        let info_hash = store_key(info_hashes)?;
+        self.conn()
+            .execute(
+                "DELETE FROM metadata WHERE info_hash = ?1 AND key = ?2",
+                params![info_hash, key],
+            )
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    /// all metadata of a torrent
+    pub fn metadata(&self, info_hashes: &InfoHashes) -> LTResult<BTreeMap<String, String>> {
+        let info_hash = store_key(info_hashes)?;
+        let conn = self.conn();
+        let query = || -> rusqlite::Result<BTreeMap<String, String>> {
+            let mut stmt = conn.prepare("SELECT key, value FROM metadata WHERE info_hash = ?1")?;
+            let rows = stmt.query_map(params![info_hash], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        };
+        query().map_err(|e| self.error(e))
+    }
+
+    /// copies every torrent of ``dir`` into the database in a single
+    /// transaction and returns their number. Used to migrate from the
+    /// directory layout.
+    pub fn import_dir(&self, dir: &DirResumeStore) -> LTResult<usize> {
+        let entries = dir
+            .load_all()
+            .map_err(|e| LTError::Io(dir.resume_dir().to_path_buf(), e))?;
+
+        let mut conn = self.conn();
+        let mut import = || -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            for entry in &entries {
+                let resume_data = if entry.resume_data.is_empty() {
+                    &entry.backup
+                } else {
+                    &entry.resume_data
+                };
+                let torrent = (!entry.torrent.is_empty()).then_some(&entry.torrent[..]);
+                upsert(&tx, &entry.info_hash, resume_data, torrent)?;
+            }
+            tx.commit()
+        };
+        import().map_err(|e| self.error(e))?;
+        Ok(entries.len())
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn error(&self, e: rusqlite::Error) -> LTError {
+        LTError::Sqlite(self.path.clone(), e)
+    }
+}
+
+impl ResumeStore for SqliteStore {
+    fn load_all(&self) -> io::Result<Vec<ResumeEntry>> {
+        let conn = self.conn();
+        let query = || -> rusqlite::Result<Vec<ResumeEntry>> {
+            let mut stmt = conn.prepare("SELECT info_hash, resume_data, backup, torrent FROM torrents")?;
+            let rows = stmt.query_map([], |row| {
+                Ok(ResumeEntry {
+                    info_hash: row.get(0)?,
+                    resume_data: row.get(1)?,
+                    backup: row.get::<_, Option<Vec<u8>>>(2)?.unwrap_or_default(),
+                    torrent: row.get::<_, Option<Vec<u8>>>(3)?.unwrap_or_default(),
+                })
+            })?;
+            rows.collect()
+        };
+        query().map_err(io::Error::other)
+    }
+
+    fn load(&self, info_hash: &str) -> io::Result<Option<Vec<u8>>> {
+        self.conn()
+            .query_row(
+                "SELECT resume_data FROM torrents WHERE info_hash = ?1",
+                params![info_hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(io::Error::other)
+    }
+
+    fn save(&self, info_hash: &str, resume_data: &[u8], torrent: Option<&[u8]>) -> io::Result<()> {
+        upsert(&self.conn(), info_hash, resume_data, torrent).map_err(io::Error::other)
+    }
+
+    fn remove(&self, info_hash: &str) -> io::Result<()> {
+        let mut conn = self.conn();
+        let mut remove = || -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM torrents WHERE info_hash = ?1", params![info_hash])?;
+            tx.execute("DELETE FROM metadata WHERE info_hash = ?1", params![info_hash])?;
+            tx.commit()
+        };
+        remove().map_err(io::Error::other)
+    }
+
+    fn load_session_state(&self) -> io::Result<Option<Vec<u8>>> {
+        self.conn()
+            .query_row("SELECT state FROM session_state WHERE id = 0", [], |row| row.get(0))
+            .optional()
+            .map_err(io::Error::other)
+    }
+
+    fn save_session_state(&self, state: &[u8]) -> io::Result<()> {
+        self.conn()
+            .execute(
+                "INSERT INTO session_state (id, state) VALUES (0, ?1)
+                 ON CONFLICT (id) DO UPDATE SET state = excluded.state",
+                params![state],
+            )
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// the key of a torrent in the store, the session keys the resume data by the
+/// same info-hash
+fn store_key(info_hashes: &InfoHashes) -> LTResult<String> {
+    info_hashes
+        .best()
+        .map(|h| h.to_string())
+        .ok_or_else(|| LTError::InvalidInfoHash(String::new()))
+}
+
+/// stores the resume data of a torrent, the previous one becomes the backup.
+/// The .torrent file is kept from the first save which has one.
+fn upsert(conn: &Connection, info_hash: &str, resume_data: &[u8], torrent: Option<&[u8]>) -> rusqlite::Result<()> {
+    let updated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    conn.execute(
+        "INSERT INTO torrents (info_hash, resume_data, torrent, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (info_hash) DO UPDATE SET
+             backup = torrents.resume_data,
+             resume_data = excluded.resume_data,
+             torrent = COALESCE(torrents.torrent, excluded.torrent),
+             updated_at = excluded.updated_at",
+        params![info_hash, resume_data, torrent, updated_at],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,1337 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        ops::{ControlFlow, Deref},
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        AddTorrentParams, Alert, DirResumeStore, InfoHash, InfoHashes, LTError, LTSession, LogFilter, LogLevel,
+        LogOverflowPolicy, ResumeRecovery, ResumeStore, SaveResumeSummary, SessionConfig, SettingValue, SettingsPack,
+        SettingsPreset, SettingsProfile, Sha1Hash, Sha256Hash, StatsPost, TorrentBuilder, TorrentInfo, TorrentVersion,
+        alert::AlertDispatcher,
+    };
+    use libtorrent_rasterbar_sys::{
+        ffi::{self, AlertKind},
+        flags::SaveStateFlags,
+    };
+
+    /// a directory for a test, removed when it's dropped, also if the test
+    /// panics
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("libtest-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn create_default_session() -> anyhow::Result<LTSession> {
+        let ses = LTSession::builder()
+            .setting("user_agent", "libtorrent-rs/2.0.11")
+            .setting(
+                "alert_mask",
+                "error,peer,port_mapping,storage,tracker,connect,status,ip_block,performance_warning,dht,incoming_request,dht_operation,port_mapping_log,file_progress",
+            )
+            .log_capacity(20)
+            .build()?;
+
+        Ok(ses)
+    }
+
+    #[test]
+    #[ignore = "fetches the metadata from the public swarm"]
+    fn test_add_magnet() {
+        let ses = create_default_session().unwrap();
+
+        let params = AddTorrentParams::builder()
+            .max_connections(100)
+            .max_uploads(-1)
+            .save_path("/tmp/t/libtest/items/3574438")
+            .build()
+            .unwrap();
+
+        let added = ses
+            .add_magnet("magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343", &params)
+            .unwrap();
+        assert!(matches!(added.handle.get_torrent_info(), Err(LTError::NoMetadata)));
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(120);
+        let info = loop {
+            match added.handle.get_torrent_info() {
+                Ok(info) => break info,
+                Err(LTError::NoMetadata) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(500))
+                }
+                Err(e) => panic!("no metadata: {e}"),
+            }
+        };
+        assert_eq!(info.info_hash, "771cc76f2c725648b20a74d27ffeba2458a88343");
+        assert!(info.num_files > 0);
+    }
+
+    #[test]
+    fn test_add_torrent_params_rejects_invalid_values() {
+        let invalid = [
+            AddTorrentParams::builder().save_path(""),
+            AddTorrentParams::builder().tracker("htp://tracker.example.com/announce", 0),
+            AddTorrentParams::builder().tracker("udp://", 0),
+            AddTorrentParams::builder().web_seed("ftp://example.com/file"),
+            AddTorrentParams::builder().max_connections(-2),
+            AddTorrentParams::builder().max_connections(0),
+            AddTorrentParams::builder().max_connections(1),
+            AddTorrentParams::builder().dht_node("router.bittorrent.com", 0),
+            AddTorrentParams::builder().rename_file(-1, "a.txt"),
+            AddTorrentParams::builder().resume_data(Vec::new()),
+        ];
+
+        for builder in invalid {
+            assert!(matches!(builder.build(), Err(LTError::InvalidAddTorrentParam(..))));
+        }
+
+        AddTorrentParams::builder()
+            .save_path("/tmp/t/libtest/items")
+            .tracker("udp://tracker.opentrackr.org:1337/announce", 0)
+            .tracker("https://tracker.example.com/announce", 1)
+            .web_seed("https://example.com/files/")
+            .dht_node("router.bittorrent.com", 6881)
+            .max_connections(-1)
+            .peer("127.0.0.1:6881".parse().unwrap())
+            .build()
+            .unwrap();
+        AddTorrentParams::builder().max_connections(2).build().unwrap();
+    }
+
+    #[test]
+    fn test_add_duplicate_torrent() {
+        let ses = create_default_session().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path("/tmp/t/libtest/duplicate")
+            .build()
+            .unwrap();
+        let magnet = "magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343";
+
+        ses.add_magnet(magnet, &params).unwrap();
+        assert!(matches!(
+            ses.add_magnet(magnet, &params),
+            Err(LTError::DuplicateTorrent(info_hash)) if info_hash == "771cc76f2c725648b20a74d27ffeba2458a88343"
+        ));
+    }
+
+    #[test]
+    fn test_add_invalid_torrent_file() {
+        let dir = TempDir::new("invalid-file");
+        let path = dir.join("invalid.torrent");
+        std::fs::write(&path, b"not a torrent").unwrap();
+
+        let ses = create_default_session().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.to_str().unwrap())
+            .build()
+            .unwrap();
+        assert!(matches!(
+            ses.add_torrent(path.to_str().unwrap(), &params),
+            Err(LTError::InvalidMetadata(_))
+        ));
+        assert!(matches!(
+            ses.add_torrent_bytes(b"d4:infoi1ee", &params),
+            Err(LTError::InvalidMetadata(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_torrent_invalid_save_path() {
+        let dir = TempDir::new("invalid-save-path");
+        // a save path below a regular file can't be created
+        std::fs::write(dir.join("file"), b"").unwrap();
+        let save_path = dir.join("file").join("items");
+
+        let ses = create_default_session().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(save_path.to_str().unwrap())
+            .build()
+            .unwrap();
+        assert!(matches!(
+            ses.add_magnet("magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343", &params),
+            Err(LTError::InvalidSavePath(path, _)) if path == save_path.to_str().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_torrent_info_errors() {
+        let parse = |data: &[u8]| TorrentInfo::from_bytes(data).unwrap_err();
+
+        assert!(matches!(parse(b"d8:announce"), LTError::InvalidBencode(_)));
+        assert!(matches!(parse(b"d8:announce3:fooe"), LTError::MissingInfoDict(_)));
+        assert!(matches!(
+            parse(b"d4:infod6:lengthi1e4:name1:a12:piece lengthi0e6:pieces20:aaaaaaaaaaaaaaaaaaaaee"),
+            LTError::InvalidPieceLength(_)
+        ));
+        assert!(matches!(
+            TorrentInfo::from_file("/tmp/t/libtest/does-not-exist.torrent"),
+            Err(LTError::Io(..))
+        ));
+    }
+
+    #[test]
+    fn test_info_hashes() {
+        let v1 = Sha1Hash::from_hex("dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c").unwrap();
+        let v2 = Sha256Hash::from_hex("caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e").unwrap();
+        assert_eq!(v1.to_string(), "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c");
+        assert!(Sha1Hash::from_hex("dd8255ecdc7ca55fb0bbf81323d87062db1f6d1").is_none());
+        assert!(Sha1Hash::from_hex("zz8255ecdc7ca55fb0bbf81323d87062db1f6d1c").is_none());
+        assert!(Sha1Hash::from_hex("+d8255ecdc7ca55fb0bbf81323d87062db1f6d1c").is_none());
+
+        let hybrid = InfoHashes {
+            v1: Some(v1),
+            v2: Some(v2),
+        };
+        let magnet_v1 = InfoHashes { v1: Some(v1), v2: None };
+        let magnet_v2 = InfoHashes { v1: None, v2: Some(v2) };
+        assert!(magnet_v1.matches(&hybrid));
+        assert!(magnet_v2.matches(&hybrid));
+        assert!(!magnet_v1.matches(&magnet_v2));
+        assert!(!InfoHashes::default().matches(&InfoHashes::default()));
+    }
+
+    #[test]
+    fn test_parse_info_hash() {
+        let v1: InfoHash = "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c".parse().unwrap();
+        assert!(matches!(v1, InfoHash::V1(_)));
+        assert_eq!(v1, "3WBFL3G4PSSV7MF37AJSHWDQMLNR63I4".parse().unwrap());
+        assert_eq!(v1, "DD8255ECDC7CA55FB0BBF81323D87062DB1F6D1C".parse().unwrap());
+        assert_eq!(v1.to_string(), "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c");
+
+        let v2: InfoHash = "caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e"
+            .parse()
+            .unwrap();
+        assert!(matches!(v2, InfoHash::V2(_)));
+        assert_eq!(
+            v2,
+            "ZLY6DQYOQHFTMG464FT4JKTEEKFH7JH2T5QQKIZLFCWQTHZ2GAXA===="
+                .parse()
+                .unwrap()
+        );
+
+        for bad in [
+            "",
+            "dd8255",
+            "zz8255ecdc7ca55fb0bbf81323d87062db1f6d1c",
+            "+d8255ecdc7ca55fb0bbf81323d87062db1f6d1c",
+            "dd+255ecdc7ca55fb0bbf81323d87062db1f6d1c",
+            "3WBFL3G4PSSV7MF37AJSHWDQMLNR63I1",
+        ] {
+            assert!(matches!(bad.parse::<InfoHash>(), Err(LTError::InvalidInfoHash(_))));
+        }
+    }
+
+    #[test]
+    fn test_settings_pack() {
+        let mut pack = SettingsPack::new();
+        pack.set("upload_rate_limit", 1000)
+            .unwrap()
+            .set("enable_dht", false)
+            .unwrap();
+
+        assert!(matches!(
+            pack.set("upload_rate_limit", "fast"),
+            Err(LTError::InvalidSetting(..))
+        ));
+        assert!(matches!(
+            pack.set("no_such_setting", 1),
+            Err(LTError::InvalidSetting(..))
+        ));
+
+        assert_eq!(SettingsPack::default_value("upload_rate_limit"), Some(&0.into()));
+        assert_eq!(pack.diff_defaults(), pack);
+
+        let ses = create_default_session().unwrap();
+        ses.apply_settings(&pack).unwrap();
+
+        let settings = ses.get_settings();
+        assert_eq!(settings.get_int("upload_rate_limit"), Some(1000));
+        assert_eq!(settings.get_bool("enable_dht"), Some(false));
+        assert_eq!(settings.diff_defaults().get_int("upload_rate_limit"), Some(1000));
+    }
+
+    #[test]
+    fn test_settings_profile() {
+        let profile = SettingsProfile::from_toml(
+            r#"
+            name = "seedbox"
+            preset = "high_performance_seed"
+
+            [settings]
+            upload_rate_limit = 1000
+            enable_dht = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.preset, SettingsPreset::HighPerformanceSeed);
+        assert_eq!(profile.settings.get_int("upload_rate_limit"), Some(1000));
+
+        let json = profile.to_json().unwrap();
+        assert_eq!(SettingsProfile::from_json(&json).unwrap(), profile);
+
+        let effective = profile.effective();
+        assert_eq!(effective.preset, SettingsPreset::Default);
+        assert_eq!(effective.settings.get_bool("enable_dht"), Some(false));
+        assert!(effective.settings.len() > profile.settings.len());
+
+        for bad in [
+            "[settings]\nno_such_setting = 1",
+            "[settings]\nupload_rate_limit = \"fast\"",
+            "preset = \"turbo\"",
+        ] {
+            assert!(matches!(
+                SettingsProfile::from_toml(bad),
+                Err(LTError::InvalidSettingsProfile(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_session_config() {
+        let config: SessionConfig = toml::from_str(
+            r#"
+            save_state_flags = "save_settings | save_dht_state"
+            resume_dir = "/tmp/t/libtest/resume"
+            alert_poll_interval_ms = 100
+            autosave_interval_ms = 60000
+            log_overflow = "drop_newest"
+            torrent_updates_interval_ms = 100
+            dht_stats_interval_ms = 0
+
+            [profile]
+            preset = "min_memory_usage"
+
+            [profile.settings]
+            alert_mask = "error,status"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.save_state_flags,
+            SaveStateFlags::save_settings | SaveStateFlags::save_dht_state
+        );
+        assert_eq!(config.session_state_path, None);
+        assert_eq!(config.alert_poll_interval, std::time::Duration::from_millis(100));
+        assert_eq!(config.autosave_interval, Some(std::time::Duration::from_secs(60)));
+        assert_eq!(config.log_capacity, SessionConfig::default().log_capacity);
+        assert_eq!(config.log_overflow, LogOverflowPolicy::DropNewest);
+        assert_eq!(config.torrent_updates_interval, std::time::Duration::from_millis(100));
+        assert_eq!(config.dht_stats_interval, std::time::Duration::ZERO);
+        assert_eq!(
+            config.session_stats_interval,
+            SessionConfig::default().session_stats_interval
+        );
+        assert!(matches!(
+            config.profile.settings.get("alert_mask"),
+            Some(SettingValue::Int(_))
+        ));
+
+        assert!(matches!(
+            LTSession::builder().log_capacity(0).config(),
+            Err(LTError::InvalidSessionConfig("log_capacity", _))
+        ));
+        assert!(matches!(
+            LTSession::builder()
+                .autosave_interval(std::time::Duration::ZERO)
+                .config(),
+            Err(LTError::InvalidSessionConfig("autosave_interval", _))
+        ));
+        assert!(matches!(
+            LTSession::builder().setting("alert_mask", "no_such_category").config(),
+            Err(LTError::InvalidSetting(..))
+        ));
+
+        // the settings are applied in order, the last one wins
+        let mut pack = SettingsPack::new();
+        pack.set("connections_limit", 2).unwrap();
+        let config = LTSession::builder()
+            .setting("connections_limit", 1)
+            .settings(&pack)
+            .config()
+            .unwrap();
+        assert_eq!(config.profile.settings.get_int("connections_limit"), Some(2));
+        let config = LTSession::builder()
+            .settings(&pack)
+            .setting("connections_limit", 1)
+            .config()
+            .unwrap();
+        assert_eq!(config.profile.settings.get_int("connections_limit"), Some(1));
+    }
+
+    #[test]
+    fn test_ephemeral_session() {
+        let ses = create_default_session().unwrap();
+        assert!(ses.is_ephemeral());
+        assert!(ses.get_torrents().is_empty());
+
+        let dir = TempDir::new("ephemeral");
+        let ses = LTSession::builder()
+            .session_state_path(dir.join("session.state"))
+            .build()
+            .unwrap();
+        assert!(!ses.is_ephemeral());
+        let ses = LTSession::builder().resume_dir(dir.join("resume")).build().unwrap();
+        assert!(!ses.is_ephemeral());
+        let store = DirResumeStore::new(dir.join("store")).unwrap();
+        let ses = LTSession::with_resume_store(&SessionConfig::default(), store).unwrap();
+        assert!(!ses.is_ephemeral());
+        drop(ses);
+    }
+
+    #[test]
+    fn test_dir_resume_store() {
+        let dir = TempDir::new("resume-store");
+        let store = DirResumeStore::new(dir.join("resume"))
+            .unwrap()
+            .with_torrent_dir(dir.join("torrents"))
+            .unwrap();
+        let hash = "c9e15763f722f23e98a29decdfae341b98d53056";
+
+        assert!(store.load_all().unwrap().is_empty());
+        assert_eq!(store.load(hash).unwrap(), None);
+
+        store.save(hash, b"d1:ai1ee", Some(b"d4:infod1:ai1eee")).unwrap();
+        store.save(hash, b"d1:ai2ee", Some(b"d4:infod1:ai2eee")).unwrap();
+        assert_eq!(store.load(hash).unwrap().as_deref(), Some(&b"d1:ai2ee"[..]));
+
+        // the .torrent file is kept from the first save
+        let entries = store.load_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].info_hash, hash);
+        assert_eq!(entries[0].torrent, b"d4:infod1:ai1eee");
+
+        // the previous resume data is kept as a backup, which is used if the
+        // resume file is lost
+        assert_eq!(entries[0].backup, b"d1:ai1ee");
+        assert!(std::fs::read_dir(dir.join("resume")).unwrap().all(|e| {
+            let name = e.unwrap().file_name();
+            !name.to_str().unwrap().ends_with(".tmp")
+        }));
+        std::fs::remove_file(dir.join("resume").join(format!("{hash}.resume"))).unwrap();
+        let entries = store.load_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].resume_data.is_empty());
+        assert_eq!(store.load(hash).unwrap().as_deref(), Some(&b"d1:ai1ee"[..]));
+
+        store.remove(hash).unwrap();
+        store.remove(hash).unwrap();
+        assert!(store.load_all().unwrap().is_empty());
+        assert!(!dir.join("torrents").join(format!("{hash}.torrent")).exists());
+
+        assert!(matches!(
+            LTSession::builder()
+                .resume_dir(dir.join("resume"))
+                .resume_store(store)
+                .build(),
+            Err(LTError::InvalidSessionConfig("resume_dir", _))
+        ));
+        assert!(matches!(
+            LTSession::builder().torrent_dir(dir.join("torrents")).config(),
+            Err(LTError::InvalidSessionConfig("torrent_dir", _))
+        ));
+    }
+
+    #[test]
+    fn test_dir_resume_store_concurrent_saves() {
+        let dir = TempDir::new("resume-concurrent");
+        let store = DirResumeStore::new(dir.to_path_buf()).unwrap();
+        let hash = "c9e15763f722f23e98a29decdfae341b98d53056";
+
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let store = &store;
+                s.spawn(move || {
+                    for j in 0..20 {
+                        store.save(hash, format!("d1:ai{i}{j}ee").as_bytes(), None).unwrap();
+                    }
+                });
+            }
+        });
+
+        let resume_data = store.load(hash).unwrap().unwrap();
+        assert!(resume_data.starts_with(b"d1:ai") && resume_data.ends_with(b"ee"));
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files.len(), 2, "{files:?}");
+    }
+
+    #[test]
+    fn test_truncated_resume_file() {
+        let dir = TempDir::new("truncated-resume");
+        std::fs::create_dir_all(dir.join("items")).unwrap();
+        let truncated: InfoHash = "771cc76f2c725648b20a74d27ffeba2458a88343".parse().unwrap();
+        let intact: InfoHash = "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c".parse().unwrap();
+
+        let ses = LTSession::builder().resume_dir(dir.join("resume")).build().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.join("items").to_str().unwrap())
+            .build()
+            .unwrap();
+        for info_hash in [truncated, intact] {
+            ses.add_magnet(&format!("magnet:?xt=urn:btih:{info_hash}"), &params)
+                .unwrap();
+        }
+        assert!(
+            ses.save_all_resume_data(std::time::Duration::from_secs(10))
+                .is_complete()
+        );
+        drop(ses);
+
+        // e.g. a disk which filled up while the file was written
+        let resume_file = dir.join(format!("resume/{truncated}.resume"));
+        let resume_data = std::fs::read(&resume_file).unwrap();
+        std::fs::write(&resume_file, &resume_data[..resume_data.len() / 2]).unwrap();
+        let _ = std::fs::remove_file(dir.join(format!("resume/{truncated}.resume.bak")));
+
+        let ses = LTSession::builder().resume_dir(dir.join("resume")).build().unwrap();
+        let failures = ses.resume_load_failures();
+        assert_eq!(failures.len(), 1, "{failures:?}");
+        assert_eq!(failures[0].info_hash, truncated.to_string());
+        assert!(!failures[0].error.is_empty());
+        assert_eq!(failures[0].recovery, ResumeRecovery::NotRecovered);
+
+        // the other torrent is still added back
+        assert!(ses.get_torrent_handle(&intact).is_ok());
+        assert!(matches!(
+            ses.get_torrent_handle(&truncated),
+            Err(LTError::InvalidTorrentHandle)
+        ));
+
+        drop(ses);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        use crate::SqliteStore;
+
+        let store = SqliteStore::open_in_memory().unwrap();
+        let hash = "c9e15763f722f23e98a29decdfae341b98d53056";
+        let info_hash = InfoHashes {
+            v1: Sha1Hash::from_hex(hash),
+            v2: None,
+        };
+
+        assert!(store.load_all().unwrap().is_empty());
+        assert_eq!(store.load_session_state().unwrap(), None);
+
+        store.save(hash, b"d1:ai1ee", None).unwrap();
+        store.save(hash, b"d1:ai2ee", Some(b"d4:infod1:ai1eee")).unwrap();
+        store.save(hash, b"d1:ai3ee", Some(b"d4:infod1:ai2eee")).unwrap();
+        assert_eq!(store.load(hash).unwrap().as_deref(), Some(&b"d1:ai3ee"[..]));
+
+        let entries = store.load_all().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].backup, b"d1:ai2ee");
+        assert_eq!(entries[0].torrent, b"d4:infod1:ai1eee");
+
+        store.set_metadata(&info_hash, "category", "linux").unwrap();
+        store.set_metadata(&info_hash, "category", "iso").unwrap();
+        assert_eq!(
+            store.metadata(&info_hash).unwrap().get("category").map(String::as_str),
+            Some("iso")
+        );
+
+        store.save_session_state(b"d3:dhtde").unwrap();
+        assert_eq!(store.load_session_state().unwrap().as_deref(), Some(&b"d3:dhtde"[..]));
+
+        store.remove(hash).unwrap();
+        assert_eq!(store.load(hash).unwrap(), None);
+        assert!(store.metadata(&info_hash).unwrap().is_empty());
+
+        // a hybrid torrent is stored by its v1 info-hash, its metadata too
+        let hybrid = InfoHashes {
+            v1: Sha1Hash::from_hex(hash),
+            v2: Sha256Hash::from_hex("caf1e1c30e81cb361b9ee167c4aa64228a7fa4fa9f6105232b28ad099f3a302e"),
+        };
+        store.save(hash, b"d1:ai1ee", None).unwrap();
+        store.set_metadata(&hybrid, "category", "iso").unwrap();
+        assert_eq!(store.metadata(&info_hash).unwrap().len(), 1);
+        store.remove(&hybrid.best().unwrap().to_string()).unwrap();
+        assert!(store.metadata(&hybrid).unwrap().is_empty());
+
+        // a v2-only torrent by its full v2 info-hash
+        let v2 = InfoHashes { v1: None, ..hybrid };
+        let v2_hash = v2.best().unwrap().to_string();
+        assert_eq!(v2_hash.len(), 64);
+        store.save(&v2_hash, b"d1:ai1ee", None).unwrap();
+        store.set_metadata(&v2, "category", "iso").unwrap();
+        store.remove(&v2_hash).unwrap();
+        assert!(store.metadata(&v2).unwrap().is_empty());
+
+        assert!(matches!(
+            store.set_metadata(&InfoHashes::default(), "category", "iso"),
+            Err(LTError::InvalidInfoHash(_))
+        ));
+    }
+
+    #[test]
+    fn test_orphaned_torrents() {
+        let dir = TempDir::new("orphaned");
+        let store = DirResumeStore::new(dir.join("resume")).unwrap();
+        let info_hash: InfoHash = "771cc76f2c725648b20a74d27ffeba2458a88343".parse().unwrap();
+        let other: InfoHash = "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c".parse().unwrap();
+
+        let ses = LTSession::builder().resume_store(store.clone()).build().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.join("items").to_str().unwrap())
+            .build()
+            .unwrap();
+        ses.add_magnet(&format!("magnet:?xt=urn:btih:{info_hash}"), &params)
+            .unwrap();
+        ses.add_magnet(&format!("magnet:?xt=urn:btih:{other}"), &params)
+            .unwrap();
+        drop(ses);
+
+        // the save path is gone, e.g. the drive isn't mounted
+        std::fs::remove_dir_all(dir.join("items")).unwrap();
+        let ses = LTSession::builder().resume_store(store).build().unwrap();
+        let orphans = ses.orphaned_torrents();
+        assert_eq!(orphans.len(), 2);
+        assert!(orphans.iter().any(|o| o.info_hashes.best() == Some(info_hash)));
+        assert!(ses.get_torrents().is_empty());
+        assert!(ses.retry_orphaned_torrents().is_empty());
+
+        // a failure other than not being orphaned is passed through, the
+        // torrent stays orphaned
+        std::fs::write(dir.join("file"), b"").unwrap();
+        assert!(matches!(
+            ses.relocate_orphaned_torrent(&info_hash, dir.join("file/moved").to_str().unwrap()),
+            Err(LTError::InvalidSavePath(..))
+        ));
+        assert_eq!(ses.orphaned_torrents().len(), 2);
+
+        let added = ses
+            .relocate_orphaned_torrent(&info_hash, dir.join("moved").to_str().unwrap())
+            .unwrap();
+        assert_eq!(added.info_hashes.best(), Some(info_hash));
+        assert_eq!(ses.orphaned_torrents().len(), 1);
+
+        // the drive is mounted again
+        std::fs::create_dir_all(dir.join("items")).unwrap();
+        let retried = ses.retry_orphaned_torrents();
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].as_ref().unwrap().info_hashes.best(), Some(other));
+        assert!(ses.orphaned_torrents().is_empty());
+        assert!(ses.retry_orphaned_torrents().is_empty());
+
+        assert!(matches!(
+            ses.relocate_orphaned_torrent(&info_hash, dir.join("moved").to_str().unwrap()),
+            Err(LTError::NotOrphaned(_))
+        ));
+        assert!(!ses.forget_orphaned_torrent(&info_hash).unwrap());
+
+        drop(ses);
+    }
+
+    #[test]
+    fn test_btmh_magnet_resume() {
+        let dir = TempDir::new("btmh-resume");
+        std::fs::create_dir_all(dir.join("seed")).unwrap();
+        std::fs::create_dir_all(dir.join("items")).unwrap();
+        std::fs::write(dir.join("seed/file.bin"), vec![7u8; 100_000]).unwrap();
+
+        // the metadata of the hybrid torrent comes from a local seed
+        let seed = LTSession::builder()
+            .setting("listen_interfaces", "127.0.0.1:46881")
+            .setting("enable_dht", false)
+            .build()
+            .unwrap();
+        let (_, seeded) = TorrentBuilder::new(dir.join("seed/file.bin"))
+            .add_to_session(&seed)
+            .unwrap();
+        let v1 = seeded.info_hashes.v1.unwrap();
+        let v2 = seeded.info_hashes.v2.unwrap();
+
+        let builder = || {
+            LTSession::builder()
+                .setting("listen_interfaces", "127.0.0.1:46882")
+                .setting("enable_dht", false)
+                .resume_dir(dir.join("resume"))
+        };
+        let resume_file = |info_hash: &dyn std::fmt::Display| dir.join(format!("resume/{info_hash}.resume"));
+        let ses = builder().build().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.join("items").to_str().unwrap())
+            .peer("127.0.0.1:46881".parse().unwrap())
+            .build()
+            .unwrap();
+        let added = ses
+            .add_magnet(&format!("magnet:?xt=urn:btmh:1220{v2}"), &params)
+            .unwrap();
+
+        // saved under the v2 info-hash while only that is known
+        let summary = ses.save_all_resume_data(std::time::Duration::from_secs(10));
+        assert!(summary.is_complete(), "{summary:?}");
+        assert!(resume_file(&v2).exists());
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+        while added.handle.get_torrent_info().is_err() {
+            assert!(std::time::Instant::now() < deadline, "no metadata from the seed");
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        // the hybrid torrent moves to its v1 info-hash
+        let summary = ses.save_all_resume_data(std::time::Duration::from_secs(10));
+        assert!(summary.is_complete(), "{summary:?}");
+        assert!(resume_file(&v1).exists());
+        assert!(!resume_file(&v2).exists());
+        drop(ses);
+
+        // it's added back once
+        let ses = builder().build().unwrap();
+        assert_eq!(ses.get_torrents().len(), 1);
+        assert!(ses.get_torrent_handle(&InfoHash::V2(v2)).is_ok());
+
+        ses.remove_torrent(&InfoHash::V2(v2), false).unwrap();
+        assert!(!resume_file(&v1).exists());
+        assert!(!resume_file(&v2).exists());
+        drop(ses);
+
+        let ses = builder().build().unwrap();
+        assert!(ses.get_torrents().is_empty());
+    }
+
+    #[test]
+    fn test_save_all_resume_data() {
+        let dir = TempDir::new("save-resume");
+        let info_hash: InfoHash = "771cc76f2c725648b20a74d27ffeba2458a88343".parse().unwrap();
+
+        let ses = LTSession::builder()
+            .session_state_path(dir.join("session.state"))
+            .resume_dir(dir.join("resume"))
+            .autosave_interval(std::time::Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.join("items").to_str().unwrap())
+            .build()
+            .unwrap();
+        ses.add_magnet(&format!("magnet:?xt=urn:btih:{info_hash}"), &params)
+            .unwrap();
+
+        let summary = ses.save_all_resume_data(std::time::Duration::from_secs(10));
+        assert!(summary.is_complete(), "{summary:?}");
+        assert_eq!(summary.saved, vec![info_hash]);
+        assert!(dir.join(format!("resume/{info_hash}.resume")).exists());
+
+        ses.save_state().unwrap();
+        assert!(dir.join("session.state").exists());
+        drop(ses);
+
+        // nothing to save to
+        let ses = create_default_session().unwrap();
+        assert_eq!(
+            ses.save_all_resume_data(std::time::Duration::from_secs(1)),
+            SaveResumeSummary::default()
+        );
+        ses.save_state().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let dir = TempDir::new("shutdown");
+        let info_hash: InfoHash = "771cc76f2c725648b20a74d27ffeba2458a88343".parse().unwrap();
+
+        let ses = LTSession::builder().resume_dir(dir.join("resume")).build().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path(dir.join("items").to_str().unwrap())
+            .build()
+            .unwrap();
+        ses.add_magnet(&format!("magnet:?xt=urn:btih:{info_hash}"), &params)
+            .unwrap();
+
+        let report = ses.shutdown(std::time::Duration::from_secs(10));
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(report.resume_data.saved, vec![info_hash]);
+
+        let ses = LTSession::builder().resume_dir(dir.join("resume")).build().unwrap();
+        assert_eq!(ses.get_torrents().len(), 1);
+        let report = ses.shutdown(std::time::Duration::ZERO);
+        assert_eq!(report.resume_data.saved.len() + report.resume_data.timed_out.len(), 1);
+    }
+
+    #[test]
+    fn test_get_logs_from_threads() {
+        let ses = LTSession::builder()
+            .setting("alert_mask", "all")
+            .log_capacity(4)
+            .log_overflow(LogOverflowPolicy::DropOldest)
+            .alert_poll_interval(std::time::Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path("/tmp/t/libtest/logs")
+            .build()
+            .unwrap();
+        ses.add_magnet("magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343", &params)
+            .unwrap();
+
+        let logs = std::thread::scope(|s| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut n = 0;
+                        for _ in 0..50 {
+                            let logs = ses.get_logs();
+                            assert!(logs.len() <= 4);
+                            n += logs.len();
+                            std::thread::sleep(std::time::Duration::from_millis(5));
+                        }
+                        n
+                    })
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).sum::<usize>()
+        });
+        assert!(logs > 0 || ses.dropped_logs() > 0);
+    }
+
+    #[test]
+    fn test_block_log_overflow() {
+        let dir = TempDir::new("block-logs");
+        std::fs::write(dir.join("file.bin"), vec![7u8; 100_000]).unwrap();
+
+        let ses = LTSession::builder()
+            .setting("alert_mask", "all")
+            .resume_dir(dir.join("resume"))
+            .log_capacity(2)
+            .log_overflow(LogOverflowPolicy::Block)
+            .alert_poll_interval(std::time::Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let (_, added) = TorrentBuilder::new(dir.join("file.bin")).add_to_session(&ses).unwrap();
+        let info_hash = added.info_hashes.best().unwrap();
+
+        // the log is full, the alert thread waits for room
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        // none of these may wait for the alert thread, run them on another
+        // thread to fail rather than hang
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let h = &added.handle;
+            let timeout = std::time::Duration::from_millis(200);
+            assert!(matches!(h.get_peers(timeout), Ok(_) | Err(LTError::Timeout(_))));
+            assert!(matches!(h.get_piece_info(timeout), Ok(_) | Err(LTError::Timeout(_))));
+            assert!(h.get_torrent_status().is_ok());
+            ses.remove_torrent(&info_hash, false).unwrap();
+            let report = ses.shutdown(std::time::Duration::from_secs(5));
+            tx.send(report).unwrap();
+        });
+
+        let report = rx.recv_timeout(std::time::Duration::from_secs(20)).unwrap();
+        assert!(report.state_error.is_none());
+    }
+
+    #[test]
+    fn test_post_intervals() {
+        let ses = LTSession::builder()
+            .post_interval(StatsPost::SessionStats, std::time::Duration::from_millis(50))
+            .post_interval(StatsPost::DhtStats, std::time::Duration::ZERO)
+            .build()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        let stats = ses.get_stats().two_session_stats;
+        assert!(stats.timestamp > stats.prev_timestamp);
+
+        ses.set_post_interval(StatsPost::SessionStats, std::time::Duration::ZERO)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let timestamp = ses.get_stats().two_session_stats.timestamp;
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert_eq!(ses.get_stats().two_session_stats.timestamp, timestamp);
+
+        // neither rounded up to 1ms nor down to zero
+        let sub_ms = std::time::Duration::from_micros(500);
+        assert!(matches!(
+            ses.set_post_interval(StatsPost::SessionStats, sub_ms),
+            Err(LTError::InvalidSessionConfig("post_interval", _))
+        ));
+        assert!(matches!(
+            LTSession::builder().post_interval(StatsPost::DhtStats, sub_ms).config(),
+            Err(LTError::InvalidSessionConfig("dht_stats_interval", _))
+        ));
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let ses = LTSession::builder()
+            .setting("alert_mask", "all")
+            .log_capacity(10000)
+            .build()
+            .unwrap();
+        let info_hash: InfoHash = "771cc76f2c725648b20a74d27ffeba2458a88343".parse().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path("/tmp/t/libtest/logs")
+            .build()
+            .unwrap();
+        ses.add_magnet(&format!("magnet:?xt=urn:btih:{info_hash}"), &params)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(2));
+
+        let logs = ses.get_logs_filtered(&LogFilter::new().torrent(info_hash).max_level(LogLevel::Debug));
+        assert!(!logs.is_empty());
+        assert!(
+            logs.iter()
+                .all(|log| log.info_hash == Some(info_hash) && log.level <= LogLevel::Debug)
+        );
+        assert!(logs.iter().any(|log| log.alert_type.is_some()));
+
+        // the others are kept. The torrent keeps logging, so the remaining
+        // logs may hold matching ones again and only the drained batch is
+        // checked for them
+        assert!(
+            ses.get_logs()
+                .iter()
+                .any(|log| log.info_hash != Some(info_hash) || log.level > LogLevel::Debug)
+        );
+        assert!(LogLevel::Error < LogLevel::Trace);
+    }
+
+    #[test]
+    fn test_torrent_handle_errors() {
+        let dir = TempDir::new("handle-errors");
+        std::fs::write(dir.join("file.bin"), vec![7u8; 100_000]).unwrap();
+
+        let ses = create_default_session().unwrap();
+        let unknown: InfoHash = "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c".parse().unwrap();
+        assert!(matches!(
+            ses.get_torrent_handle(&unknown),
+            Err(LTError::InvalidTorrentHandle)
+        ));
+        assert!(matches!(
+            ses.remove_torrent(&unknown, false),
+            Err(LTError::InvalidTorrentHandle)
+        ));
+
+        // no metadata yet
+        let params = AddTorrentParams::builder()
+            .save_path(dir.to_str().unwrap())
+            .build()
+            .unwrap();
+        let magnet = ses
+            .add_magnet("magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343", &params)
+            .unwrap();
+        assert!(matches!(magnet.handle.get_torrent_info(), Err(LTError::NoMetadata)));
+        assert!(matches!(
+            magnet.handle.set_file_priority(0, 4),
+            Err(LTError::NoMetadata)
+        ));
+        magnet.handle.add_tracker("udp://tracker.example.com:6969", 0).unwrap();
+
+        let (_, added) = TorrentBuilder::new(dir.join("file.bin")).add_to_session(&ses).unwrap();
+        let h = added.handle;
+        h.set_file_priority(0, 4).unwrap();
+        assert!(matches!(h.set_file_priority(1, 4), Err(LTError::InvalidFileIndex(1))));
+        assert!(matches!(h.get_file_priority(-1), Err(LTError::InvalidFileIndex(-1))));
+        assert!(matches!(
+            h.set_prioritize_files(&[4, 4]),
+            Err(LTError::InvalidFileIndex(1))
+        ));
+
+        ses.remove_torrent(&added.info_hashes.best().unwrap(), false).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(matches!(h.force_recheck(), Err(LTError::InvalidTorrentHandle)));
+        assert!(matches!(h.upload_limit(), Err(LTError::InvalidTorrentHandle)));
+        assert!(matches!(h.pause(), Err(LTError::InvalidTorrentHandle)));
+    }
+
+    fn ffi_alert(kind: AlertKind, error_value: i32) -> ffi::Alert {
+        ffi::Alert {
+            kind,
+            timestamp: 0,
+            info_hashes: ffi::InfoHashes {
+                v1: "dd8255ecdc7ca55fb0bbf81323d87062db1f6d1c".to_string(),
+                v2: String::new(),
+            },
+            torrent_name: "t".to_string(),
+            file_index: -1,
+            piece_index: -1,
+            path: "f".to_string(),
+            tracker_url: String::new(),
+            count: 0,
+            state: 0,
+            prev_state: 0,
+            endpoint: String::new(),
+            error: ffi::ErrorCode {
+                category: "libtorrent".to_string(),
+                value: error_value,
+                message: "error".to_string(),
+            },
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_alert_from_ffi() {
+        // an alert without an error code is kept
+        let Some(Alert::FileError { error, path, .. }) = Alert::from_ffi(ffi_alert(AlertKind::FileError, 0)) else {
+            panic!("the file error alert was dropped");
+        };
+        assert_eq!(path, "f");
+        assert!(error.is_none());
+
+        let Some(Alert::TrackerError { error, .. }) = Alert::from_ffi(ffi_alert(AlertKind::TrackerError, 2)) else {
+            panic!("the tracker error alert was dropped");
+        };
+        assert_eq!(error.unwrap().value, 2);
+
+        let alert = Alert::from_ffi(ffi_alert(AlertKind::TorrentFinished, 0)).unwrap();
+        assert!(alert.info_hashes().unwrap().has_v1());
+    }
+
+    #[test]
+    fn test_alert_dispatcher() {
+        let dispatcher = AlertDispatcher::default();
+        let first = dispatcher.subscribe();
+        let second = dispatcher.subscribe();
+
+        dispatcher.dispatch(ffi_alert(AlertKind::TorrentPaused, 0));
+        assert!(matches!(first.try_recv(), Ok(Alert::TorrentPaused { .. })));
+        assert!(matches!(second.try_recv(), Ok(Alert::TorrentPaused { .. })));
+
+        // a subscriber whose receiver is gone or which panics is dropped, the
+        // others still get the alerts
+        drop(second);
+        dispatcher.add_subscriber(Box::new(|_| panic!("subscriber")));
+        let third = dispatcher.subscribe();
+        dispatcher.dispatch(ffi_alert(AlertKind::TorrentResumed, 0));
+        dispatcher.dispatch(ffi_alert(AlertKind::TorrentFinished, 0));
+
+        for alerts in [&first, &third] {
+            assert!(matches!(alerts.try_recv(), Ok(Alert::TorrentResumed { .. })));
+            assert!(matches!(alerts.try_recv(), Ok(Alert::TorrentFinished { .. })));
+        }
+    }
+    #[test]
+    fn test_handle_outlives_session() {
+        let ses = create_default_session().unwrap();
+        let params = AddTorrentParams::builder()
+            .save_path("/tmp/t/libtest/outlives")
+            .build()
+            .unwrap();
+        let added = ses
+            .add_magnet("magnet:?xt=urn:btih:771cc76f2c725648b20a74d27ffeba2458a88343", &params)
+            .unwrap();
+        let alerts = ses.subscribe();
+        assert!(added.handle.is_valid());
+
+        drop(ses);
+        assert!(!added.handle.is_valid());
+        assert!(matches!(
+            added.handle.force_reannounce(),
+            Err(LTError::InvalidTorrentHandle)
+        ));
+        assert!(matches!(
+            added.handle.get_peers(std::time::Duration::from_secs(1)),
+            Err(LTError::InvalidTorrentHandle)
+        ));
+
+        // the subscribers are disconnected
+        while alerts.try_recv().is_ok() {}
+        assert!(matches!(alerts.recv(), Err(std::sync::mpsc::RecvError)));
+    }
+
+    #[test]
+    fn test_add_torrent_info_round_trip() {
+        let dir = TempDir::new("round-trip");
+        std::fs::write(dir.join("file.bin"), vec![7u8; 100_000]).unwrap();
+
+        let torrent = TorrentBuilder::new(dir.join("file.bin")).build().unwrap();
+        let info = TorrentInfo::from_bytes(&torrent).unwrap();
+        assert_eq!(info.torrent_file(), torrent.as_slice());
+        // a hybrid torrent is identified by its v1 info-hash everywhere
+        let v1 = info.info_hashes.v1.unwrap().to_string();
+        assert!(info.info_hashes.has_v2());
+        assert_eq!(info.info_hash, v1);
+
+        let params = AddTorrentParams::builder()
+            .save_path(dir.to_str().unwrap())
+            .build()
+            .unwrap();
+        let ses = create_default_session().unwrap();
+        let added = ses.add_torrent_info(&info, &params).unwrap();
+        assert!(added.info_hashes.matches(&info.info_hashes));
+
+        // the infos of the session have no .torrent file
+        let infos = ses.get_torrents();
+        assert!(infos[0].torrent_file().is_empty());
+        assert_eq!(infos[0].info_hash, v1);
+        assert!(ses.get_all_torrent_status().iter().all(|s| s.info_hash == v1));
+        assert!(matches!(
+            ses.add_torrent_info(&info, &params),
+            Err(LTError::DuplicateTorrent(info_hash)) if info_hash == v1
+        ));
+        assert!(added.handle.get_torrent_info().unwrap().torrent_file().is_empty());
+
+        let other = create_default_session().unwrap();
+        assert!(matches!(
+            other.add_torrent_info(&infos[0], &params),
+            Err(LTError::InvalidMetadata(_))
+        ));
+        let bytes = added.handle.get_torrent_file().unwrap();
+        let readded = other.add_torrent_bytes(&bytes, &params).unwrap();
+        assert!(readded.info_hashes.matches(&info.info_hashes));
+        assert_eq!(TorrentInfo::from_bytes(&bytes).unwrap().info_hashes, info.info_hashes);
+    }
+
+    #[test]
+    fn test_torrent_builder() {
+        let dir = TempDir::new("torrent-builder");
+        std::fs::write(dir.join("file.bin"), vec![7u8; 200_000]).unwrap();
+
+        let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let calls = progress.clone();
+        let torrent = TorrentBuilder::new(dir.join("file.bin"))
+            .piece_size(16 * 1024)
+            .version(TorrentVersion::V1Only)
+            .tracker("udp://tracker.example.com:6969", 0)
+            .web_seed("https://example.com/file.bin")
+            .comment("comment")
+            .creator("creator")
+            .private(true)
+            .progress(move |hashed, num_pieces| {
+                calls.lock().unwrap().push((hashed, num_pieces));
+                ControlFlow::Continue(())
+            })
+            .build()
+            .unwrap();
+
+        let info = TorrentInfo::from_bytes(&torrent).unwrap();
+        assert_eq!(info.name, "file.bin");
+        assert_eq!(info.total_size, 200_000);
+        assert_eq!(info.piece_length, 16 * 1024);
+        assert_eq!(info.num_pieces, 13);
+        assert!(info.info_hashes.has_v1() && !info.info_hashes.has_v2());
+        assert_eq!(info.trackers, vec!["udp://tracker.example.com:6969".to_string()]);
+        assert_eq!(info.web_seeds, vec!["https://example.com/file.bin".to_string()]);
+        assert_eq!(info.comment, "comment");
+        assert_eq!(info.creator, "creator");
+        assert!(info.is_private);
+
+        // called once per piece, in order
+        let progress = progress.lock().unwrap().clone();
+        assert_eq!(progress, (1..=13).map(|hashed| (hashed, 13)).collect::<Vec<_>>());
+
+        // cancelled after the first piece, the callback is not called again
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counter = calls.clone();
+        let cancelled = TorrentBuilder::new(dir.join("file.bin"))
+            .piece_size(16 * 1024)
+            .progress(move |_, _| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                ControlFlow::Break(())
+            })
+            .build();
+        assert!(matches!(cancelled, Err(LTError::Cancelled)));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        for piece_size in [1000, 8 * 1024, 1 << 30, 1 << 31] {
+            assert!(matches!(
+                TorrentBuilder::new(dir.join("file.bin")).piece_size(piece_size).build(),
+                Err(LTError::InvalidTorrentBuilderParam("piece_size", _))
+            ));
+        }
+        assert!(matches!(
+            TorrentBuilder::new(dir.join("file.bin"))
+                .tracker("ws://tracker.example.com", 0)
+                .build(),
+            Err(LTError::InvalidTorrentBuilderParam("tracker", _))
+        ));
+        assert!(matches!(
+            TorrentBuilder::new(dir.join("file.bin"))
+                .web_seed("https:///file.bin")
+                .build(),
+            Err(LTError::InvalidTorrentBuilderParam("web_seed", _))
+        ));
+        assert!(matches!(
+            TorrentBuilder::new(dir.join("missing")).build(),
+            Err(LTError::FailedToCreateTorrent(_))
+        ));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_log() {
+        use std::{
+            collections::HashMap,
+            fmt,
+            sync::{Arc, Mutex},
+        };
+        use tracing::{
+            Event, Level, Metadata, Subscriber,
+            field::{Field, Visit},
+            span,
+        };
+
+        #[derive(Debug)]
+        struct Captured {
+            level: Level,
+            target: String,
+            fields: HashMap<&'static str, String>,
+        }
+
+        struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+        impl Visit for Fields<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.insert(field.name(), format!("{value:?}"));
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<Captured>>>);
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = HashMap::new();
+                event.record(&mut Fields(&mut fields));
+                self.0.lock().unwrap().push(Captured {
+                    level: *event.metadata().level(),
+                    target: event.metadata().target().to_string(),
+                    fields,
+                });
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        fn log(level: ffi::LogLevel, target: ffi::LogTarget, info_hash: &str) -> ffi::Log {
+            ffi::Log {
+                message: format!("{level:?} {target:?}"),
+                timestamp: 0,
+                alert_type: if target == ffi::LogTarget::Wrapper {
+                    ""
+                } else {
+                    "torrent_log"
+                }
+                .to_string(),
+                category: 0,
+                level,
+                target,
+                info_hash: info_hash.to_string(),
+                torrent_name: String::new(),
+                peer: String::new(),
+            }
+        }
+
+        let hash = "771cc76f2c725648b20a74d27ffeba2458a88343";
+        let logs = [
+            (
+                ffi::LogLevel::Error,
+                ffi::LogTarget::Torrent,
+                Level::ERROR,
+                "libtorrent::torrent",
+            ),
+            (
+                ffi::LogLevel::Warn,
+                ffi::LogTarget::Peer,
+                Level::WARN,
+                "libtorrent::peer",
+            ),
+            (ffi::LogLevel::Info, ffi::LogTarget::Dht, Level::INFO, "libtorrent::dht"),
+            (
+                ffi::LogLevel::Debug,
+                ffi::LogTarget::Tracker,
+                Level::DEBUG,
+                "libtorrent::tracker",
+            ),
+            (
+                ffi::LogLevel::Trace,
+                ffi::LogTarget::Storage,
+                Level::TRACE,
+                "libtorrent::storage",
+            ),
+            (
+                ffi::LogLevel::Trace,
+                ffi::LogTarget::PortMapping,
+                Level::TRACE,
+                "libtorrent::port_mapping",
+            ),
+            (
+                ffi::LogLevel::Info,
+                ffi::LogTarget::Session,
+                Level::INFO,
+                "libtorrent::session",
+            ),
+            (
+                ffi::LogLevel::Warn,
+                ffi::LogTarget::Wrapper,
+                Level::WARN,
+                "libtorrent_rasterbar_rs",
+            ),
+        ];
+
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            for (level, target, _, _) in &logs {
+                crate::tracing_log::emit(log(*level, *target, hash));
+            }
+        });
+
+        let captured = capture.0.lock().unwrap();
+        assert_eq!(captured.len(), logs.len());
+        for ((level, target, expected_level, expected_target), event) in logs.iter().zip(captured.iter()) {
+            assert_eq!(event.level, *expected_level);
+            assert_eq!(event.target, *expected_target);
+            assert_eq!(event.fields["message"], format!("{level:?} {target:?}"));
+            assert_eq!(event.fields["info_hash"], hash);
+            // empty fields aren't recorded
+            assert!(!event.fields.contains_key("torrent_name"));
+            assert!(!event.fields.contains_key("peer"));
+            if *target == ffi::LogTarget::Wrapper {
+                assert!(!event.fields.contains_key("alert_type"));
+            } else {
+                assert_eq!(event.fields["alert_type"], "torrent_log");
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::{ops::ControlFlow, path::PathBuf};
+
+use libtorrent_rasterbar_sys::{
+    CreateTorrentProgress,
+    ffi::{CreateTorrentParams, create_torrent},
+    flags::TorrentFlags,
+};
+
+pub use libtorrent_rasterbar_sys::ffi::TorrentVersion;
+
+use crate::{AddTorrentParams, AddedTorrent, LTError, LTResult, LTSession, add_torrent_params::check_url};
+
+type ProgressCallback = Box<dyn FnMut(u32, u32) -> ControlFlow<()> + Send>;
+
+/// ``file_storage::max_piece_size`` of libtorrent, 512 MiB
+const MAX_PIECE_SIZE: u32 = 512 * 1024 * 1024;
+
+/// libtorrent/create_torrent.hpp
+///
+/// creates a .torrent file from a file or a directory, wrapping
+/// ``create_torrent`` and ``set_piece_hashes``.
+pub struct TorrentBuilder {
+    path: PathBuf,
+    piece_size: u32,
+    version: TorrentVersion,
+    trackers: Vec<(String, u8)>,
+    web_seeds: Vec<String>,
+    comment: String,
+    creator: String,
+    is_private: bool,
+    progress: Option<ProgressCallback>,
+}
+
+impl TorrentBuilder {
+    /// creates a builder for the file or directory at ``path``
+    pub fn new(path: impl Into<PathBuf>) -> TorrentBuilder {
+        TorrentBuilder {
+            path: path.into(),
+            piece_size: 0,
+            version: TorrentVersion::Hybrid,
+            trackers: Vec::new(),
+            web_seeds: Vec::new(),
+            comment: String::new(),
+            creator: String::new(),
+            is_private: false,
+            progress: None,
+        }
+    }
+
+    /// the size of each piece in bytes. It must be a power of 2 from 16 kiB to
+    /// 512 MiB. By default (0) a piece size is picked based on the total size.
+    pub fn piece_size(mut self, piece_size: u32) -> Self {
+        self.piece_size = piece_size;
+        self
+    }
+
+    /// v1-only, v2-only or hybrid (the default) metadata
+    pub fn version(mut self, version: TorrentVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// adds a tracker (http, https or udp) in the given tier
+    pub fn tracker(mut self, url: impl Into<String>, tier: u8) -> Self {
+        self.trackers.push((url.into(), tier));
+        self
+    }
+
+    /// adds a web seed (BEP 19), an http or https url
+    pub fn web_seed(mut self, url: impl Into<String>) -> Self {
+        self.web_seeds.push(url.into());
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    pub fn creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = creator.into();
+        self
+    }
+
+    /// sets the private flag, disabling DHT, PEX and local peer discovery
+    pub fn private(mut self, is_private: bool) -> Self {
+        self.is_private = is_private;
+        self
+    }
+
+    /// called with the number of hashed pieces and the total number of
+    /// pieces while hashing. Returning ``ControlFlow::Break`` cancels the
+    /// creation, ``build`` then fails with ``LTError::Cancelled``.
+    pub fn progress<F>(mut self, progress: F) -> Self
+    where
+        F: FnMut(u32, u32) -> ControlFlow<()> + Send + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// hashes the files and returns the bencoded .torrent file.
+    pub fn build(self) -> LTResult<Vec<u8>> {
+        let params = self.create_torrent_params()?;
+
+        let progress = match self.progress {
+            Some(mut f) => CreateTorrentProgress::new(move |hashed, num_pieces| f(hashed, num_pieces).is_continue()),
+            None => CreateTorrentProgress::noop(),
+        };
+
+        create_torrent(&params, &progress).map_err(|e| {
+            if progress.is_cancelled() {
+                LTError::Cancelled
+            } else {
+                LTError::FailedToCreateTorrent(e.to_string())
+            }
+        })
+    }
+
+    /// builds the torrent and adds it to ``session`` in seed mode, with the
+    /// parent directory of the path as save path. Returns the .torrent file
+    /// along with the added torrent.
+    pub fn add_to_session(self, session: &LTSession) -> LTResult<(Vec<u8>, AddedTorrent)> {
+        let path = std::path::absolute(&self.path).map_err(|e| LTError::Io(self.path.clone(), e))?;
+        let save_path = path
+            .parent()
+            .and_then(|p| p.to_str())
+            .ok_or_else(|| invalid("path", format!("{path:?} has no parent directory")))?
+            .to_string();
+
+        let torrent = self.build()?;
+
+        let params = AddTorrentParams::builder()
+            .save_path(save_path)
+            .flags(
+                TorrentFlags::seed_mode
+                    | TorrentFlags::auto_managed
+                    | TorrentFlags::apply_ip_filter
+                    | TorrentFlags::update_subscribe
+                    | TorrentFlags::need_save_resume,
+            )
+            .build()?;
+
+        let added = session.add_torrent_bytes(&torrent, &params)?;
+        Ok((torrent, added))
+    }
+
+    fn create_torrent_params(&self) -> LTResult<CreateTorrentParams> {
+        let Some(path) = self.path.to_str() else {
+            return Err(invalid("path", format!("{:?} is not valid UTF-8", self.path)));
+        };
+
+        if self.piece_size != 0
+            && (!(16 * 1024..=MAX_PIECE_SIZE).contains(&self.piece_size) || !self.piece_size.is_power_of_two())
+        {
+            return Err(invalid(
+                "piece_size",
+                format!("{} is not a power of 2 from 16 kiB to 512 MiB", self.piece_size),
+            ));
+        }
+
+        for (url, _) in &self.trackers {
+            check_url(url, &["http", "https", "udp"]).map_err(|reason| invalid("tracker", reason))?;
+        }
+
+        for url in &self.web_seeds {
+            check_url(url, &["http", "https"]).map_err(|reason| invalid("web_seed", reason))?;
+        }
+
+        Ok(CreateTorrentParams {
+            path: path.to_string(),
+            piece_size: self.piece_size as i32,
+            version: self.version,
+            trackers: self.trackers.iter().map(|(url, _)| url.clone()).collect(),
+            tracker_tiers: self.trackers.iter().map(|(_, tier)| *tier as i32).collect(),
+            web_seeds: self.web_seeds.clone(),
+            comment: self.comment.clone(),
+            creator: self.creator.clone(),
+            is_private: self.is_private,
+        })
+    }
+}
+
+fn invalid(param: &'static str, reason: String) -> LTError {
+    LTError::InvalidTorrentBuilderParam(param, reason)
+}
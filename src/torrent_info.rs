@@ -1,6 +1,10 @@
-use libtorrent_rasterbar_sys::ffi;
+use std::path::Path;
+
+use libtorrent_rasterbar_sys::ffi::{self, TorrentInfoError, parse_torrent_info};
 use serde::Serialize;
 
+use crate::{InfoHashes, LTError, LTResult};
+
 #[derive(Serialize, Debug)]
 pub struct FileEntry {
     pub file_path: String,
@@ -64,11 +68,15 @@ pub struct TorrentInfo {
     /// may be fewer in the last piece)
     pub blocks_per_piece: u32,
 
-    /// the info-hash of the torrent. For BitTorrent v2 support, use
-    /// ``info_hashes()`` to get an object that may hold both a v1 and v2
-    /// info-hash
+    /// the info-hash of the torrent as hex, the v1 info-hash or the v2
+    /// info-hash of a v2-only torrent, like ``InfoHashes::best``. It
+    /// identifies the torrent in ``LTSession::get_torrent_handle``, the logs
+    /// and the ``ResumeStore``.
     pub info_hash: String,
 
+    /// the v1 and v2 info-hashes, both are set for a hybrid torrent
+    pub info_hashes: InfoHashes,
+
     /// the number of files in the torrent
     pub num_files: u32,
 
@@ -97,6 +105,55 @@ pub struct TorrentInfo {
     /// Flags indicating torrent properties
     pub is_private: bool, // True if this is a private torrent
     pub is_i2p: bool, // True if this is an i2p torrent
+
+    /// the bencoded .torrent file, see ``LTSession::add_torrent_info``. Only
+    /// set by ``from_bytes`` and ``from_file``.
+    #[serde(skip)]
+    pub(crate) torrent_file: Vec<u8>,
+}
+
+impl TorrentInfo {
+    /// parses the bencoded contents of a .torrent file. No session is
+    /// needed.
+    ///
+    /// Fails with ``LTError::InvalidBencode``, ``LTError::MissingInfoDict``,
+    /// ``LTError::InvalidPieceLength`` or, for any other problem with the
+    /// torrent, ``LTError::InvalidMetadata``.
+    pub fn from_bytes(data: &[u8]) -> LTResult<TorrentInfo> {
+        let r = parse_torrent_info(data);
+        let message = r.error.message;
+
+        match r.error_kind {
+            TorrentInfoError::None => Ok(r.info.into()),
+            TorrentInfoError::InvalidBencode => Err(LTError::InvalidBencode(message)),
+            TorrentInfoError::MissingInfoDict => Err(LTError::MissingInfoDict(message)),
+            TorrentInfoError::InvalidPieceLength => Err(LTError::InvalidPieceLength(message)),
+            _ => Err(LTError::InvalidMetadata(message)),
+        }
+    }
+
+    /// reads and parses a .torrent file, see ``from_bytes``.
+    pub fn from_file(path: impl AsRef<Path>) -> LTResult<TorrentInfo> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|e| LTError::Io(path.to_path_buf(), e))?;
+        TorrentInfo::from_bytes(&data)
+    }
+
+    /// the bencoded .torrent file. Empty for the infos of the torrents of a
+    /// session, see ``LTTorrentHandle::get_torrent_file``.
+    pub fn torrent_file(&self) -> &[u8] {
+        &self.torrent_file
+    }
+
+    pub(crate) fn torrent_bytes(&self) -> LTResult<&[u8]> {
+        if self.torrent_file.is_empty() {
+            return Err(LTError::InvalidMetadata(format!(
+                "no torrent file for {}, see LTTorrentHandle::get_torrent_file",
+                self.info_hash
+            )));
+        }
+        Ok(&self.torrent_file)
+    }
 }
 
 impl From<ffi::TorrentInfo> for TorrentInfo {
@@ -113,6 +170,7 @@ impl From<ffi::TorrentInfo> for TorrentInfo {
             num_pieces: t.num_pieces,
             blocks_per_piece: t.blocks_per_piece,
             info_hash: t.info_hash,
+            info_hashes: t.info_hashes.into(),
             num_files: t.num_files,
             name: t.name,
             creation_date: t.creation_date,
@@ -121,6 +179,7 @@ impl From<ffi::TorrentInfo> for TorrentInfo {
             ssl_cert: t.ssl_cert,
             is_private: t.is_private,
             is_i2p: t.is_i2p,
+            torrent_file: t.torrent_file,
         }
     }
 }
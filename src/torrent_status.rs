@@ -3,6 +3,8 @@
 use libtorrent_rasterbar_sys::ffi;
 use serde::Serialize;
 
+use crate::InfoHashes;
+
 /// the different overall states a torrent can be in
 #[repr(u8)]
 pub enum State {
@@ -351,8 +353,9 @@ pub struct TorrentStatus {
     pub announcing_to_lsd: bool,
     pub announcing_to_dht: bool,
 
-    /// the info-hash for this torrent
+    /// the info-hash for this torrent, see ``TorrentInfo::info_hash``
     pub info_hash: String,
+    pub info_hashes: InfoHashes,
 
     /// the timestamps of the last time this torrent uploaded or downloaded
     /// payload to any peer.
@@ -442,6 +445,7 @@ impl From<ffi::TorrentStatus> for TorrentStatus {
             announcing_to_lsd: ts.announcing_to_lsd,
             announcing_to_dht: ts.announcing_to_dht,
             info_hash: ts.info_hash,
+            info_hashes: ts.info_hashes.into(),
             last_upload: ts.last_upload,
             last_download: ts.last_download,
             active_duration: ts.active_duration,
@@ -0,0 +1,47 @@
+use libtorrent_rasterbar_sys::ffi::{Log, LogLevel, LogTarget};
+
+macro_rules! event {
+    ($target:literal, $log:ident) => {
+        match $log.level {
+            LogLevel::Error => event!(@ $target, ERROR, $log),
+            LogLevel::Warn => event!(@ $target, WARN, $log),
+            LogLevel::Info => event!(@ $target, INFO, $log),
+            LogLevel::Debug => event!(@ $target, DEBUG, $log),
+            _ => event!(@ $target, TRACE, $log),
+        }
+    };
+    (@ $target:literal, $level:ident, $log:ident) => {
+        tracing::event!(
+            target: $target,
+            tracing::Level::$level,
+            alert_type = non_empty(&$log.alert_type),
+            info_hash = non_empty(&$log.info_hash),
+            torrent_name = non_empty(&$log.torrent_name),
+            peer = non_empty(&$log.peer),
+            "{}",
+            $log.message
+        )
+    };
+}
+
+/// forwards a log message of a session as a tracing event. The target is
+/// ``libtorrent::<category>`` for alerts, e.g. ``libtorrent::peer`` for
+/// peer_log_alerts, and ``libtorrent_rasterbar_rs`` for the diagnostics of
+/// the wrapper.
+pub(crate) fn emit(log: Log) {
+    match log.target {
+        LogTarget::Session => event!("libtorrent::session", log),
+        LogTarget::Torrent => event!("libtorrent::torrent", log),
+        LogTarget::Peer => event!("libtorrent::peer", log),
+        LogTarget::Dht => event!("libtorrent::dht", log),
+        LogTarget::Tracker => event!("libtorrent::tracker", log),
+        LogTarget::Storage => event!("libtorrent::storage", log),
+        LogTarget::PortMapping => event!("libtorrent::port_mapping", log),
+        _ => event!("libtorrent_rasterbar_rs", log),
+    }
+}
+
+/// empty fields aren't recorded
+fn non_empty(s: &str) -> Option<&str> {
+    (!s.is_empty()).then_some(s)
+}